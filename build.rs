@@ -1,3 +1,4 @@
 fn main() {
+    #[cfg(feature = "hardware")]
     embuild::espidf::sysenv::output();
 }
@@ -1,111 +1,295 @@
 use anyhow::{anyhow, Result};
+use esp_idf_hal::{
+    gpio::{AnyOutputPin, Output, PinDriver},
+    uart::UartTxDriver,
+};
 use esp_idf_svc::log::EspLogger;
-use log::info;
-use std::sync::{Arc, Mutex};
+use log::{debug, error, info, warn};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
 
 use esp_flow::{
-    gps::{Reading, Sensor},
-    infra::{Poller, Switch},
-    thread,
+    clock::Clock,
+    gps::{
+        self, Config as GpsConfig, MotionDetector, NmeaSource, Protocol, Reading,
+        ReplaySource, Sensor, SpeedTracker, Stats, Units, DEFAULT_FIX_TIMEOUT_MS,
+        DEFAULT_READ_FAILURE_THRESHOLD,
+    },
+    infra::{self, Poller},
+    message::Notifier,
+    thread::{self, Faults},
 };
 
 mod common;
 use common::{
-    hw::Context,
-    logic::{trace_func, Core, DeviceNearby, State, Trigger},
+    hw::{self, Context},
+    logic::{trace_func, Core, State, Trigger},
 };
 
+// Readings older than this are ignored rather than advertised.
+const GPS_MAX_FIX_AGE_MS: u64 = 5000;
+
+// Recorded ride replayed instead of the GPS module when GPS_REPLAY is set.
+const GPS_REPLAY_DATA: &[u8] = include_bytes!("data/ride.nmea");
+
+// Triggers handled by the client on top of the common ones.
+const HANDLED_TRIGGERS: [&Trigger; 7] = [
+    &Trigger::GpsFixLost,
+    &Trigger::GpsFixAcquired,
+    &Trigger::GpsDataAvailable,
+    &Trigger::MotionStarted,
+    &Trigger::MotionStopped,
+    &Trigger::UpdateRequested,
+    &Trigger::ThreadFaulted,
+];
+
+// Delay between replayed sentences, i.e. a GGA and an RMC sentence per second.
+const GPS_REPLAY_LINE_INTERVAL_MS: u32 = 500;
+
+// Returns the units readings are logged in from UNITS, metric unless set to
+// "imperial".
+fn display_units() -> Units {
+    match option_env!("UNITS") {
+        Some(units) if units.eq_ignore_ascii_case("imperial") => Units::Imperial,
+        _ => Units::Metric,
+    }
+}
+
 // State machine for the client device (GPS tracking, BLE advertising).
-struct StateMachine<'a> {
-    core: Core<'a>,
+struct StateMachine<'a, C: Clock> {
+    core: Core<'a, C>,
     location: Arc<Mutex<Option<Reading>>>,
-    max_speed_mps: f32,
+    gps_stats: Arc<Mutex<Stats>>,
+    last_reading: Option<Reading>,
+    speed_tracker: SpeedTracker,
+    faults: Faults,
 }
 
-impl<'a> StateMachine<'a> {
+impl<'a, C: Clock> StateMachine<'a, C> {
     // Creates a new client state machine.
-    fn new(core: Core<'a>, location: Arc<Mutex<Option<Reading>>>) -> Self {
+    // The device turns off after `idle_timeout_s` seconds without finding a
+    // device, zero disables auto-off. `faults` are the ones recorded by the
+    // supervised GPS thread.
+    fn new(
+        mut core: Core<'a, C>,
+        location: Arc<Mutex<Option<Reading>>>,
+        gps_stats: Arc<Mutex<Stats>>,
+        button_state: Arc<Mutex<State>>,
+        idle_timeout_s: u64,
+        faults: Faults,
+    ) -> Self {
+        core.set_idle_timeout(idle_timeout_s, button_state);
+
         Self {
             core,
             location,
-            max_speed_mps: 0.0,
+            gps_stats,
+            last_reading: None,
+            speed_tracker: SpeedTracker::default(),
+            faults,
         }
     }
 
-    // Custom button handler that also resets the max speed.
+    // Logs the errors supervised threads stopped with. Only the GPS thread is
+    // supervised, so the device keeps running with tracking disabled.
+    fn handle_faults(faults: &Faults) {
+        faults.take().iter().for_each(|fault| {
+            error!(
+                "Thread {} stopped: {}, continuing without it",
+                fault.thread(),
+                fault.message()
+            );
+        });
+    }
+
+    // Custom button handler that also resets the speed tracker.
     fn handle_button_pressed(
-        core: &mut Core<'_>,
-        max_speed_mps: &mut f32,
+        core: &mut Core<'_, C>,
+        speed_tracker: &mut SpeedTracker,
+    ) -> Result<()> {
+        trace_func!();
+
+        core.transition(&Trigger::ButtonPressed);
+        if core.state.is_on() {
+            speed_tracker.reset();
+        }
+
+        core.sync_advertiser()
+    }
+
+    // Logs movement starts and stops, with the speeds tracked so far on stops.
+    fn handle_motion(
+        triggers: &HashSet<&'static Trigger>,
+        speed_tracker: &SpeedTracker,
+    ) {
+        if triggers.contains(&Trigger::MotionStarted) {
+            info!("Started moving");
+        }
+        if triggers.contains(&Trigger::MotionStopped) {
+            info!("Stopped moving, {}", speed_tracker.summary());
+        }
+    }
+
+    // Logs, tracks and advertises the latest GPS reading, unless stale.
+    fn handle_gps_data(
+        core: &mut Core<'_, C>,
+        location: &Mutex<Option<Reading>>,
+        gps_stats: &Mutex<Stats>,
+        last_reading: &mut Option<Reading>,
+        speed_tracker: &mut SpeedTracker,
+        units: Units,
     ) -> Result<()> {
         trace_func!();
 
-        if core.state.is_off() {
-            *max_speed_mps = 0.0;
-            core.state = State::on();
-        } else {
-            core.state = State::off();
+        let mut data = infra::lock(location);
+
+        if let Some(reading) = data
+            .take()
+            .filter(|reading| !reading.is_stale(GPS_MAX_FIX_AGE_MS))
+        {
+            info!(
+                "GPS Reading: {}, fix: {:?}, HDOP: {:?}, age: {} ms",
+                reading.format_with(units),
+                reading.fix_type(),
+                reading.hdop(),
+                reading.age_ms()
+            );
+            debug!("GPS link: {}", infra::lock(gps_stats));
+            if let Some(last) = last_reading.as_ref() {
+                info!(
+                    "Moved {:.1} m towards {:.0}° from ({:.6}, {:.6}), altitude: {:?} m",
+                    gps::haversine(last, &reading),
+                    gps::bearing(last, &reading),
+                    last.latitude(),
+                    last.longitude(),
+                    reading.altitude()
+                );
+            }
+            speed_tracker.update(&reading);
+            let summary = speed_tracker.summary();
+            info!("Speed summary: {summary}");
+            let bytes = reading.to_bytes().to_vec();
+            info!("Advertising {} bytes: {bytes:?} ({reading})", bytes.len());
+            core.advertiser.set_payload(Some(bytes))?;
+            *last_reading = Some(reading);
         }
 
-        core.advertiser.toggle()
+        Ok(())
+    }
+
+    // Returns the current state. Only usable from the thread running the state
+    // machine; other threads should read `Core::status` instead.
+    #[allow(dead_code)]
+    fn state(&self) -> &State {
+        &self.core.state
     }
 
     // Runs the state machine.
     fn run(&mut self) -> Result<()> {
-        let max_speed_mps = &mut self.max_speed_mps;
+        let speed_tracker = &mut self.speed_tracker;
         let location = &self.location;
+        let gps_stats = &self.gps_stats;
+        let last_reading = &mut self.last_reading;
+        let faults = &self.faults;
+        let units = display_units();
 
         self.core.run(|core, triggers| {
-            if core.handle_common_triggers(
+            // Faults are checked on every iteration since their trigger may
+            // come along with others handled first
+            Self::handle_faults(faults);
+
+            let remaining = core.handle_common_triggers(
                 triggers,
-                |c| Self::handle_button_pressed(c, max_speed_mps),
+                |c| Self::handle_button_pressed(c, speed_tracker),
                 |c| {
                     trace_func!();
-                    // Only change state if not already Off or ActiveDeviceNearby
-                    if c.state.is_on()
-                        && !matches!(c.state, State::On(Some(DeviceNearby::Active)))
-                    {
-                        c.state = State::On(Some(DeviceNearby::Active));
-                    }
+                    c.transition(&Trigger::DeviceFoundActive);
                     Ok(())
                 },
-            )? {
-                Ok(())
-            } else if triggers.contains(&Trigger::GpsDataAvailable) {
-                let mut data = location
-                    .lock()
-                    .map_err(|e| anyhow!("Mutex lock error: {:?}", e))?;
-
-                if let Some(reading) = data.take() {
-                    info!("GPS Reading: {}", reading);
-                    if let Some(speed) = reading.speed_mps() {
-                        if speed > *max_speed_mps {
-                            *max_speed_mps = speed;
-                        }
-                    }
-                    let payload = (*max_speed_mps > 0.0).then(|| {
-                        let bytes = max_speed_mps.to_le_bytes().to_vec();
-                        let kmph = *max_speed_mps * 3.6;
-                        info!(
-                            "Advertising {} bytes: {:?} (max_speed: {kmph:.2} km/h)",
-                            bytes.len(),
-                            bytes
-                        );
-                        bytes
-                    });
-                    core.advertiser.set_payload(payload)?;
-                }
-                Ok(())
-            } else {
-                Err(anyhow!("Unknown triggers: {:?}", triggers))
+            )?;
+            if remaining.contains(&Trigger::GpsFixLost) {
+                warn!("GPS fix lost, waiting for it to come back");
+                *last_reading = None;
+            }
+            if remaining.contains(&Trigger::GpsFixAcquired) {
+                info!("GPS fix acquired");
             }
+            if remaining.contains(&Trigger::GpsDataAvailable) {
+                Self::handle_gps_data(
+                    core,
+                    location,
+                    gps_stats,
+                    last_reading,
+                    speed_tracker,
+                    units,
+                )?;
+            }
+            Self::handle_motion(triggers, speed_tracker);
+            if remaining.contains(&Trigger::UpdateRequested) {
+                warn!("OTA updates require Wi-Fi, ignoring update request");
+            }
+
+            remaining
+                .iter()
+                .find(|trigger| !HANDLED_TRIGGERS.contains(trigger))
+                .map_or(Ok(()), |trigger| {
+                    Err(anyhow!("Unknown trigger: {trigger:?}"))
+                })
         })
     }
 }
 
+// Configures the GPS sensor reading from `source`, returning it ready to poll
+// along with its link statistics.
+fn gps_sensor<S: NmeaSource + Send + 'static>(
+    source: S,
+    notifier: Notifier<Trigger>,
+    button_state: &Arc<Mutex<State>>,
+    tx: Option<UartTxDriver<'static>>,
+    config: GpsConfig,
+    enable: Option<PinDriver<'static, AnyOutputPin, Output>>,
+    location: &Arc<Mutex<Option<Reading>>>,
+) -> Result<(Box<dyn Poller + Send>, Arc<Mutex<Stats>>)> {
+    let mut gps = Sensor::new(
+        notifier,
+        &Trigger::GpsDataAvailable,
+        Arc::clone(button_state),
+        source,
+        tx,
+        config,
+        Arc::clone(location),
+    )
+    .with_fix_triggers(
+        &Trigger::GpsFixLost,
+        &Trigger::GpsFixAcquired,
+        DEFAULT_FIX_TIMEOUT_MS,
+    )
+    .with_motion_triggers(
+        &Trigger::MotionStarted,
+        &Trigger::MotionStopped,
+        MotionDetector::default(),
+    )
+    .with_error_triggers(
+        &Trigger::GpsError,
+        &Trigger::GpsRecovered,
+        DEFAULT_READ_FAILURE_THRESHOLD,
+    )
+    .with_enable_pin(enable)?;
+    gps.configure_module()?;
+    if let Some(commands) = option_env!("GPS_COMMANDS") {
+        gps.configure(&commands.split(';').collect::<Vec<_>>())?;
+    }
+    let gps_stats = gps.stats();
+
+    Ok((Box::new(gps), gps_stats))
+}
+
 fn main() -> ! {
-    thread::main(|| {
-        EspLogger::initialize_default();
+    EspLogger::initialize_default();
 
+    thread::main(|| {
         // Setup common context (peripherals, threads, etc.)
         let context = Context::try_default()?;
 
@@ -115,26 +299,68 @@ fn main() -> ! {
             dispatcher,
             advertiser,
             led,
+            _,
             led_timer,
+            _,
             gps_notifier,
             button_state,
-            uart_driver,
+            (uart_driver, uart_tx_driver, gps_config),
+            gps_enable,
             _,
             _,
+            deep_sleep_gpio,
         ) = context.into_parts();
 
-        let mut gps = Sensor::new(
-            gps_notifier,
-            &Trigger::GpsDataAvailable,
-            button_state,
-            uart_driver,
-            Arc::clone(&location),
+        // GPS_REPLAY exercises the whole pipeline indoors, without a fix; the
+        // recording is NMEA whatever the module speaks
+        let (mut gps, gps_stats) = match option_env!("GPS_REPLAY") {
+            Some(_) => gps_sensor(
+                ReplaySource::new(GPS_REPLAY_DATA, GPS_REPLAY_LINE_INTERVAL_MS),
+                gps_notifier,
+                &button_state,
+                uart_tx_driver,
+                gps_config.with_protocol(Protocol::Nmea),
+                gps_enable,
+                &location,
+            )?,
+            None => gps_sensor(
+                uart_driver,
+                gps_notifier,
+                &button_state,
+                uart_tx_driver,
+                gps_config,
+                gps_enable,
+                &location,
+            )?,
+        };
+        // A faulting GPS thread only disables tracking, the rest keeps running
+        let faults = Faults::new();
+        thread::spawn_supervised(
+            "gps",
+            &faults,
+            dispatcher.notifier()?,
+            &Trigger::ThreadFaulted,
+            move || gps.poll(),
         );
-        thread::spawn(move || gps.poll());
 
         // Create and run state machine with location
-        let core = Core::new(State::on(), dispatcher, advertiser, led, led_timer)?;
-        let mut sm = StateMachine::new(core, location);
+        let core = Core::builder()
+            .dispatcher(dispatcher)
+            .advertiser(advertiser)
+            .led(led)
+            .timer(led_timer)
+            .battery_warning(hw::battery_warning_color()?)
+            .gps_warning(hw::gps_error_color()?)
+            .deep_sleep(deep_sleep_gpio)
+            .build()?;
+        let mut sm = StateMachine::new(
+            core,
+            location,
+            gps_stats,
+            button_state,
+            hw::idle_timeout_s()?,
+            faults,
+        );
 
         sm.run()
     })
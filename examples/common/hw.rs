@@ -1,45 +1,92 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use esp32_nimble::enums::PowerLevel;
 use esp_idf_hal::{
-    gpio::{self, PinDriver},
+    gpio::{AnyIOPin, AnyOutputPin, Output, Pin, PinDriver},
     modem::Modem,
     prelude::Peripherals,
     rmt::{config::TransmitConfig, TxRmtDriver},
     timer::{TimerConfig, TimerDriver},
-    uart::{self, UartRxDriver},
-    units::Hertz,
+    uart::{UartDriver, UartRxDriver, UartTxDriver},
+};
+use std::{
+    collections::HashMap,
+    rc::Rc,
+    sync::{Arc, Mutex},
 };
-use std::sync::{Arc, Mutex};
 
 use esp_flow::{
+    battery::BatteryMonitor,
     ble::{self, Advertiser, Scanner, ScannerConfig},
-    button::Button,
-    clock::Timer,
+    button::{Button, TouchButton},
+    clock::{Clock, RtcTimer, Scheduler, Timer},
+    color::{ChannelOrder, Rgb, AMBER, RED},
+    gps::Config as GpsConfig,
     infra::{Poller, State},
-    light::Led,
+    light::{Led, LedKind},
     message::{Dispatcher, Notifier},
+    protocol,
     thread::spawn,
 };
 
 use super::logic::Trigger;
 
-const BLE_ACTIVE_SUFFIX: &str = "-Active";
-const BLE_INACTIVE_SUFFIX: &str = "-Inactive";
 const BLE_POWER_LEVEL: PowerLevel = PowerLevel::N0;
 const BLE_SCAN_FREQ_HZ: u64 = 1;
-const BLINK_FREQ_HZ: u64 = 3;
+pub const BLINK_FREQ_HZ: u64 = 3;
+pub const LOW_BATTERY_BLINK_FREQ_HZ: u64 = 1;
+pub const GPS_ERROR_BLINK_FREQ_HZ: u64 = 2;
+const BATTERY_DIVIDER_RATIO: f32 = 2.0;
+const SCHEDULER_BASE_HZ: u64 = 100;
+const TIMER_CALIBRATION_MS: u32 = 1000;
+const OTA_LONG_PRESS_MS: u64 = 3000;
+const DEFAULT_HEARTBEAT_INTERVAL_S: u64 = 60;
+const DEFAULT_IDLE_TIMEOUT_S: u64 = 0;
+
+// Returns the application name from APP_NAME, "esp-flow" when unset.
+pub fn app_name() -> &'static str {
+    option_env!("APP_NAME").unwrap_or("esp-flow")
+}
+
+// Reads the auto-off timeout from IDLE_TIMEOUT_S, zero (disabled) when unset.
+pub fn idle_timeout_s() -> Result<u64> {
+    Ok(option_env!("IDLE_TIMEOUT_S")
+        .map(str::parse::<u64>)
+        .transpose()?
+        .unwrap_or(DEFAULT_IDLE_TIMEOUT_S))
+}
+
+// Reads the low battery LED color from BATTERY_WARNING_COLOR, either a color
+// name or a hex color, amber when unset.
+pub fn battery_warning_color() -> Result<Rgb> {
+    option_env!("BATTERY_WARNING_COLOR").map_or(Ok(AMBER), |value| {
+        Rgb::from_name(value).map_or_else(|| Rgb::from_hex(value), Ok)
+    })
+}
+
+// Reads the GPS error LED color from GPS_ERROR_COLOR, either a color name or a
+// hex color, red when unset.
+#[allow(dead_code)]
+pub fn gps_error_color() -> Result<Rgb> {
+    option_env!("GPS_ERROR_COLOR").map_or(Ok(RED), |value| {
+        Rgb::from_name(value).map_or_else(|| Rgb::from_hex(value), Ok)
+    })
+}
 
 // Common hardware context shared by both server and client binaries.
 pub struct Context<'a> {
     dispatcher: Dispatcher<Trigger>,
     advertiser: Advertiser,
-    led: Led<'a>,
-    led_timer: Timer<'a, Trigger>,
+    led: Led<TxRmtDriver<'a>>,
+    status_led: Option<Led<TxRmtDriver<'a>>>,
+    led_timer: Box<dyn Clock + 'a>,
+    heartbeat_timer: Timer<'a, Trigger>,
     button_state: Arc<Mutex<State>>,
-    uart_driver: UartRxDriver<'a>,
+    gps_uart: (UartRxDriver<'a>, Option<UartTxDriver<'a>>, GpsConfig),
+    gps_enable: Option<PinDriver<'a, AnyOutputPin, Output>>,
     gps_notifier: Notifier<Trigger>,
     ble_payload: Arc<Mutex<Option<Vec<u8>>>>,
     modem: Modem,
+    deep_sleep_gpio: Option<i32>,
 }
 
 impl<'a> Context<'a> {
@@ -53,7 +100,9 @@ impl<'a> Context<'a> {
         let peripherals = Peripherals::take()?;
         let Peripherals {
             timer01: ble_timer_peripheral,
-            timer00: led_timer_peripheral,
+            timer00: scheduler_timer_peripheral,
+            timer10: heartbeat_timer_peripheral,
+            adc1: battery_adc,
             pins,
             rmt,
             uart2: uart_peripheral,
@@ -61,57 +110,152 @@ impl<'a> Context<'a> {
             ..
         } = peripherals;
         let button_peripheral = pins.gpio39;
+        // The button doubles as the deep sleep wakeup source when DEEP_SLEEP is set
+        let deep_sleep_gpio =
+            option_env!("DEEP_SLEEP").map(|_| button_peripheral.pin());
         let channel_peripheral = rmt.channel0;
         let led_peripheral = pins.gpio27;
-        let uart_rx = pins.gpio22;
+        let gps_config = GpsConfig::from_env()?;
+        let status_led_pin = option_env!("STATUS_LED_PIN")
+            .map(str::parse::<i32>)
+            .transpose()?;
+        let touch_button_pin = option_env!("TOUCH_BUTTON_PIN")
+            .map(str::parse::<i32>)
+            .transpose()?;
+        // Only the pins left free by the board can be selected for the GPS,
+        // its enable line, the status LED and the touch button
+        let mut free_pins: HashMap<i32, AnyIOPin> = HashMap::from([
+            (19, pins.gpio19.into()),
+            (21, pins.gpio21.into()),
+            (22, pins.gpio22.into()),
+            (23, pins.gpio23.into()),
+            (25, pins.gpio25.into()),
+            (26, pins.gpio26.into()),
+            (32, pins.gpio32.into()),
+        ]);
+        let mut take_pin = |pin: i32| {
+            free_pins
+                .remove(&pin)
+                .ok_or_else(|| anyhow!("GPIO {pin} is not free"))
+        };
+        let uart_rx = take_pin(gps_config.rx_pin())?;
+        let uart_tx = gps_config.tx_pin().map(&mut take_pin).transpose()?;
+        let gps_enable_peripheral =
+            gps_config.enable_pin().map(&mut take_pin).transpose()?;
+        let touch_button_peripheral =
+            touch_button_pin.map(&mut take_pin).transpose()?;
+        let status_led_peripheral = status_led_pin.map(take_pin).transpose()?;
+        let battery_pin = pins.gpio33;
 
         let dispatcher = Dispatcher::new()?;
         let ble_notifier = dispatcher.notifier()?;
         let button_notifier = dispatcher.notifier()?;
         let led_timer_notifier = dispatcher.notifier()?;
         let gps_notifier = dispatcher.notifier()?;
+        let heartbeat_notifier = dispatcher.notifier()?;
+        let battery_notifier = dispatcher.notifier()?;
 
         let timers_cfg = TimerConfig::new().auto_reload(true);
         let tx_rmt_cfg = TransmitConfig::new().clock_divider(1);
-        let uart_cfg = uart::config::Config::new().baudrate(Hertz(115_200));
 
         let ble_timer_driver = TimerDriver::new(ble_timer_peripheral, &timers_cfg)?;
-        let led_timer_driver = TimerDriver::new(led_timer_peripheral, &timers_cfg)?;
+        let scheduler_timer_driver =
+            TimerDriver::new(scheduler_timer_peripheral, &timers_cfg)?;
+        let heartbeat_timer_driver =
+            TimerDriver::new(heartbeat_timer_peripheral, &timers_cfg)?;
         let pin_driver = PinDriver::input(button_peripheral)?;
         let tx_rmt_driver =
             TxRmtDriver::new(channel_peripheral, led_peripheral, &tx_rmt_cfg)?;
-        let uart_driver = UartRxDriver::new(
-            uart_peripheral,
-            uart_rx,
-            None::<gpio::AnyIOPin>,
-            None::<gpio::AnyIOPin>,
-            &uart_cfg,
-        )?;
+        let status_tx_rmt_driver = status_led_peripheral
+            .map(|pin| TxRmtDriver::new(rmt.channel1, pin, &tx_rmt_cfg))
+            .transpose()?;
+        let gps_enable = gps_enable_peripheral
+            .map(|pin| PinDriver::output(AnyOutputPin::from(pin)))
+            .transpose()?;
+        // The GPS can only be configured when its RX line is wired to a TX pin
+        let (uart_driver, uart_tx_driver) = match uart_tx {
+            Some(uart_tx) => {
+                let (tx, rx) = UartDriver::new(
+                    uart_peripheral,
+                    uart_tx,
+                    uart_rx,
+                    None::<AnyIOPin>,
+                    None::<AnyIOPin>,
+                    &gps_config.uart_config(),
+                )?
+                .into_split();
+                (rx, Some(tx))
+            }
+            None => (
+                UartRxDriver::new(
+                    uart_peripheral,
+                    uart_rx,
+                    None::<AnyIOPin>,
+                    None::<AnyIOPin>,
+                    &gps_config.uart_config(),
+                )?,
+                None,
+            ),
+        };
 
         // Shared state between button and BLE scanner to control scanning based on system state.
         let button_state = Arc::new(Mutex::new(State::on()));
         let ble_payload = Arc::new(Mutex::new(None::<Vec<u8>>));
 
-        // Spawn button polling thread
-        let mut button = Button::new(
-            button_notifier,
-            &Trigger::ButtonPressed,
-            pin_driver,
-            Arc::clone(&button_state),
-        )?;
+        // Spawn button polling thread, long press requests an OTA update when configured
+        // BUTTON_INTERRUPT opts into interrupts on boards without antenna interference,
+        // TOUCH_BUTTON_PIN replaces the button with a touch pad
+        let long_press = option_env!("OTA_URL")
+            .map(|_| (&Trigger::UpdateRequested, OTA_LONG_PRESS_MS));
+        let mut button: Box<dyn Poller + Send> = match touch_button_peripheral {
+            Some(pin) => {
+                let button =
+                    TouchButton::new(button_notifier, &Trigger::ButtonPressed, pin)?
+                        .with_state(Arc::clone(&button_state));
+                Box::new(match long_press {
+                    Some((trigger, hold_ms)) => {
+                        button.with_long_press(trigger, hold_ms)
+                    }
+                    None => button,
+                })
+            }
+            None => {
+                let button = match option_env!("BUTTON_INTERRUPT") {
+                    Some(_) => Button::new_interrupt(
+                        button_notifier,
+                        &Trigger::ButtonPressed,
+                        pin_driver,
+                    )?,
+                    None => Button::new(
+                        button_notifier,
+                        &Trigger::ButtonPressed,
+                        pin_driver,
+                    )?,
+                }
+                .with_state(Arc::clone(&button_state));
+                Box::new(match long_press {
+                    Some((trigger, hold_ms)) => {
+                        button.with_long_press(trigger, hold_ms)
+                    }
+                    None => button,
+                })
+            }
+        };
         spawn(move || button.poll());
 
         // Spawn BLE scanner thread
-        let ble_timer = Timer::new(ble_timer_driver)?;
+        let mut ble_timer = Timer::new(ble_timer_driver)?;
+        if option_env!("TIMER_CALIBRATION").is_some() {
+            let ratio = ble_timer.calibrate(TIMER_CALIBRATION_MS)?;
+            log::info!("Measured timer tick rate ratio: {ratio:.4}");
+            ble_timer.apply_correction(ratio);
+        }
         let scanner_config = ScannerConfig::new(
-            |name| match name {
-                n if n.ends_with(BLE_ACTIVE_SUFFIX) => {
-                    Some(&Trigger::DeviceFoundActive)
-                }
-                n if n.ends_with(BLE_INACTIVE_SUFFIX) => {
-                    Some(&Trigger::DeviceFoundInactive)
-                }
-                _ => None,
+            |name| {
+                protocol::parse_name(name).map(|state| match state {
+                    State::On(_) => &Trigger::DeviceFoundActive,
+                    State::Off => &Trigger::DeviceFoundInactive,
+                })
             },
             &Trigger::DeviceNotFound,
             &Trigger::DeviceFoundActive,
@@ -126,37 +270,88 @@ impl<'a> Context<'a> {
         )?;
         spawn(move || scanner.poll());
 
+        // Spawn battery monitor thread, only when a low battery threshold is set
+        // since boards without a battery sense divider would read a floating pin
+        if let Some(low_mv) = option_env!("BATTERY_LOW_MV") {
+            let mut battery = BatteryMonitor::new(
+                battery_notifier,
+                &Trigger::LowBattery,
+                &Trigger::BatteryOk,
+                battery_adc,
+                battery_pin,
+                BATTERY_DIVIDER_RATIO,
+                low_mv.parse()?,
+            )?;
+            spawn(move || battery.poll());
+        }
+
         // Setup BLE advertiser
         let advertiser = Advertiser::new(State::on(), |state, payload| {
-            let app_name = option_env!("APP_NAME").unwrap_or("esp-flow");
+            let name = protocol::advertised_name(app_name(), state);
             match state {
-                State::On(_) => (
-                    format!("{app_name}{BLE_ACTIVE_SUFFIX}"),
-                    payload.map(<[u8]>::to_vec),
-                ),
-                State::Off => (format!("{app_name}{BLE_INACTIVE_SUFFIX}"), None),
+                State::On(_) => (name, payload.map(<[u8]>::to_vec)),
+                State::Off => (name, None),
             }
         })?;
 
         // Setup LED and its timer
-        let led = Led::new(tx_rmt_driver)?;
-        let mut led_timer = Timer::new(led_timer_driver)?;
-        led_timer.configure_interrupt(
-            BLINK_FREQ_HZ,
-            led_timer_notifier,
-            &Trigger::TimerTicked,
+        let led = Led::new(tx_rmt_driver, ChannelOrder::Grb, LedKind::Rgb)?;
+        // Optional second LED, left for the binaries to drive
+        let status_led = status_tx_rmt_driver
+            .map(|driver| Led::new(driver, ChannelOrder::Grb, LedKind::Rgb))
+            .transpose()?;
+        // LED blinking runs on the shared scheduler; LED_TIMER=rtc selects the
+        // esp_timer-backed clock instead, which keeps running in light sleep
+        let led_timer: Box<dyn Clock + 'a> = match option_env!("LED_TIMER") {
+            Some("rtc") => {
+                let mut timer = RtcTimer::new()?;
+                timer.configure_interrupt(
+                    BLINK_FREQ_HZ,
+                    led_timer_notifier,
+                    &Trigger::TimerTicked,
+                )?;
+                Box::new(timer)
+            }
+            _ => {
+                let scheduler = Rc::new(Scheduler::new(
+                    scheduler_timer_driver,
+                    SCHEDULER_BASE_HZ,
+                )?);
+                Box::new(Scheduler::clock(
+                    &scheduler,
+                    BLINK_FREQ_HZ,
+                    led_timer_notifier,
+                    &Trigger::TimerTicked,
+                )?)
+            }
+        };
+
+        // Setup heartbeat timer, left off until a binary opts into heartbeats
+        let heartbeat_interval_s = option_env!("HEARTBEAT_INTERVAL_S")
+            .map(str::parse::<u64>)
+            .transpose()?
+            .unwrap_or(DEFAULT_HEARTBEAT_INTERVAL_S);
+        let mut heartbeat_timer = Timer::new(heartbeat_timer_driver)?;
+        heartbeat_timer.configure_interval(
+            heartbeat_interval_s * 1000,
+            heartbeat_notifier,
+            &Trigger::Heartbeat,
         )?;
 
         Ok(Context {
             dispatcher,
             advertiser,
             led,
+            status_led,
             led_timer,
+            heartbeat_timer,
             button_state,
-            uart_driver,
+            gps_uart: (uart_driver, uart_tx_driver, gps_config),
+            gps_enable,
             gps_notifier,
             ble_payload,
             modem,
+            deep_sleep_gpio,
         })
     }
 
@@ -166,24 +361,32 @@ impl<'a> Context<'a> {
     ) -> (
         Dispatcher<Trigger>,
         Advertiser,
-        Led<'a>,
+        Led<TxRmtDriver<'a>>,
+        Option<Led<TxRmtDriver<'a>>>,
+        Box<dyn Clock + 'a>,
         Timer<'a, Trigger>,
         Notifier<Trigger>,
         Arc<Mutex<State>>,
-        UartRxDriver<'a>,
+        (UartRxDriver<'a>, Option<UartTxDriver<'a>>, GpsConfig),
+        Option<PinDriver<'a, AnyOutputPin, Output>>,
         Arc<Mutex<Option<Vec<u8>>>>,
         Modem,
+        Option<i32>,
     ) {
         (
             self.dispatcher,
             self.advertiser,
             self.led,
+            self.status_led,
             self.led_timer,
+            self.heartbeat_timer,
             self.gps_notifier,
             self.button_state,
-            self.uart_driver,
+            self.gps_uart,
+            self.gps_enable,
             self.ble_payload,
             self.modem,
+            self.deep_sleep_gpio,
         )
     }
 }
@@ -1,16 +1,44 @@
-use anyhow::Result;
-use std::collections::HashSet;
+use anyhow::{anyhow, Result};
+use esp_idf_hal::rmt::TxRmtDriver;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use esp_flow::{
     ble::Advertiser,
-    clock::Timer,
-    color::{Rgb, GREEN, RED},
+    clock::Clock,
+    color::{Palette, Rgb, AMBER, RED},
+    diag::{EventKind, EventLog},
     infra::{self, Switch},
     light::Led,
     message::Dispatcher,
-    trigger_enum,
+    thread,
+    time::now_ms,
 };
 
+pub use esp_flow::logic::{next_state, DeviceNearby, State, Trigger, STATES};
+
+use super::hw::{BLINK_FREQ_HZ, GPS_ERROR_BLINK_FREQ_HZ, LOW_BATTERY_BLINK_FREQ_HZ};
+
+// Triggers handled by `Core::handle_common_triggers`.
+const COMMON_TRIGGERS: [&Trigger; 9] = [
+    &Trigger::ButtonPressed,
+    &Trigger::DeviceFoundActive,
+    &Trigger::DeviceFoundInactive,
+    &Trigger::DeviceNotFound,
+    &Trigger::TimerTicked,
+    &Trigger::LowBattery,
+    &Trigger::BatteryOk,
+    &Trigger::GpsError,
+    &Trigger::GpsRecovered,
+];
+
+// Triggers fired periodically, left out of the event log.
+const UNRECORDED_TRIGGERS: [&Trigger; 2] =
+    [&Trigger::TimerTicked, &Trigger::DeviceNotFound];
+
 macro_rules! func {
     () => {{
         fn f() {}
@@ -34,32 +62,42 @@ macro_rules! trace_func {
 }
 pub(crate) use trace_func;
 
-trigger_enum! {
-    #[derive(Debug, Eq, Hash, PartialEq)]
-    pub enum Trigger {
-        ButtonPressed = 1 << 0,
-        TimerTicked = 1 << 1,
-        DeviceFoundActive = 1 << 2,
-        DeviceFoundInactive = 1 << 3,
-        DeviceNotFound = 1 << 4,
-        GpsDataAvailable = 1 << 5,
+// Builds the LED palette from the default one, green when on or near an
+// active device and red otherwise. Each state's color can be overridden with
+// a COLOR_* variable, either a color name, a hex color, or a temperature such
+// as 2700K.
+fn palette() -> Result<Palette<State>> {
+    let mut palette = Palette::default();
+
+    let overrides = [
+        (State::Off, option_env!("COLOR_OFF")),
+        (State::On(None), option_env!("COLOR_ON")),
+        (
+            State::On(Some(DeviceNearby::Active)),
+            option_env!("COLOR_ACTIVE"),
+        ),
+        (
+            State::On(Some(DeviceNearby::Inactive)),
+            option_env!("COLOR_INACTIVE"),
+        ),
+    ];
+    for (state, value) in overrides {
+        if let Some(value) = value {
+            match (Rgb::from_name(value), value.strip_suffix('K')) {
+                (Some(color), _) => palette.set(state, color),
+                (None, Some(kelvin)) => palette.set_kelvin(state, kelvin.parse()?),
+                (None, None) => palette.set_hex(state, value)?,
+            }
+        }
     }
-}
 
-// Represents whether a nearby device is active or inactive.
-#[derive(PartialEq)]
-pub enum DeviceNearby {
-    Active,
-    Inactive,
+    palette.validate(&STATES)?;
+    Ok(palette)
 }
 
-// Application state: either Off or On with optional device nearby info.
-pub type State = infra::State<DeviceNearby>;
-
 // Extension trait for app-specific State behavior.
 pub trait StateExt {
     fn to_str(&self) -> &'static str;
-    fn to_color(&self) -> Rgb;
 }
 
 impl StateExt for State {
@@ -71,79 +109,384 @@ impl StateExt for State {
             State::On(Some(DeviceNearby::Inactive)) => "InactiveDeviceNearby",
         }
     }
+}
 
-    fn to_color(&self) -> Rgb {
-        match self {
-            State::On(None | Some(DeviceNearby::Active)) => GREEN,
-            State::Off | State::On(Some(DeviceNearby::Inactive)) => RED,
+// Logs a state change as a single structured line, so that field logs can be
+// grepped for `state_transition` to reconstruct the device's behavior.
+fn log_transition(from: &State, to: &State, trigger: &str) {
+    log::info!(
+        "state_transition t_ms={} from={} to={} trigger={}",
+        now_ms(),
+        from.to_str(),
+        to.to_str(),
+        trigger
+    );
+}
+
+// LED blink frequencies (Hz) used while a device is nearby.
+#[derive(Clone, Copy)]
+pub struct BlinkFreq {
+    pub active: u64,
+    pub inactive: u64,
+}
+
+impl Default for BlinkFreq {
+    fn default() -> Self {
+        Self {
+            active: BLINK_FREQ_HZ,
+            inactive: BLINK_FREQ_HZ,
         }
     }
 }
 
-pub struct Core<'a> {
+// Auto-off bookkeeping: the device turns off once no device has been found
+// for `timeout`. `switch` is the state shared with the button and scanner,
+// which must follow the transition so the next press turns the device on.
+struct Idle {
+    timeout: Duration,
+    last_seen: Instant,
+    switch: Arc<Mutex<State>>,
+}
+
+pub struct Core<'a, C: Clock> {
     pub state: State,
     pub dispatcher: Dispatcher<Trigger>,
     pub advertiser: Advertiser,
-    pub led: Led<'a>,
-    pub timer: Timer<'a, Trigger>,
+    pub led: Led<TxRmtDriver<'a>>,
+    pub timer: C,
+    blink: BlinkFreq,
+    blink_hz: Option<u64>,
+    // The run loop owns the core exclusively, so other threads cannot borrow
+    // `state`. They read this mirror instead, refreshed once per loop iteration
+    // after triggers are handled.
+    status: Arc<Mutex<State>>,
+    idle: Option<Idle>,
+    // While the battery is low, the LED blinks this color whatever the state.
+    palette: Palette<State>,
+    battery_warning: Rgb,
+    battery_low: bool,
+    // While GPS reads keep failing, the LED blinks this color unless the
+    // battery is low.
+    gps_warning: Rgb,
+    gps_error: bool,
+    deep_sleep_gpio: Option<i32>,
+    events: Option<EventLog>,
 }
 
-impl<'a> Core<'a> {
-    // Creates a new core with initialized LED.
+// Builds a `Core` from its hardware parts. The dispatcher, advertiser, LED
+// and timer are required; the device starts on, with the default blink
+// frequencies, an amber battery warning, a red GPS warning and no deep sleep
+// unless set.
+pub struct CoreBuilder<'a, C: Clock> {
+    state: State,
+    dispatcher: Option<Dispatcher<Trigger>>,
+    advertiser: Option<Advertiser>,
+    led: Option<Led<TxRmtDriver<'a>>>,
+    timer: Option<C>,
+    blink: BlinkFreq,
+    battery_warning: Rgb,
+    gps_warning: Rgb,
+    deep_sleep_gpio: Option<i32>,
+}
+
+impl<'a, C: Clock> CoreBuilder<'a, C> {
+    // Sets the initial state.
+    #[allow(dead_code)]
+    pub fn state(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn dispatcher(mut self, dispatcher: Dispatcher<Trigger>) -> Self {
+        self.dispatcher = Some(dispatcher);
+        self
+    }
+
+    pub fn advertiser(mut self, advertiser: Advertiser) -> Self {
+        self.advertiser = Some(advertiser);
+        self
+    }
+
+    pub fn led(mut self, led: Led<TxRmtDriver<'a>>) -> Self {
+        self.led = Some(led);
+        self
+    }
+
+    // Sets the clock driving LED blinking.
+    pub fn timer(mut self, timer: C) -> Self {
+        self.timer = Some(timer);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn blink_freq(mut self, blink: BlinkFreq) -> Self {
+        self.blink = blink;
+        self
+    }
+
+    // Sets the color the LED blinks while the battery is low.
+    pub fn battery_warning(mut self, color: Rgb) -> Self {
+        self.battery_warning = color;
+        self
+    }
+
+    // Sets the color the LED blinks while GPS reads keep failing.
+    #[allow(dead_code)]
+    pub fn gps_warning(mut self, color: Rgb) -> Self {
+        self.gps_warning = color;
+        self
+    }
+
+    // Enables deep sleep whenever the device turns off, waking up when the
+    // button on `wake_gpio` is pressed. `None` keeps the device awake.
+    pub fn deep_sleep(mut self, wake_gpio: Option<i32>) -> Self {
+        self.deep_sleep_gpio = wake_gpio;
+        self
+    }
+
+    // Creates the core with initialized LED, colored from the palette.
+    pub fn build(self) -> Result<Core<'a, C>> {
+        let missing = |part: &str| anyhow!("Missing {part} to build the core");
+        let mut led = self.led.ok_or_else(|| missing("LED"))?;
+        let palette = palette()?;
+        led.set_color(palette.color(&self.state))?;
+        led.on()?;
+
+        Ok(Core {
+            state: self.state,
+            dispatcher: self.dispatcher.ok_or_else(|| missing("dispatcher"))?,
+            advertiser: self.advertiser.ok_or_else(|| missing("advertiser"))?,
+            led,
+            timer: self.timer.ok_or_else(|| missing("timer"))?,
+            blink: self.blink,
+            blink_hz: None,
+            palette,
+            status: Arc::new(Mutex::new(self.state)),
+            idle: None,
+            battery_warning: self.battery_warning,
+            battery_low: false,
+            gps_warning: self.gps_warning,
+            gps_error: false,
+            deep_sleep_gpio: self.deep_sleep_gpio,
+            events: None,
+        })
+    }
+}
+
+impl<'a, C: Clock> Core<'a, C> {
+    // Creates a new core with initialized LED, colored from the palette.
+    #[allow(dead_code)]
+    #[deprecated(note = "use `Core::builder` instead")]
     pub fn new(
         state: State,
         dispatcher: Dispatcher<Trigger>,
         advertiser: Advertiser,
-        mut led: Led<'a>,
-        timer: Timer<'a, Trigger>,
+        led: Led<TxRmtDriver<'a>>,
+        timer: C,
+        blink: BlinkFreq,
+        battery_warning: Rgb,
     ) -> Result<Self> {
-        led.set_color(state.to_color())?;
-        led.on()?;
+        Self::builder()
+            .state(state)
+            .dispatcher(dispatcher)
+            .advertiser(advertiser)
+            .led(led)
+            .timer(timer)
+            .blink_freq(blink)
+            .battery_warning(battery_warning)
+            .build()
+    }
 
-        Ok(Self {
-            state,
-            dispatcher,
-            advertiser,
-            led,
-            timer,
+    // Starts building a core, see `CoreBuilder`.
+    pub fn builder() -> CoreBuilder<'a, C> {
+        CoreBuilder {
+            state: State::on(),
+            dispatcher: None,
+            advertiser: None,
+            led: None,
+            timer: None,
+            blink: BlinkFreq::default(),
+            battery_warning: AMBER,
+            gps_warning: RED,
+            deep_sleep_gpio: None,
+        }
+    }
+
+    // Records transitions, triggers and errors into `events` for remote
+    // diagnostics, on top of logging them.
+    pub fn set_event_log(&mut self, events: EventLog) {
+        self.events = Some(events);
+    }
+
+    // Records an event when an event log is set.
+    fn record(&self, kind: EventKind, message: impl FnOnce() -> String) {
+        if let Some(events) = self.events.as_ref() {
+            events.record(kind, message());
+        }
+    }
+
+    // Moves to `next`, logging the transition if the state changes. A missing
+    // trigger means the transition was caused by the idle timeout.
+    pub fn set_state(&mut self, next: State, trigger: Option<&Trigger>) {
+        if next != self.state {
+            let trigger = trigger
+                .map_or_else(|| "IdleTimeout".to_string(), |t| format!("{t:?}"));
+            log_transition(&self.state, &next, &trigger);
+            self.record(EventKind::Transition, || {
+                format!("{} -> {} ({trigger})", self.state.to_str(), next.to_str())
+            });
+        }
+        self.state = next;
+    }
+
+    // Moves to the state following `trigger`, see `next_state`.
+    pub fn transition(&mut self, trigger: &Trigger) {
+        self.set_state(next_state(&self.state, trigger), Some(trigger));
+    }
+
+    // Turns the advertiser on or off to match the current state.
+    pub fn sync_advertiser(&mut self) -> Result<()> {
+        self.advertiser.set(if self.state.is_on() {
+            infra::State::on()
+        } else {
+            infra::State::off()
         })
     }
 
+    // Enters deep sleep if the device is off and deep sleep is enabled. The
+    // device wakes up through a reset, restarting from `main` in the on state.
+    fn sleep_if_off(&self) -> Result<()> {
+        match self.deep_sleep_gpio {
+            Some(gpio) if self.state.is_off() => {
+                thread::deep_sleep(gpio).map(|never| never)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Enables auto-off after `timeout_s` seconds without finding a device.
+    // A zero timeout disables auto-off.
+    pub fn set_idle_timeout(&mut self, timeout_s: u64, switch: Arc<Mutex<State>>) {
+        self.idle = (timeout_s > 0).then(|| Idle {
+            timeout: Duration::from_secs(timeout_s),
+            last_seen: Instant::now(),
+            switch,
+        });
+    }
+
+    // Turns the device off when the idle timeout expired. The countdown is
+    // restarted by any found device and held while the device is off.
+    fn check_idle(&mut self, triggers: &HashSet<&'static Trigger>) -> Result<()> {
+        let found = triggers.contains(&Trigger::DeviceFoundActive)
+            || triggers.contains(&Trigger::DeviceFoundInactive);
+
+        match &mut self.idle {
+            Some(idle) if found || self.state.is_off() => {
+                idle.last_seen = Instant::now();
+                Ok(())
+            }
+            Some(idle) if idle.last_seen.elapsed() >= idle.timeout => {
+                log::info!(
+                    "No device found for {}s, turning off",
+                    idle.timeout.as_secs()
+                );
+                *infra::lock(&idle.switch) = State::off();
+                self.set_state(State::off(), None);
+                self.sync_advertiser()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Returns a shared handle mirroring the current state, for other threads.
+    #[allow(dead_code)]
+    pub fn status(&self) -> Arc<Mutex<State>> {
+        Arc::clone(&self.status)
+    }
+
+    // Refreshes the shared state mirror.
+    fn publish_status(&self) {
+        *infra::lock(&self.status) = self.state;
+    }
+
     // Handles the timer ticked trigger (LED blinking when device nearby).
+    // Coalesced ticks are compensated for so the blink phase stays on schedule.
     pub fn handle_timer_ticked(&mut self) -> Result<()> {
         trace_func!();
 
+        let pending = self.timer.process();
+        if pending > 1 {
+            log::warn!(
+                "Missed {} timer ticks ({} in total)",
+                pending - 1,
+                self.timer.missed()
+            );
+        }
+
         match self.state {
-            State::On(Some(_)) => self.led.toggle(),
+            _ if (self.battery_low || self.gps_error) && pending % 2 == 1 => {
+                self.led.toggle()
+            }
+            State::On(Some(_)) if pending % 2 == 1 => self.led.toggle(),
             _ => Ok(()),
         }
     }
 
+    // Handles the low battery trigger, overriding the LED until BatteryOk.
+    pub fn handle_low_battery(&mut self) {
+        trace_func!();
+
+        log::warn!("Battery low");
+        self.battery_low = true;
+    }
+
+    // Handles the battery ok trigger, restoring the normal LED behavior.
+    pub fn handle_battery_ok(&mut self) {
+        trace_func!();
+
+        log::info!("Battery ok");
+        self.battery_low = false;
+    }
+
+    // Handles the GPS error trigger, overriding the LED until GpsRecovered.
+    pub fn handle_gps_error(&mut self) {
+        trace_func!();
+
+        log::warn!("GPS reads keep failing, check the wiring");
+        self.gps_error = true;
+    }
+
+    // Handles the GPS recovered trigger, restoring the normal LED behavior.
+    pub fn handle_gps_recovered(&mut self) {
+        trace_func!();
+
+        log::info!("GPS reads recovered");
+        self.gps_error = false;
+    }
+
     // Handles the device found inactive trigger.
     pub fn handle_device_found_inactive(&mut self) {
         trace_func!();
 
-        if self.state.is_on() {
-            self.state = State::On(Some(DeviceNearby::Inactive));
-        }
+        self.transition(&Trigger::DeviceFoundInactive);
     }
 
     // Handles the device not found trigger.
     pub fn handle_device_not_found(&mut self) {
         trace_func!();
 
-        if self.state.is_on() {
-            self.state = State::on();
-        }
+        self.transition(&Trigger::DeviceNotFound);
     }
 
-    // Handles common triggers, returning true if handled.
+    // Handles every common trigger in `triggers`, returning the remaining ones
+    // for the caller. Triggers coalesced into the same notification are all
+    // handled, one after the other.
     pub fn handle_common_triggers(
         &mut self,
         triggers: &HashSet<&'static Trigger>,
         on_button_pressed: impl FnOnce(&mut Self) -> Result<()>,
         on_device_found_active: impl FnOnce(&mut Self) -> Result<()>,
-    ) -> Result<bool> {
+    ) -> Result<HashSet<&'static Trigger>> {
         log::debug!(
             "{}: triggers: {:?}, state: {}",
             func!(),
@@ -151,32 +494,81 @@ impl<'a> Core<'a> {
             self.state.to_str()
         );
 
-        let mut handled = true;
         if triggers.contains(&Trigger::ButtonPressed) {
             on_button_pressed(self)?;
-        } else if triggers.contains(&Trigger::DeviceFoundActive) {
+        }
+        if triggers.contains(&Trigger::DeviceFoundActive) {
             on_device_found_active(self)?;
-        } else if triggers.contains(&Trigger::DeviceFoundInactive) {
+        }
+        if triggers.contains(&Trigger::DeviceFoundInactive) {
             self.handle_device_found_inactive();
-        } else if triggers.contains(&Trigger::DeviceNotFound) {
+        }
+        if triggers.contains(&Trigger::DeviceNotFound) {
             self.handle_device_not_found();
-        } else if triggers.contains(&Trigger::TimerTicked) {
+        }
+        if triggers.contains(&Trigger::TimerTicked) {
             self.handle_timer_ticked()?;
-        } else {
-            handled = false;
+        }
+        if triggers.contains(&Trigger::LowBattery) {
+            self.handle_low_battery();
+        }
+        if triggers.contains(&Trigger::BatteryOk) {
+            self.handle_battery_ok();
+        }
+        if triggers.contains(&Trigger::GpsError) {
+            self.handle_gps_error();
+        }
+        if triggers.contains(&Trigger::GpsRecovered) {
+            self.handle_gps_recovered();
         }
 
-        Ok(handled)
+        Ok(triggers
+            .iter()
+            .filter(|trigger| !COMMON_TRIGGERS.contains(trigger))
+            .copied()
+            .collect())
     }
 
-    // Updates LED state based on current state.
+    // Updates LED state based on current state, unless the low battery or
+    // GPS error warning overrides it.
     pub fn update_led(&mut self) -> Result<()> {
-        self.led.set_color(self.state.to_color())?;
-        if matches!(self.state, State::On(None) | State::Off) {
-            self.timer.off()?;
-            self.led.on()?;
+        self.led.set_color(if self.battery_low {
+            self.battery_warning
+        } else if self.gps_error {
+            self.gps_warning
         } else {
-            self.timer.on()?;
+            self.palette.color(&self.state)
+        })?;
+        match self.state {
+            _ if self.battery_low => {
+                self.set_blink_freq(LOW_BATTERY_BLINK_FREQ_HZ)?;
+                self.timer.on()?;
+            }
+            _ if self.gps_error => {
+                self.set_blink_freq(GPS_ERROR_BLINK_FREQ_HZ)?;
+                self.timer.on()?;
+            }
+            State::On(None) | State::Off => {
+                self.timer.off()?;
+                self.led.on()?;
+            }
+            State::On(Some(nearby)) => {
+                self.set_blink_freq(match nearby {
+                    DeviceNearby::Active => self.blink.active,
+                    DeviceNearby::Inactive => self.blink.inactive,
+                })?;
+                self.timer.on()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Changes the blink frequency, only touching the timer when it differs
+    // since reprogramming it restarts the current blink period.
+    fn set_blink_freq(&mut self, freq: u64) -> Result<()> {
+        if self.blink_hz != Some(freq) {
+            self.timer.set_freq(freq)?;
+            self.blink_hz = Some(freq);
         }
         Ok(())
     }
@@ -188,7 +580,24 @@ impl<'a> Core<'a> {
     {
         loop {
             let triggers = self.dispatcher.collect()?;
-            handle_triggers(self, &triggers)?;
+            // Periodic triggers would flood the log, only the others are
+            // recorded
+            let recorded: Vec<_> = triggers
+                .iter()
+                .filter(|trigger| !UNRECORDED_TRIGGERS.contains(trigger))
+                .collect();
+            if !recorded.is_empty() {
+                self.record(EventKind::Trigger, || format!("{recorded:?}"));
+            }
+            // Handler errors restart the device, so they only stay in the
+            // event log until then
+            handle_triggers(self, &triggers).or_else(|e| {
+                self.record(EventKind::Error, || format!("{e:#}"));
+                Err(e)
+            })?;
+            self.check_idle(&triggers)?;
+            self.sleep_if_off()?;
+            self.publish_status();
             self.update_led()?;
         }
     }
@@ -1,46 +1,110 @@
 use anyhow::{anyhow, Result};
+use esp_idf_hal::rmt::TxRmtDriver;
 use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
     log::EspLogger,
-    nvs::EspDefaultNvsPartition,
+    nvs::{EspDefaultNvsPartition, EspNvs},
     wifi::{BlockingWifi, EspWifi},
 };
-use log::info;
-use std::sync::{Arc, Mutex};
+use log::{debug, info, warn};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use esp_flow::{
-    http::Client,
-    infra::Switch,
-    thread,
-    wifi::{Config as WifiConfig, Connection},
+    batch::Batch,
+    clock::{Clock, Timer},
+    color::BLUE,
+    diag::EventLog,
+    error::Error,
+    gps::Reading,
+    http::{Client, Server},
+    infra::{self, Poller, Switch},
+    light::Led,
+    ota, thread,
+    time::{now_ms, now_unix},
+    wifi::{
+        self, Config as WifiConfig, Connection, Monitor, DEFAULT_MONITOR_DEBOUNCE,
+        DEFAULT_NTP_SERVER,
+    },
 };
 
 mod common;
 use common::{
-    hw::Context,
-    logic::{trace_func, Core, DeviceNearby, State},
+    hw::{self, Context},
+    logic::{next_state, trace_func, Core, DeviceNearby, State, Trigger},
 };
 
+// Port of the HTTP server, advertised over mDNS.
+const HTTP_SERVER_PORT: u16 = 80;
+
+// NVS namespace the provisioned Wi-Fi credentials are stored in.
+const WIFI_NVS_NAMESPACE: &str = "wifi";
+
+// Readings kept while they cannot be posted, the oldest are dropped beyond
+// this.
+const READING_QUEUE_CAPACITY: usize = 100;
+const DEFAULT_BATCH_SIZE: usize = 10;
+const DEFAULT_BATCH_INTERVAL_S: u64 = 60;
+
+// Number of recent events served on the diagnostics endpoint.
+const DIAG_EVENTS_CAPACITY: usize = 64;
+
+// Triggers handled by the server on top of the common ones.
+const HANDLED_TRIGGERS: [&Trigger; 4] = [
+    &Trigger::WifiDisconnected,
+    &Trigger::WifiConnected,
+    &Trigger::Heartbeat,
+    &Trigger::UpdateRequested,
+];
+
 // State machine for the server device (BLE scanning, HTTP posting).
-struct StateMachine<'a> {
-    core: Core<'a>,
+struct StateMachine<'a, C: Clock> {
+    core: Core<'a, C>,
     http: Client<'a>,
     url: &'a str,
     param: &'a str,
     ble_payload: Arc<Mutex<Option<Vec<u8>>>>,
+    readings: Batch<Reading>,
+    heartbeat_url: Option<&'a str>,
+    ota_url: Option<&'a str>,
+    _heartbeat: Timer<'a, Trigger>,
+    status_led: Option<Led<TxRmtDriver<'a>>>,
 }
 
-impl<'a> StateMachine<'a> {
+impl<'a, C: Clock> StateMachine<'a, C> {
     // Creates a new server state machine.
+    // Heartbeats are only enabled when HTTP_HEARTBEAT_URL is set. The device
+    // turns off after `idle_timeout_s` seconds without finding a device, zero
+    // disables auto-off. Readings are posted every HTTP_BATCH_SIZE readings or
+    // HTTP_BATCH_INTERVAL_S seconds, whichever comes first.
     fn new(
-        core: Core<'a>,
+        mut core: Core<'a, C>,
         http: Client<'a>,
         ble_payload: Arc<Mutex<Option<Vec<u8>>>>,
+        mut heartbeat: Timer<'a, Trigger>,
+        button_state: Arc<Mutex<State>>,
+        idle_timeout_s: u64,
     ) -> Result<Self> {
         let url = option_env!("HTTP_URL")
             .ok_or_else(|| anyhow!("HTTP_URL environment variable not set"))?;
         let param = option_env!("HTTP_PARAM")
             .ok_or_else(|| anyhow!("HTTP_PARAM environment variable not set"))?;
+        let heartbeat_url = option_env!("HTTP_HEARTBEAT_URL");
+        let batch_size = option_env!("HTTP_BATCH_SIZE")
+            .map(str::parse::<usize>)
+            .transpose()?
+            .unwrap_or(DEFAULT_BATCH_SIZE);
+        let batch_interval_s = option_env!("HTTP_BATCH_INTERVAL_S")
+            .map(str::parse::<u64>)
+            .transpose()?
+            .unwrap_or(DEFAULT_BATCH_INTERVAL_S);
+
+        if heartbeat_url.is_some() {
+            heartbeat.on()?;
+        }
+        core.set_idle_timeout(idle_timeout_s, button_state);
 
         Ok(Self {
             core,
@@ -48,20 +112,72 @@ impl<'a> StateMachine<'a> {
             url,
             param,
             ble_payload,
+            readings: Batch::new(
+                READING_QUEUE_CAPACITY,
+                batch_size,
+                batch_interval_s * 1000,
+            )?,
+            heartbeat_url,
+            ota_url: option_env!("OTA_URL"),
+            _heartbeat: heartbeat,
+            status_led: None,
         })
     }
 
-    // Sends the max speed from BLE payload over HTTP.
-    // Does nothing if no payload is available (not an error).
-    fn post_speed(
-        http: &mut Client<'_>,
-        url: &str,
-        param: &str,
+    // Shows the Wi-Fi status on a second LED, blue while connected.
+    fn with_status_led(
+        mut self,
+        mut led: Option<Led<TxRmtDriver<'a>>>,
+    ) -> Result<Self> {
+        if let Some(led) = led.as_mut() {
+            led.set_color(BLUE)?;
+        }
+        self.status_led = led;
+        Ok(self)
+    }
+
+    // Turns the status LED on or off to follow the Wi-Fi connection.
+    fn sync_status_led(
+        led: Option<&mut Led<TxRmtDriver<'_>>>,
+        http: &Client<'_>,
+    ) -> Result<()> {
+        match led {
+            Some(led) => led.set(if http.is_connected()? {
+                infra::State::on()
+            } else {
+                infra::State::Off
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // Posts a liveness heartbeat to the health endpoint when Wi-Fi is up. A
+    // failed heartbeat is only logged, the next one may get through.
+    fn handle_heartbeat(http: &mut Client<'_>, url: Option<&str>) -> Result<()> {
+        trace_func!();
+
+        match url {
+            Some(url) if http.is_connected()? => {
+                match http.post(url, None) {
+                    Ok(status) => {
+                        info!("Heartbeat sent to {}, status: {}", url, status);
+                    }
+                    Err(e) => warn!("Failed to send heartbeat to {}: {}", url, e),
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Queues the reading from the BLE payload for the next batched post,
+    // timestamped with the time it was received at when its fix has no UTC
+    // time. Does nothing if no payload is available (not an error).
+    fn queue_reading(
+        readings: &mut Batch<Reading>,
         ble_payload: &Arc<Mutex<Option<Vec<u8>>>>,
     ) -> Result<()> {
-        let mut data = ble_payload
-            .lock()
-            .map_err(|e| anyhow!("Mutex lock error: {:?}", e))?;
+        let mut data = infra::lock(ble_payload);
 
         match data.take() {
             None => {
@@ -69,109 +185,300 @@ impl<'a> StateMachine<'a> {
                 Ok(())
             }
             Some(payload) => {
-                let bytes: [u8; 4] =
-                    payload.as_slice().try_into().map_err(|_| {
-                        anyhow!("Invalid BLE payload length: {}", payload.len())
-                    })?;
-                let max_speed_mps = f32::from_le_bytes(bytes);
-                let max_speed_kmph = max_speed_mps * 3.6;
+                let reading = Reading::from_bytes(&payload)?;
                 info!(
-                    "Received BLE payload: {} bytes: {:?} (max_speed: {max_speed_kmph:.2} km/h)",
-                    payload.len(),
-                    payload
+                    "Received BLE payload: {} bytes: {payload:?} ({reading})",
+                    payload.len()
                 );
 
-                let url = format!("{url}?{param}={max_speed_kmph:.2}");
-                let status = http.post(&url, None)?;
-                info!("HTTP POST request sent to {}, status: {}", url, status);
-
+                let utc_s = reading.utc_s().or_else(now_unix);
+                if let Some(dropped) =
+                    readings.push(reading.with_utc(utc_s), now_ms())
+                {
+                    warn!("Reading queue full, dropping {dropped}");
+                }
                 Ok(())
             }
         }
     }
 
-    // Custom device found active handler that posts speed data.
-    fn handle_device_found_active(
-        core: &mut Core<'_>,
+    // Posts the queued readings as a JSON array once a batch is due, with the
+    // highest speed of the batch in km/h as the `param` query parameter. The
+    // readings are kept while Wi-Fi is down, and retried after the batch
+    // interval on server errors and timeouts; only a 4xx status drops them.
+    fn flush_readings(
         http: &mut Client<'_>,
         url: &str,
         param: &str,
+        readings: &mut Batch<Reading>,
+    ) -> Result<()> {
+        if readings.is_due(now_ms()) {
+            let json = format!(
+                "[{}]",
+                readings
+                    .iter()
+                    .map(Reading::to_json)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+            let url = match readings
+                .iter()
+                .filter_map(Reading::speed_kmh)
+                .reduce(f32::max)
+            {
+                Some(max_speed_kmph) => {
+                    format!("{url}?{param}={max_speed_kmph:.2}")
+                }
+                None => url.to_string(),
+            };
+            match http.post_json(&url, &json) {
+                Ok(status) => {
+                    info!(
+                        "HTTP POST request sent to {}: {} readings, status: {}",
+                        url,
+                        readings.len(),
+                        status
+                    );
+                    readings.clear();
+                    Ok(())
+                }
+                // Not worth a restart, the batch is posted once Wi-Fi is back
+                Err(Error::WifiDown) => {
+                    debug!("Wi-Fi is down, keeping {} readings", readings.len());
+                    Ok(())
+                }
+                // The endpoint rejects the batch, retrying would not help
+                Err(Error::HttpStatus(status)) if (400..500).contains(&status) => {
+                    warn!(
+                        "HTTP POST request to {} rejected with status {}, dropping {} readings",
+                        url,
+                        status,
+                        readings.len()
+                    );
+                    readings.clear();
+                    Ok(())
+                }
+                // Server errors and timeouts are transient, retry next interval
+                Err(e) => {
+                    warn!(
+                        "HTTP POST request to {} failed, keeping {} readings: {}",
+                        url,
+                        readings.len(),
+                        e
+                    );
+                    readings.defer(now_ms());
+                    Ok(())
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    // Custom device found active handler that queues the advertised reading.
+    fn handle_device_found_active(
+        core: &mut Core<'_, C>,
+        readings: &mut Batch<Reading>,
         ble_payload: &Arc<Mutex<Option<Vec<u8>>>>,
     ) -> Result<()> {
         trace_func!();
 
-        if core.state.is_on() {
-            // Only post when transitioning to DeviceNearby::Active.
-            if !matches!(core.state, State::On(Some(DeviceNearby::Active))) {
-                Self::post_speed(http, url, param, ble_payload)?;
-            }
-            core.state = State::On(Some(DeviceNearby::Active));
+        let next = next_state(&core.state, &Trigger::DeviceFoundActive);
+        // Only queue when transitioning to DeviceNearby::Active.
+        if next != core.state && next == State::On(Some(DeviceNearby::Active)) {
+            Self::queue_reading(readings, ble_payload)?;
         }
-
+        core.set_state(next, Some(&Trigger::DeviceFoundActive));
         Ok(())
     }
 
+    // Downloads and flashes a firmware image, rebooting into it on success.
+    fn handle_update_requested(
+        http: &mut Client<'_>,
+        url: Option<&str>,
+    ) -> Result<()> {
+        trace_func!();
+
+        url.map_or_else(
+            || Err(anyhow!("Update requested but OTA_URL is not set")),
+            |url| ota::update(http, url).map(|never| never),
+        )
+    }
+
+    // Returns the current state. Only usable from the thread running the state
+    // machine; other threads should read `Core::status` instead.
+    #[allow(dead_code)]
+    fn state(&self) -> &State {
+        &self.core.state
+    }
+
     // Runs the state machine.
     fn run(&mut self) -> Result<()> {
         let http = &mut self.http;
         let url = self.url;
         let param = self.param;
         let ble_payload = &self.ble_payload;
+        let readings = &mut self.readings;
+        let heartbeat_url = self.heartbeat_url;
+        let ota_url = self.ota_url;
+        let status_led = &mut self.status_led;
 
         self.core.run(|core, triggers| {
-            if core.handle_common_triggers(
+            Self::sync_status_led(status_led.as_mut(), http)?;
+            Self::flush_readings(http, url, param, readings)?;
+
+            let remaining = core.handle_common_triggers(
                 triggers,
                 |c| {
                     trace_func!();
-                    c.state = if c.state.is_off() {
-                        State::on()
-                    } else {
-                        State::off()
-                    };
-                    c.advertiser.toggle()
-                },
-                |c| {
-                    Self::handle_device_found_active(
-                        c,
-                        http,
-                        url,
-                        param,
-                        ble_payload,
-                    )
+                    c.transition(&Trigger::ButtonPressed);
+                    c.sync_advertiser()
                 },
-            )? {
-                Ok(())
-            } else {
-                Err(anyhow!("Unknown triggers: {:?}", triggers))
+                |c| Self::handle_device_found_active(c, readings, ble_payload),
+            )?;
+            if remaining.contains(&Trigger::WifiDisconnected) {
+                warn!("Wi-Fi disconnected, queueing readings until it is back");
+            }
+            if remaining.contains(&Trigger::WifiConnected) {
+                info!("Wi-Fi connected");
+            }
+            if remaining.contains(&Trigger::Heartbeat) {
+                Self::handle_heartbeat(http, heartbeat_url)?;
             }
+            if remaining.contains(&Trigger::UpdateRequested) {
+                Self::handle_update_requested(http, ota_url)?;
+            }
+
+            remaining
+                .iter()
+                .find(|trigger| !HANDLED_TRIGGERS.contains(trigger))
+                .map_or(Ok(()), |trigger| {
+                    Err(anyhow!("Unknown trigger: {trigger:?}"))
+                })
         })
     }
 }
 
 fn main() -> ! {
-    thread::main(|| {
-        EspLogger::initialize_default();
+    EspLogger::initialize_default();
 
+    thread::main(|| {
         // Setup common context (peripherals, threads, etc.) and keep modem for WiFi
         let context = Context::try_default()?;
-        let (dispatcher, advertiser, led, led_timer, _, _, _, ble_payload, modem) =
-            context.into_parts();
+        let (
+            dispatcher,
+            advertiser,
+            led,
+            status_led,
+            led_timer,
+            heartbeat_timer,
+            _,
+            button_state,
+            _,
+            _,
+            ble_payload,
+            modem,
+            deep_sleep_gpio,
+        ) = context.into_parts();
 
         // Setup WiFi and HTTP client for server
         let nvs = EspDefaultNvsPartition::take()?;
         let sys_loop = EspSystemEventLoop::take()?;
 
-        let wifi_driver = BlockingWifi::wrap(
-            EspWifi::new(modem, sys_loop.clone(), Some(nvs))?,
+        let mut wifi_driver = BlockingWifi::wrap(
+            EspWifi::new(modem, sys_loop.clone(), Some(nvs.clone()))?,
             sys_loop,
         )?;
 
-        let wifi_config = WifiConfig::from_env()?;
-        let wifi = Connection::new(wifi_driver, &wifi_config)?;
-        let http = Client::new(wifi)?;
+        // Credentials set at build time win over the ones provisioned at runtime
+        let wifi_nvs = EspNvs::new(nvs, WIFI_NVS_NAMESPACE, true)?;
+        let provisioned = option_env!("WIFI_SSID").is_none();
+        let wifi_config = if provisioned {
+            WifiConfig::load(&wifi_nvs)?.map_or_else(
+                || wifi::provision(&mut wifi_driver, &wifi_nvs, hw::app_name()),
+                Ok,
+            )?
+        } else {
+            WifiConfig::from_env()?
+        };
+        // Clear wrong provisioned credentials rather than retrying them on every
+        // boot, so that the restart opens the setup access point
+        let mut wifi = Connection::new(wifi_driver, &wifi_config).or_else(|e| {
+            if provisioned {
+                WifiConfig::clear(&wifi_nvs)?;
+                Err(e.context(format!(
+                    "Failed to connect to {}, cleared the stored credentials",
+                    wifi_config.ssid()
+                )))
+            } else {
+                Err(e)
+            }
+        })?;
+        wifi.advertise(&hw::app_name().to_lowercase(), HTTP_SERVER_PORT)?;
+        wifi.sync_time(option_env!("NTP_SERVER").unwrap_or(DEFAULT_NTP_SERVER))?;
+        let mut http = match option_env!("HTTP_TIMEOUT_MS")
+            .map(str::parse::<u64>)
+            .transpose()?
+        {
+            Some(timeout_ms) => {
+                Client::new(wifi)?.with_timeout(Duration::from_millis(timeout_ms))?
+            }
+            None => Client::new(wifi)?,
+        };
+        // HTTP_CONFIG_URL demonstrates fetching a configuration once online
+        if let Some(config_url) = option_env!("HTTP_CONFIG_URL") {
+            match http.get_string(config_url) {
+                Ok((status, config)) if (200..300).contains(&status) => {
+                    info!("Fetched configuration: {config}");
+                }
+                Ok((status, _)) => {
+                    warn!("Configuration request failed with status {status}");
+                }
+                Err(e) => warn!("Failed to fetch configuration: {e}"),
+            }
+        }
+        if option_env!("OTA_URL").is_some() {
+            ota::mark_valid()?;
+        }
+
+        let mut wifi_monitor = Monitor::new(
+            dispatcher.notifier()?,
+            &Trigger::WifiConnected,
+            &Trigger::WifiDisconnected,
+            DEFAULT_MONITOR_DEBOUNCE,
+        );
+        thread::spawn(move || wifi_monitor.poll());
 
-        let core = Core::new(State::on(), dispatcher, advertiser, led, led_timer)?;
-        let mut sm = StateMachine::new(core, http, ble_payload)?;
+        let mut core = Core::builder()
+            .dispatcher(dispatcher)
+            .advertiser(advertiser)
+            .led(led)
+            .timer(led_timer)
+            .battery_warning(hw::battery_warning_color()?)
+            .deep_sleep(deep_sleep_gpio)
+            .build()?;
+
+        // Serve remote control and recent events for diagnostics, the server
+        // must outlive the state machine for the endpoints to stay up
+        let mut server = Server::new(
+            core.status(),
+            Arc::clone(&button_state),
+            core.dispatcher.notifier()?,
+            &Trigger::ButtonPressed,
+        )?;
+        let events = EventLog::new(DIAG_EVENTS_CAPACITY);
+        events.serve(server.server_mut())?;
+        core.set_event_log(events);
+
+        let mut sm = StateMachine::new(
+            core,
+            http,
+            ble_payload,
+            heartbeat_timer,
+            button_state,
+            hw::idle_timeout_s()?,
+        )?
+        .with_status_led(status_led)?;
 
         sm.run()
     })
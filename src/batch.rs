@@ -0,0 +1,141 @@
+use anyhow::{ensure, Result};
+use std::collections::VecDeque;
+
+/// A bounded queue accumulating items, e.g. speed samples, to upload them in
+/// batches rather than one request each.
+///
+/// A batch is due once it holds the batch size, or once its oldest item waited
+/// for the interval, whichever comes first. Items are only removed by
+/// [`Batch::clear`], so a failed upload keeps them for the next attempt, which
+/// [`Batch::defer`] postpones by the interval. Once the queue is full, pushing
+/// an item drops the oldest one, which bounds the memory used while offline.
+///
+/// Time is passed in by callers, in milliseconds since any fixed origin such
+/// as boot, so that the queue does not depend on a clock.
+pub struct Batch<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+    size: usize,
+    interval_ms: u64,
+    since_ms: Option<u64>,
+    deferred_until_ms: Option<u64>,
+}
+
+impl<T> Batch<T> {
+    /// Creates a new, empty `Batch`.
+    ///
+    /// # Arguments
+    /// * `capacity` - Maximum number of items kept while they cannot be
+    ///   uploaded.
+    /// * `batch_size` - Number of items making a batch due, at most
+    ///   `capacity`.
+    /// * `interval_ms` - How long the oldest item waits before a smaller batch
+    ///   is due, in milliseconds.
+    ///
+    /// # Returns
+    /// A new `Batch` with room for `capacity` items.
+    ///
+    /// # Errors
+    /// Returns an error if `batch_size` is zero or exceeds `capacity`.
+    pub fn new(
+        capacity: usize,
+        batch_size: usize,
+        interval_ms: u64,
+    ) -> Result<Self> {
+        ensure!(
+            batch_size > 0 && batch_size <= capacity,
+            "Invalid batch size {batch_size}, expected 1 to {capacity}"
+        );
+
+        Ok(Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+            size: batch_size,
+            interval_ms,
+            since_ms: None,
+            deferred_until_ms: None,
+        })
+    }
+
+    /// Queues an item, dropping the oldest one when the queue is full.
+    ///
+    /// # Arguments
+    /// * `item` - The item to queue.
+    /// * `now_ms` - The current time, in milliseconds.
+    ///
+    /// # Returns
+    /// The dropped item, if any.
+    pub fn push(&mut self, item: T, now_ms: u64) -> Option<T> {
+        let dropped = (self.items.len() >= self.capacity)
+            .then(|| self.items.pop_front())
+            .flatten();
+        self.items.push_back(item);
+        self.since_ms.get_or_insert(now_ms);
+
+        dropped
+    }
+
+    /// Checks whether the queued items should be uploaded.
+    ///
+    /// # Arguments
+    /// * `now_ms` - The current time, in milliseconds.
+    ///
+    /// # Returns
+    /// `true` if the batch size is reached or the oldest item waited for the
+    /// interval, `false` otherwise, including when the queue is empty or the
+    /// batch is deferred.
+    #[must_use]
+    pub fn is_due(&self, now_ms: u64) -> bool {
+        let deferred = self
+            .deferred_until_ms
+            .is_some_and(|until_ms| now_ms < until_ms);
+
+        !deferred
+            && (self.items.len() >= self.size
+                || self.since_ms.is_some_and(|since_ms| {
+                    now_ms.saturating_sub(since_ms) >= self.interval_ms
+                }))
+    }
+
+    /// Postpones the batch by the interval, e.g. after a failed upload, so
+    /// that it is not retried right away even when full.
+    ///
+    /// # Arguments
+    /// * `now_ms` - The current time, in milliseconds.
+    pub fn defer(&mut self, now_ms: u64) {
+        self.deferred_until_ms = Some(now_ms.saturating_add(self.interval_ms));
+    }
+
+    /// Returns the queued items, oldest first.
+    ///
+    /// # Returns
+    /// An iterator over the items.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    /// Returns the number of queued items.
+    ///
+    /// # Returns
+    /// The number of items, at most the capacity.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Checks whether no item is queued.
+    ///
+    /// # Returns
+    /// `true` if the queue is empty, `false` otherwise.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Removes all queued items, e.g. once they were uploaded.
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.since_ms = None;
+        self.deferred_until_ms = None;
+    }
+}
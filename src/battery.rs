@@ -0,0 +1,197 @@
+use anyhow::Result;
+use esp_idf_hal::{
+    adc::{
+        attenuation::DB_11,
+        oneshot::{config::AdcChannelConfig, AdcChannelDriver, AdcDriver},
+    },
+    gpio::ADCPin,
+    peripheral::Peripheral,
+};
+use std::fmt::Display;
+
+use crate::{
+    infra::Poller,
+    message::{Notifier, Trigger},
+    time::sleep,
+};
+
+/// Single-cell `LiPo` discharge curve as `(millivolts, percent)` points, in
+/// ascending order. Percentages in between are linearly interpolated.
+const DISCHARGE_CURVE: [(u32, u8); 8] = [
+    (3300, 0),
+    (3500, 5),
+    (3600, 10),
+    (3700, 30),
+    (3800, 55),
+    (3900, 70),
+    (4000, 85),
+    (4200, 100),
+];
+
+/// Estimates the remaining charge of a single-cell `LiPo` battery.
+fn percent(millivolts: u32) -> u8 {
+    DISCHARGE_CURVE
+        .windows(2)
+        .find(|w| millivolts < w[1].0)
+        .map_or(100, |w| {
+            let ((lo_mv, lo_pct), (hi_mv, hi_pct)) = (w[0], w[1]);
+            let span = u32::from(hi_pct - lo_pct);
+            let offset = millivolts.saturating_sub(lo_mv) * span / (hi_mv - lo_mv);
+            lo_pct + u8::try_from(offset).unwrap_or(u8::MAX)
+        })
+}
+
+/// A battery reading.
+///
+/// # Fields
+/// * `millivolts` - Battery voltage in millivolts.
+/// * `percent` - Estimated remaining charge, `0` to `100`.
+pub struct Reading {
+    millivolts: u32,
+    percent: u8,
+}
+
+impl Reading {
+    /// Returns the battery voltage in millivolts.
+    ///
+    /// # Returns
+    /// The voltage as `u32`.
+    #[must_use]
+    pub fn millivolts(&self) -> u32 {
+        self.millivolts
+    }
+
+    /// Returns the estimated remaining charge.
+    ///
+    /// # Returns
+    /// The charge as a percentage, `0` to `100`.
+    #[must_use]
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+}
+
+impl Display for Reading {
+    /// Formats the reading as `{millivolts} mV ({percent}%)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} mV ({}%)", self.millivolts, self.percent)
+    }
+}
+
+/// Represents a battery voltage monitor on an ADC pin.
+///
+/// The battery is expected to be a single `LiPo` cell wired to the pin through a
+/// resistor voltage divider, since the cell voltage (up to 4.2 V) exceeds the
+/// ADC input range. The divider is board-specific: `divider_ratio` is the
+/// battery voltage divided by the pin voltage, e.g. `2.0` for two equal
+/// resistors. The ADC is read with 11 dB attenuation, which covers pin voltages
+/// up to about 2.5 V accurately.
+///
+/// # Type Parameters
+/// * `'a` - Lifetime of the monitor.
+/// * `P` - The ADC-capable GPIO pin the battery is sensed on.
+/// * `T` - The trigger type implementing the `Trigger` trait.
+pub struct BatteryMonitor<'a, P: ADCPin, T: Trigger> {
+    notifier: Notifier<T>,
+    low_trigger: &'static T,
+    ok_trigger: &'static T,
+    channel: AdcChannelDriver<'a, P, AdcDriver<'a, P::Adc>>,
+    divider_ratio: f32,
+    low_millivolts: u32,
+}
+
+impl<'a, P: ADCPin, T: Trigger> BatteryMonitor<'a, P, T> {
+    /// Interval between two readings when polling, in milliseconds.
+    const INTERVAL: u32 = 30_000;
+
+    /// Creates a new `BatteryMonitor` instance.
+    ///
+    /// # Arguments
+    /// * `notifier` - A notifier to send battery level events.
+    /// * `low_trigger` - The trigger to emit when the battery runs low.
+    /// * `ok_trigger` - The trigger to emit when the battery recovers (e.g. when charging).
+    /// * `adc` - The ADC unit the pin belongs to.
+    /// * `pin` - The GPIO pin sensing the battery voltage.
+    /// * `divider_ratio` - Battery voltage divided by the voltage seen on the pin.
+    /// * `low_millivolts` - Battery voltage below which the battery is considered low.
+    ///
+    /// # Returns
+    /// A new `BatteryMonitor` ready to read or poll.
+    ///
+    /// # Errors
+    /// Returns an error if the ADC unit or channel cannot be configured.
+    pub fn new(
+        notifier: Notifier<T>,
+        low_trigger: &'static T,
+        ok_trigger: &'static T,
+        adc: impl Peripheral<P = P::Adc> + 'a,
+        pin: impl Peripheral<P = P> + 'a,
+        divider_ratio: f32,
+        low_millivolts: u32,
+    ) -> Result<Self> {
+        let config = AdcChannelConfig {
+            attenuation: DB_11,
+            calibration: true,
+            ..Default::default()
+        };
+        let channel = AdcChannelDriver::new(AdcDriver::new(adc)?, pin, &config)?;
+
+        Ok(Self {
+            notifier,
+            low_trigger,
+            ok_trigger,
+            channel,
+            divider_ratio,
+            low_millivolts,
+        })
+    }
+
+    /// Reads the battery voltage.
+    ///
+    /// # Returns
+    /// A `Reading` with the battery voltage and estimated remaining charge.
+    ///
+    /// # Errors
+    /// Returns an error if the ADC cannot be read.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn read(&mut self) -> Result<Reading> {
+        let pin_millivolts = self.channel.read()?;
+        let millivolts =
+            (f32::from(pin_millivolts) * self.divider_ratio).round() as u32;
+
+        Ok(Reading {
+            millivolts,
+            percent: percent(millivolts),
+        })
+    }
+}
+
+impl<P: ADCPin, T: Trigger> Poller for BatteryMonitor<'_, P, T> {
+    /// Periodically reads the battery voltage and reports when it runs low.
+    ///
+    /// The low trigger is emitted once when the voltage drops below the low
+    /// threshold, and the ok trigger once when it rises back above it (e.g.
+    /// when charging).
+    ///
+    /// # Errors
+    /// Returns an error if the ADC cannot be read or the notification fails.
+    fn poll(&mut self) -> Result<!> {
+        let mut low = false;
+        loop {
+            let reading = self.read()?;
+            log::debug!("Battery: {reading}");
+
+            let below = reading.millivolts() < self.low_millivolts;
+            if below != low {
+                self.notifier.notify(if below {
+                    self.low_trigger
+                } else {
+                    self.ok_trigger
+                })?;
+                low = below;
+            }
+
+            sleep(Self::INTERVAL);
+        }
+    }
+}
@@ -1,15 +1,24 @@
-use anyhow::{anyhow, Result};
+use anyhow::{ensure, Result};
+#[cfg(feature = "hardware")]
 use esp32_nimble::{
     enums::{PowerLevel, PowerType},
-    BLEAdvertisementData, BLEDevice, BLEScan,
+    BLEAdvertisedData, BLEAdvertisementData, BLEDevice, BLEScan,
 };
+#[cfg(feature = "hardware")]
 use esp_idf_hal::task::block_on;
+#[cfg(feature = "hardware")]
+use log::debug;
+#[cfg(feature = "hardware")]
 use std::sync::{Arc, Mutex};
 
+use crate::clock::{next_deadline, Pace};
+#[cfg(feature = "hardware")]
 use crate::{
     clock::Timer,
-    infra::{Poller, State, Switch},
+    error::Error,
+    infra::{self, Poller, State, Switch},
     message::{Notifier, Trigger},
+    protocol::{crc8, pack, unpack},
 };
 
 /// Initializes the BLE device with the specified power level for advertising and scanning.
@@ -22,24 +31,63 @@ use crate::{
 ///
 /// # Errors
 /// Returns an error if the BLE device cannot be configured with the specified power levels.
+#[cfg(feature = "hardware")]
 pub fn initialize(power_level: PowerLevel) -> Result<()> {
     let device = BLEDevice::take();
-    device.set_power(PowerType::Advertising, power_level)?;
-    device.set_power(PowerType::Scan, power_level)?;
+    device
+        .set_power(PowerType::Advertising, power_level)
+        .and_then(|()| device.set_power(PowerType::Scan, power_level))
+        .map_err(|e| Error::BleFailure(format!("{e:?}")))?;
 
     Ok(())
 }
 
+/// Separator between the advertised name and its checksum.
+#[cfg(feature = "hardware")]
+const CHECKSUM_SEPARATOR: char = ':';
+
+/// Appends the checksum of a name to it, as advertised over the air.
+#[cfg(feature = "hardware")]
+fn sign_name(name: &str) -> String {
+    format!("{name}{CHECKSUM_SEPARATOR}{:02X}", crc8(name.as_bytes()))
+}
+
+/// Strips and validates the checksum of an advertised name.
+///
+/// Returns the bare name, or `None` if the checksum is missing or does not match.
+#[cfg(feature = "hardware")]
+fn verify_name(advertised: &str) -> Option<&str> {
+    advertised
+        .rsplit_once(CHECKSUM_SEPARATOR)
+        .filter(|(name, checksum)| {
+            *checksum == format!("{:02X}", crc8(name.as_bytes()))
+        })
+        .map(|(name, _)| name)
+}
+
 /// Function type for deriving advertisement name and payload from state.
+#[cfg(feature = "hardware")]
 type DeriveFn = fn(&State, Option<&[u8]>) -> (String, Option<Vec<u8>>);
 
 /// Represents a BLE advertiser.
+///
+/// The derived name is advertised as `<name>:<checksum>`, where `<checksum>` is
+/// the CRC-8 (polynomial `0x07`, initial value `0x00`) of the UTF-8 bytes of
+/// `<name>`, written as two uppercase hexadecimal digits (e.g. `esp-flow-Active:32`).
+/// [`Scanner`] drops advertisements whose checksum does not match, so devices
+/// interoperating with it must follow the same format. The payload is likewise
+/// sent as manufacturer data framed by [`pack`], and dropped by the scanner
+/// when [`unpack`] rejects it. Since it does not fit in a legacy advertisement
+/// next to the name, it is sent in the scan response, which [`Scanner`]
+/// requests with active scans.
+#[cfg(feature = "hardware")]
 pub struct Advertiser {
     state: State,
     payload: Option<Vec<u8>>,
     derive: DeriveFn,
 }
 
+#[cfg(feature = "hardware")]
 impl Advertiser {
     /// Creates a new `Advertiser` instance.
     ///
@@ -73,13 +121,20 @@ impl Advertiser {
         let (name, payload) = (self.derive)(&self.state, self.payload.as_deref());
 
         let mut data = BLEAdvertisementData::new();
-        data.name(&name);
+        data.name(&sign_name(&name));
+        // The payload does not fit next to the name in a legacy advertisement
+        let mut response = BLEAdvertisementData::new();
         if let Some(bytes) = &payload {
-            data.manufacturer_data(bytes);
+            response.manufacturer_data(&pack(bytes)?);
         }
 
-        advertising.lock().set_data(&mut data)?;
-        advertising.lock().start()?;
+        let mut advertising = advertising.lock();
+        advertising.scan_response(payload.is_some());
+        advertising
+            .set_data(&mut data)
+            .and_then(|()| advertising.set_scan_response_data(&mut response))
+            .and_then(|()| advertising.start())
+            .map_err(|e| Error::BleFailure(format!("{e:?}")))?;
 
         Ok(())
     }
@@ -100,7 +155,19 @@ impl Advertiser {
     }
 }
 
+#[cfg(feature = "hardware")]
 impl Switch for Advertiser {
+    /// Returns whether the advertiser currently advertises itself as on.
+    ///
+    /// # Returns
+    /// The current `State`.
+    ///
+    /// # Errors
+    /// Never fails.
+    fn state(&self) -> Result<State> {
+        Ok(self.state)
+    }
+
     /// Toggles the state of the advertiser.
     ///
     /// # Returns
@@ -115,17 +182,24 @@ impl Switch for Advertiser {
     }
 }
 
+/// Default number of consecutive empty scans before the default trigger is
+/// emitted.
+pub const DEFAULT_MAX_MISSES: u32 = 3;
+
 /// Configuration for BLE scanning behavior.
 ///
 /// # Type Parameters
 /// * `T` - The trigger type implementing the `Trigger` trait.
+#[cfg(feature = "hardware")]
 pub struct ScannerConfig<T: Trigger> {
     triggers: fn(&str) -> Option<&'static T>,
     default_trigger: &'static T,
     payload_trigger: &'static T,
     scan_freq_hz: u64,
+    max_misses: u32,
 }
 
+#[cfg(feature = "hardware")]
 impl<T: Trigger> ScannerConfig<T> {
     /// Creates a new scan configuration.
     ///
@@ -149,8 +223,114 @@ impl<T: Trigger> ScannerConfig<T> {
             default_trigger,
             payload_trigger,
             scan_freq_hz,
+            max_misses: DEFAULT_MAX_MISSES,
         }
     }
+
+    /// Sets how many consecutive scans must find no device before the default
+    /// trigger is emitted.
+    ///
+    /// A device at the edge of range is easily missed by a single scan, so
+    /// requiring several misses avoids flickering between found and not found.
+    /// A scan finding a device resets the count.
+    ///
+    /// # Arguments
+    /// * `max_misses` - The number of empty scans, [`DEFAULT_MAX_MISSES`] by
+    ///   default. 0 or 1 emit the default trigger after every empty scan.
+    ///
+    /// # Returns
+    /// The updated `ScannerConfig`.
+    #[must_use]
+    pub fn with_max_misses(mut self, max_misses: u32) -> Self {
+        self.max_misses = max_misses;
+        self
+    }
+}
+
+/// Fixed-rate pacing and consecutive miss count of a [`Scanner`], kept apart
+/// from the BLE stack so that they can be driven by a fake clock.
+///
+/// # Type Parameters
+/// * `C` - The clock pacing the scans.
+pub struct ScanSchedule<C: Pace> {
+    clock: C,
+    period: u64,
+    deadline: u64,
+    max_misses: u32,
+    misses: u32,
+}
+
+impl<C: Pace> ScanSchedule<C> {
+    /// Creates a schedule whose first scan is due one period from now.
+    ///
+    /// # Arguments
+    /// * `clock` - The clock pacing the scans.
+    /// * `freq_hz` - Scan frequency in Hz.
+    /// * `max_misses` - The number of consecutive empty scans after which
+    ///   [`ScanSchedule::record`] reports the default trigger as due.
+    ///
+    /// # Returns
+    /// A new `ScanSchedule` instance.
+    ///
+    /// # Errors
+    /// Returns an error if `freq_hz` is zero.
+    pub fn new(clock: C, freq_hz: u64, max_misses: u32) -> Result<Self> {
+        ensure!(freq_hz > 0, "Scan frequency must be positive");
+        let period = clock.period(freq_hz);
+        let deadline = clock.now_ticks();
+
+        Ok(Self {
+            clock,
+            period,
+            deadline,
+            max_misses,
+            misses: 0,
+        })
+    }
+
+    /// Waits until the next scan is due.
+    ///
+    /// Scans start on a fixed-rate schedule so the scan duration does not make
+    /// the cadence drift; if a scan overruns one or more periods, the missed
+    /// starts are skipped rather than run back-to-back, see [`next_deadline`].
+    ///
+    /// # Returns
+    /// `Ok(())` once the next scan is due.
+    ///
+    /// # Errors
+    /// Returns an error if the clock cannot delay.
+    pub async fn wait(&mut self) -> Result<()> {
+        self.deadline =
+            next_deadline(self.deadline, self.period, self.clock.now_ticks());
+        self.clock.delay_until(self.deadline).await
+    }
+
+    /// Records the outcome of a scan.
+    ///
+    /// # Arguments
+    /// * `found` - Whether the scan found a matching device, which resets the
+    ///   miss count.
+    ///
+    /// # Returns
+    /// `true` if the scan found nothing and the number of consecutive empty
+    /// scans reached the configured maximum, i.e. the default trigger is due.
+    pub fn record(&mut self, found: bool) -> bool {
+        self.misses = if found {
+            0
+        } else {
+            self.misses.saturating_add(1)
+        };
+
+        !found && self.misses >= self.max_misses
+    }
+
+    /// Returns the clock pacing the scans, e.g. to advance a fake clock.
+    ///
+    /// # Returns
+    /// A mutable reference to the clock.
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
 }
 
 /// Represents a BLE scanner.
@@ -158,9 +338,11 @@ impl<T: Trigger> ScannerConfig<T> {
 /// # Type Parameters
 /// * `'a` - Lifetime of the scanner.
 /// * `T` - The trigger type implementing the `Trigger` trait.
-pub struct Scanner<'a, T: Trigger> {
+/// * `C` - The clock pacing the scans, a hardware [`Timer`] by default.
+#[cfg(feature = "hardware")]
+pub struct Scanner<'a, T: Trigger, C: Pace = Timer<'a, T>> {
     notifier: Notifier<T>,
-    timer: Timer<'a, T>,
+    schedule: ScanSchedule<C>,
     state: Arc<Mutex<State>>,
     payload: Arc<Mutex<Option<Vec<u8>>>>,
     device: &'a BLEDevice,
@@ -168,7 +350,8 @@ pub struct Scanner<'a, T: Trigger> {
     config: ScannerConfig<T>,
 }
 
-impl<'a, T: Trigger> Scanner<'a, T> {
+#[cfg(feature = "hardware")]
+impl<'a, T: Trigger, C: Pace> Scanner<'a, T, C> {
     /// BLE scan window duration in milliseconds.
     const WINDOW: i32 = 1000;
 
@@ -176,7 +359,7 @@ impl<'a, T: Trigger> Scanner<'a, T> {
     ///
     /// # Arguments
     /// * `notifier` - A notifier to send scan results.
-    /// * `timer` - A timer for scan intervals.
+    /// * `timer` - The clock pacing the scans.
     /// * `state` - Shared state of the scanner.
     /// * `payload` - Shared storage for BLE payload data.
     /// * `config` - Scan configuration (triggers, frequency, etc.).
@@ -185,20 +368,24 @@ impl<'a, T: Trigger> Scanner<'a, T> {
     /// A new `Scanner` ready to poll.
     ///
     /// # Errors
-    /// Returns an error if the scanner cannot be initialized.
+    /// Returns an error if the scan frequency is zero.
     pub fn new(
         notifier: Notifier<T>,
-        timer: Timer<'a, T>,
+        timer: C,
         state: Arc<Mutex<State>>,
         payload: Arc<Mutex<Option<Vec<u8>>>>,
         config: ScannerConfig<T>,
     ) -> Result<Self> {
         let device = BLEDevice::take();
-        let scan = BLEScan::new();
+        let mut scan = BLEScan::new();
+        // Payloads are only sent in scan responses
+        scan.active_scan(true);
+        let schedule =
+            ScanSchedule::new(timer, config.scan_freq_hz, config.max_misses)?;
 
         Ok(Self {
             notifier,
-            timer,
+            schedule,
             state,
             payload,
             device,
@@ -213,67 +400,77 @@ impl<'a, T: Trigger> Scanner<'a, T> {
     /// Returns an error if the scan fails.
     async fn do_scan(&mut self) -> Result<Option<&'static T>> {
         let triggers = self.config.triggers;
-        let payload = Arc::clone(&self.payload);
         let payload_trigger = self.config.payload_trigger;
-        Ok(self
+        let payload = &self.payload;
+        let store = |data: &BLEAdvertisedData<&[u8]>| {
+            data.manufacture_data().map(|mfg| {
+                // manufacture_data() splits the raw bytes into a 2-byte
+                // company_identifier and the remaining payload. We reconstruct
+                // the original bytes here.
+                let mut full = mfg.company_identifier.to_le_bytes().to_vec();
+                full.extend_from_slice(mfg.payload);
+                match unpack(&full) {
+                    Ok(bytes) => *infra::lock(payload) = Some(bytes),
+                    Err(e) => debug!("Dropping BLE payload: {e}"),
+                }
+            })
+        };
+        // The payload comes in the scan response, reported after the
+        // advertisement carrying the name
+        let mut awaiting = None;
+        let found = self
             .scan
-            .start(self.device, Self::WINDOW, move |_, data| {
-                data.name().and_then(|name| {
-                    let name = String::from_utf8_lossy(name);
-                    if let Some(trigger) = triggers(&name) {
-                        if trigger == payload_trigger {
-                            if let Some(mfg) = data.manufacture_data() {
-                                if let Ok(mut stored) = payload.lock() {
-                                    // manufacture_data() splits the raw bytes into a
-                                    // 2-byte company_identifier and the remaining payload.
-                                    // We reconstruct the original bytes here.
-                                    let mut full = mfg
-                                        .company_identifier
-                                        .to_le_bytes()
-                                        .to_vec();
-                                    full.extend_from_slice(mfg.payload);
-                                    *stored = Some(full);
-                                }
-                            }
-                        }
+            .start(self.device, Self::WINDOW, |device, data| {
+                let named = data.name().and_then(|name| {
+                    verify_name(&String::from_utf8_lossy(name)).and_then(triggers)
+                });
+                match (named, awaiting) {
+                    (Some(trigger), _) if trigger != payload_trigger => {
                         Some(trigger)
-                    } else {
-                        None
                     }
-                })
+                    (Some(trigger), _) => {
+                        awaiting = Some((*device.addr(), trigger));
+                        store(&data).map(|()| trigger)
+                    }
+                    (None, Some((addr, trigger))) if *device.addr() == addr => {
+                        store(&data).map(|()| trigger)
+                    }
+                    _ => None,
+                }
             })
-            .await?)
+            .await?;
+
+        // The device was found even if its scan response was missed
+        Ok(found.or(awaiting.map(|(_, trigger)| trigger)))
     }
 }
 
-impl<T: Trigger> Poller for Scanner<'_, T> {
+#[cfg(feature = "hardware")]
+impl<T: Trigger, C: Pace> Poller for Scanner<'_, T, C> {
     /// Polls the BLE scanner for devices.
     ///
     /// This function continuously scans for BLE devices and notifies the results.
+    /// Scans are paced by a [`ScanSchedule`], and the default trigger is only
+    /// emitted once the configured number of consecutive scans found no device.
     ///
     /// # Errors
     /// Returns an error if the scan or notification fails.
     fn poll(&mut self) -> Result<!> {
         block_on(async {
             loop {
-                self.timer.delay(self.config.scan_freq_hz).await?;
-
-                if self
-                    .state
-                    .lock()
-                    .map_err(|e| anyhow!("Mutex lock error: {:?}", e))?
-                    .is_off()
-                {
+                self.schedule.wait().await?;
+
+                if infra::lock(&self.state).is_off() {
                     continue;
                 }
 
-                let trigger = if let Some(trigger) = self.do_scan().await? {
-                    trigger
-                } else {
-                    self.config.default_trigger
-                };
-
-                self.notifier.notify(trigger)?;
+                let found = self.do_scan().await?;
+                if self.schedule.record(found.is_some()) {
+                    self.notifier.notify(self.config.default_trigger)?;
+                }
+                if let Some(trigger) = found {
+                    self.notifier.notify(trigger)?;
+                }
             }
         })
     }
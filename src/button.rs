@@ -1,15 +1,112 @@
 use anyhow::{anyhow, Result};
-use esp_idf_hal::gpio::{InputMode, InputPin, PinDriver};
-use std::sync::{Arc, Mutex};
+use esp_idf_hal::{
+    delay::BLOCK,
+    gpio::{InputMode, InputPin, InterruptType, PinDriver},
+    sys::{esp, touch_pad_config, touch_pad_init, touch_pad_read, touch_pad_t},
+    task::notification::Notification,
+};
+use log::info;
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use crate::{
-    infra::{Poller, State, Switch},
+    infra::{self, Poller, State, Switch},
     message::{Notifier, Trigger},
     time::{sleep, yield_now},
 };
 
+/// GPIOs of the ESP32 touch pads, indexed by pad number.
+const TOUCH_PAD_GPIOS: [i32; 10] = [4, 0, 2, 15, 13, 12, 14, 27, 33, 32];
+
+/// Number of readings averaged into the untouched baseline of a touch pad.
+const TOUCH_CALIBRATION_SAMPLES: u32 = 16;
+
+/// Delay between two calibration readings, in milliseconds.
+const TOUCH_CALIBRATION_INTERVAL_MS: u32 = 10;
+
+/// Default drop below the baseline, in percent, that makes a touch pad read
+/// as pressed.
+pub const DEFAULT_TOUCH_THRESHOLD_PERCENT: u8 = 20;
+
+// Press handling shared by buttons whatever their input: notifies presses and
+// long presses, and toggles the shared state if any.
+struct Presses<TR: Trigger> {
+    notifier: Notifier<TR>,
+    trigger: &'static TR,
+    state: Option<Arc<Mutex<State>>>,
+    long_press: Option<(&'static TR, Duration)>,
+}
+
+impl<TR: Trigger> Presses<TR> {
+    fn new(notifier: Notifier<TR>, trigger: &'static TR) -> Self {
+        Self {
+            notifier,
+            trigger,
+            state: None,
+            long_press: None,
+        }
+    }
+
+    /// Waits while the button stays pressed, for at most `hold`.
+    ///
+    /// # Returns
+    /// `true` if the button is still pressed after `hold`, `false` if it was released.
+    fn held(pressed: &impl Fn() -> bool, hold: Duration) -> bool {
+        let start = Instant::now();
+        while pressed() && start.elapsed() < hold {
+            yield_now();
+        }
+        pressed()
+    }
+
+    /// Reports a press, then waits for the button to settle.
+    ///
+    /// # Errors
+    /// Returns an error if the notifier fails or if the state cannot be toggled.
+    fn handle(&mut self, pressed: impl Fn() -> bool) -> Result<()> {
+        match self.long_press {
+            Some((trigger, hold)) if Self::held(&pressed, hold) => {
+                self.notifier.notify(trigger)?;
+                while pressed() {
+                    yield_now();
+                }
+            }
+            _ => {
+                self.notifier.notify(self.trigger)?;
+                self.toggle();
+            }
+        }
+        // Debounce
+        sleep(500);
+
+        Ok(())
+    }
+
+    fn state(&self) -> State {
+        self.state
+            .as_ref()
+            .map_or(State::off(), |state| *infra::lock(state))
+    }
+
+    fn toggle(&mut self) {
+        if let Some(state) = &self.state {
+            infra::lock(state).toggle();
+        }
+    }
+}
+
 /// Represents a button with a notifier and a GPIO pin.
 ///
+/// Several buttons can share one dispatcher: each is created with a notifier
+/// from the same `Dispatcher` and its own trigger. Since triggers are distinct
+/// bits, the state machine tells which button fired by checking which triggers
+/// the dispatcher collected, e.g. `triggers.contains(&Trigger::ModePressed)`.
+/// Only the designated power button should be given the shared state with
+/// [`Button::with_state`], so that the other buttons do not toggle scanning.
+///
 /// # Type Parameters
 /// * `'a` - Lifetime of the button.
 /// * `T` - Type of the GPIO pin.
@@ -21,10 +118,9 @@ where
     MODE: InputMode,
     TR: Trigger,
 {
-    notifier: Notifier<TR>,
-    trigger: &'static TR,
+    presses: Presses<TR>,
     pin: PinDriver<'a, T, MODE>,
-    state: Arc<Mutex<State>>,
+    interrupt: bool,
 }
 
 impl<'a, T, MODE, TR> Button<'a, T, MODE, TR>
@@ -39,7 +135,6 @@ where
     /// * `notifier` - A notifier to send button press events.
     /// * `trigger` - The trigger to emit when the button is pressed.
     /// * `pin` - A GPIO pin driver.
-    /// * `state` - Shared state of the button.
     ///
     /// # Returns
     /// A new `Button` instance.
@@ -50,16 +145,76 @@ where
         notifier: Notifier<TR>,
         trigger: &'static TR,
         pin: PinDriver<'a, T, MODE>,
-        state: Arc<Mutex<State>>,
     ) -> Result<Self> {
         Ok(Self {
-            notifier,
-            trigger,
+            presses: Presses::new(notifier, trigger),
             pin,
-            state,
+            interrupt: false,
+        })
+    }
+
+    /// Creates a new `Button` instance woken up by the pin's falling edge
+    /// interrupt instead of busy polling, which saves CPU time and power.
+    ///
+    /// Presses are then debounced and reported exactly like with [`Button::new`].
+    /// Avoid this on boards where the button's GPIO is routed close to the
+    /// Wi-Fi antenna (e.g. M5Stack's Atom Lite): radio interference then fires
+    /// spurious interrupts, and polling should be used instead.
+    ///
+    /// # Arguments
+    /// * `notifier` - A notifier to send button press events.
+    /// * `trigger` - The trigger to emit when the button is pressed.
+    /// * `pin` - A GPIO pin driver.
+    ///
+    /// # Returns
+    /// A new `Button` instance.
+    ///
+    /// # Errors
+    /// Returns an error if the pin interrupt cannot be configured.
+    pub fn new_interrupt(
+        notifier: Notifier<TR>,
+        trigger: &'static TR,
+        mut pin: PinDriver<'a, T, MODE>,
+    ) -> Result<Self> {
+        pin.set_interrupt_type(InterruptType::NegEdge)?;
+
+        Ok(Self {
+            interrupt: true,
+            ..Self::new(notifier, trigger, pin)?
         })
     }
 
+    /// Makes this button the power button, toggling the shared state on each press.
+    ///
+    /// # Arguments
+    /// * `state` - State shared with the other components (e.g. the BLE scanner).
+    ///
+    /// # Returns
+    /// The `Button` with the shared state attached.
+    #[must_use]
+    pub fn with_state(mut self, state: Arc<Mutex<State>>) -> Self {
+        self.presses.state = Some(state);
+        self
+    }
+
+    /// Emits a distinct trigger when the button is held down.
+    ///
+    /// A long press does not toggle the button state. Once enabled, short
+    /// presses are only reported on release, when they are known not to be
+    /// long presses.
+    ///
+    /// # Arguments
+    /// * `trigger` - The trigger to emit on a long press.
+    /// * `hold_ms` - How long the button must be held, in milliseconds.
+    ///
+    /// # Returns
+    /// The `Button` with long press detection enabled.
+    #[must_use]
+    pub fn with_long_press(mut self, trigger: &'static TR, hold_ms: u64) -> Self {
+        self.presses.long_press = Some((trigger, Duration::from_millis(hold_ms)));
+        self
+    }
+
     /// Checks if the button is pressed.
     ///
     /// # Returns
@@ -67,6 +222,42 @@ where
     fn pressed(&self) -> bool {
         self.pin.is_low()
     }
+
+    /// Reports a press, then waits for the button to settle.
+    ///
+    /// # Errors
+    /// Returns an error if the notifier fails or if the state cannot be toggled.
+    fn handle_press(&mut self) -> Result<()> {
+        let pin = &self.pin;
+        self.presses.handle(|| pin.is_low())
+    }
+
+    /// Blocks on the pin interrupt and handles presses as they come.
+    ///
+    /// The interrupt is disabled each time it fires, and only re-enabled once
+    /// the press has been handled, so bounces are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if the interrupt cannot be configured, or if handling
+    /// a press fails.
+    fn wait_interrupts(&mut self) -> Result<!> {
+        // The notification must be created by the task waiting on it.
+        let notification = Notification::new();
+        let waker = notification.notifier();
+        unsafe {
+            self.pin.subscribe(move || {
+                waker.notify_and_yield(NonZeroU32::MIN);
+            })?;
+        }
+
+        loop {
+            self.pin.enable_interrupt()?;
+            notification.wait(BLOCK);
+            if self.pressed() {
+                self.handle_press()?;
+            }
+        }
+    }
 }
 
 impl<T, MODE, TR> Poller for Button<'_, T, MODE, TR>
@@ -77,20 +268,23 @@ where
 {
     /// Polls the button for state changes.
     ///
-    /// This function continuously checks the button state and notifies when it is pressed.
+    /// This function continuously checks the button state and notifies when it is pressed,
+    /// or when it is held down if long press detection is enabled. Buttons created with
+    /// [`Button::new_interrupt`] sleep until the pin interrupt fires instead.
     ///
     /// # Errors
     /// Returns an error if the notifier fails or if the state cannot be toggled.
     fn poll(&mut self) -> Result<!> {
         // Using polling instead of interrupts for the button as on some boards
         // (e.g. M5Stack's Atom Lite) the interrupt pin of the button is too close
-        // to the WiFi antenna which causes interference.
+        // to the WiFi antenna which causes interference. Interrupts are opt-in.
+        if self.interrupt {
+            self.wait_interrupts()?;
+        }
 
         loop {
             if self.pressed() {
-                self.notifier.notify(self.trigger)?;
-                self.toggle()?;
-                sleep(500);
+                self.handle_press()?;
             }
             yield_now();
         }
@@ -103,21 +297,205 @@ where
     MODE: InputMode,
     TR: Trigger,
 {
-    /// Toggles the state of the button.
+    /// Returns the shared state, or `Off` if the button has none.
+    ///
+    /// # Returns
+    /// The current `State`.
+    ///
+    /// # Errors
+    /// Never fails, a poisoned mutex is recovered.
+    fn state(&self) -> Result<State> {
+        Ok(self.presses.state())
+    }
+
+    /// Toggles the shared state, if the button has one.
     ///
     /// # Returns
     /// `Ok(())` on success.
     ///
     /// # Errors
-    /// Returns an error if the mutex lock cannot be acquired.
+    /// Never fails, a poisoned mutex is recovered.
     fn toggle(&mut self) -> Result<()> {
-        let mut state = self
-            .state
-            .lock()
-            .map_err(|e| anyhow!("Mutex lock error: {:?}", e))?;
+        self.presses.toggle();
+        Ok(())
+    }
+}
 
-        state.toggle();
+/// Represents a button read from an ESP32 touch pad, e.g. a pad on the
+/// board or a wire touched by hand, instead of a mechanical switch.
+///
+/// The pad's capacitance is read as a value that drops when touched. The
+/// untouched baseline is calibrated when the button is created, so the pad
+/// must not be touched then, and a press is detected once the value drops
+/// below the baseline by the threshold percentage. Presses are otherwise
+/// reported exactly like with [`Button`], including long presses.
+///
+/// # Type Parameters
+/// * `TR` - The trigger type implementing the `Trigger` trait.
+pub struct TouchButton<TR: Trigger> {
+    presses: Presses<TR>,
+    pad: touch_pad_t,
+    baseline: u16,
+    threshold: u16,
+}
 
+impl<TR: Trigger> TouchButton<TR> {
+    /// Creates a new `TouchButton` instance, calibrating its baseline.
+    ///
+    /// # Arguments
+    /// * `notifier` - A notifier to send button press events.
+    /// * `trigger` - The trigger to emit when the pad is touched.
+    /// * `pin` - A touch-capable GPIO pin: 0, 2, 4, 12, 13, 14, 15, 27, 32 or 33.
+    ///
+    /// # Returns
+    /// A new `TouchButton` instance.
+    ///
+    /// # Errors
+    /// Returns an error if the pin has no touch pad, or if the touch
+    /// peripheral cannot be initialized or read.
+    pub fn new(
+        notifier: Notifier<TR>,
+        trigger: &'static TR,
+        pin: impl InputPin,
+    ) -> Result<Self> {
+        let gpio = pin.pin();
+        let pad = TOUCH_PAD_GPIOS
+            .iter()
+            .position(|&touch_gpio| touch_gpio == gpio)
+            .ok_or_else(|| anyhow!("GPIO {gpio} has no touch pad"))?;
+        let pad = touch_pad_t::try_from(pad)?;
+        esp!(unsafe { touch_pad_init() })?;
+        esp!(unsafe { touch_pad_config(pad, 0) })?;
+
+        let mut total = 0;
+        for _ in 0..TOUCH_CALIBRATION_SAMPLES {
+            total += u32::from(Self::read(pad)?);
+            sleep(TOUCH_CALIBRATION_INTERVAL_MS);
+        }
+        let baseline =
+            u16::try_from(total / TOUCH_CALIBRATION_SAMPLES).unwrap_or(u16::MAX);
+        info!("Touch pad on GPIO {gpio} calibrated, baseline {baseline}");
+
+        Ok(Self {
+            presses: Presses::new(notifier, trigger),
+            pad,
+            baseline,
+            threshold: 0,
+        }
+        .with_threshold(DEFAULT_TOUCH_THRESHOLD_PERCENT))
+    }
+
+    /// Sets how far below the baseline the pad must read to be pressed.
+    ///
+    /// # Arguments
+    /// * `percent` - The drop below the baseline in percent, clamped to
+    ///   `1..=99`; lower values are more sensitive. Defaults to
+    ///   [`DEFAULT_TOUCH_THRESHOLD_PERCENT`].
+    ///
+    /// # Returns
+    /// The `TouchButton` with the new threshold.
+    #[must_use]
+    pub fn with_threshold(mut self, percent: u8) -> Self {
+        let percent = u32::from(percent.clamp(1, 99));
+        self.threshold =
+            u16::try_from(u32::from(self.baseline) * (100 - percent) / 100)
+                .unwrap_or(u16::MAX);
+        self
+    }
+
+    /// Makes this button the power button, toggling the shared state on each press.
+    ///
+    /// # Arguments
+    /// * `state` - State shared with the other components (e.g. the BLE scanner).
+    ///
+    /// # Returns
+    /// The `TouchButton` with the shared state attached.
+    #[must_use]
+    pub fn with_state(mut self, state: Arc<Mutex<State>>) -> Self {
+        self.presses.state = Some(state);
+        self
+    }
+
+    /// Emits a distinct trigger when the pad is held, see
+    /// [`Button::with_long_press`].
+    ///
+    /// # Arguments
+    /// * `trigger` - The trigger to emit on a long press.
+    /// * `hold_ms` - How long the pad must be held, in milliseconds.
+    ///
+    /// # Returns
+    /// The `TouchButton` with long press detection enabled.
+    #[must_use]
+    pub fn with_long_press(mut self, trigger: &'static TR, hold_ms: u64) -> Self {
+        self.presses.long_press = Some((trigger, Duration::from_millis(hold_ms)));
+        self
+    }
+
+    /// Returns the untouched reading of the pad, measured at creation.
+    ///
+    /// # Returns
+    /// The baseline as a raw touch value.
+    #[must_use]
+    pub fn baseline(&self) -> u16 {
+        self.baseline
+    }
+
+    /// Reads the raw value of a touch pad.
+    ///
+    /// # Errors
+    /// Returns an error if the touch peripheral cannot be read.
+    fn read(pad: touch_pad_t) -> Result<u16> {
+        let mut value: u16 = 0;
+        esp!(unsafe { touch_pad_read(pad, &mut value) })?;
+        Ok(value)
+    }
+
+    /// Checks if the pad is touched, a failed read counting as released.
+    ///
+    /// # Returns
+    /// `true` if the pad reads below the threshold, `false` otherwise.
+    fn pressed(pad: touch_pad_t, threshold: u16) -> bool {
+        Self::read(pad).is_ok_and(|value| value < threshold)
+    }
+}
+
+impl<TR: Trigger> Poller for TouchButton<TR> {
+    /// Polls the touch pad for presses.
+    ///
+    /// # Errors
+    /// Returns an error if the notifier fails or if the state cannot be toggled.
+    fn poll(&mut self) -> Result<!> {
+        let (pad, threshold) = (self.pad, self.threshold);
+        loop {
+            if Self::pressed(pad, threshold) {
+                self.presses.handle(|| Self::pressed(pad, threshold))?;
+            }
+            yield_now();
+        }
+    }
+}
+
+impl<TR: Trigger> Switch for TouchButton<TR> {
+    /// Returns the shared state, or `Off` if the button has none.
+    ///
+    /// # Returns
+    /// The current `State`.
+    ///
+    /// # Errors
+    /// Never fails, a poisoned mutex is recovered.
+    fn state(&self) -> Result<State> {
+        Ok(self.presses.state())
+    }
+
+    /// Toggles the shared state, if the button has one.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Never fails, a poisoned mutex is recovered.
+    fn toggle(&mut self) -> Result<()> {
+        self.presses.toggle();
         Ok(())
     }
 }
@@ -1,21 +1,275 @@
 use anyhow::Result;
+#[cfg(feature = "hardware")]
+use anyhow::{anyhow, ensure};
+#[cfg(feature = "hardware")]
 use esp_idf_hal::timer::TimerDriver;
+#[cfg(feature = "hardware")]
+use esp_idf_svc::timer::{EspTaskTimerService, EspTimer};
+#[cfg(feature = "hardware")]
+use log::warn;
+#[cfg(feature = "hardware")]
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
+#[cfg(feature = "hardware")]
 use crate::{
     message::{Notifier, Trigger},
     thread::failure,
+    time::sleep,
 };
 
+/// Number of consecutive tick notifications that may fail in a timer
+/// interrupt or callback before the device restarts.
+#[cfg(feature = "hardware")]
+const MAX_NOTIFY_FAILURES: u32 = 10;
+
+/// Notifies a tick from a timer interrupt or callback.
+///
+/// A failed notification only skips the tick, the device restarts once
+/// [`MAX_NOTIFY_FAILURES`] consecutive notifications failed.
+///
+/// # Arguments
+/// * `notifier` - The notifier to send the trigger with.
+/// * `trigger` - The trigger to emit.
+/// * `failures` - The number of consecutive failed notifications so far.
+///
+/// # Returns
+/// `true` if the trigger was notified, `false` if the tick was dropped.
+#[cfg(feature = "hardware")]
+fn notify_tick<T: Trigger>(
+    notifier: &Notifier<T>,
+    trigger: &'static T,
+    failures: &mut u32,
+) -> bool {
+    let notified = notifier.notify(trigger).is_ok();
+    *failures = if notified { 0 } else { *failures + 1 };
+    if *failures >= MAX_NOTIFY_FAILURES {
+        failure();
+    }
+
+    notified
+}
+
+/// Logs the ticks dropped since the last report, as the interrupt or callback
+/// that dropped them cannot log.
+///
+/// # Arguments
+/// * `dropped` - The number of ticks dropped so far.
+/// * `reported` - The number of dropped ticks already logged, updated.
+#[cfg(feature = "hardware")]
+fn report_dropped(dropped: u32, reported: &mut u32) {
+    if dropped != *reported {
+        warn!(
+            "Skipped {} timer ticks whose notification failed",
+            dropped.wrapping_sub(*reported)
+        );
+        *reported = dropped;
+    }
+}
+
+/// A trait representing a periodic clock driving tick triggers.
+///
+/// Implemented by [`Timer`] for hardware and by `testing::FakeClock` (behind the
+/// `testing` feature) so that clock-dependent logic can run off-device.
+pub trait Clock {
+    /// Turns on the clock.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the clock cannot be turned on.
+    fn on(&mut self) -> Result<()>;
+
+    /// Turns off the clock.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the clock cannot be turned off.
+    fn off(&mut self) -> Result<()>;
+
+    /// Changes the tick frequency of the clock.
+    ///
+    /// # Arguments
+    /// * `freq` - New tick frequency in Hz.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the frequency cannot be applied.
+    fn set_freq(&mut self, freq: u64) -> Result<()>;
+
+    /// Marks every tick fired so far as processed.
+    ///
+    /// # Returns
+    /// The number of ticks fired since the previous call.
+    fn process(&mut self) -> u32;
+
+    /// Returns the number of ticks that coalesced before being processed.
+    ///
+    /// # Returns
+    /// The total count of ticks beyond the first returned by each
+    /// [`Clock::process`] call.
+    fn missed(&self) -> u32;
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn on(&mut self) -> Result<()> {
+        (**self).on()
+    }
+
+    fn off(&mut self) -> Result<()> {
+        (**self).off()
+    }
+
+    fn set_freq(&mut self, freq: u64) -> Result<()> {
+        (**self).set_freq(freq)
+    }
+
+    fn process(&mut self) -> u32 {
+        (**self).process()
+    }
+
+    fn missed(&self) -> u32 {
+        (**self).missed()
+    }
+}
+
+/// A trait representing a clock able to pace a fixed-rate loop.
+///
+/// Time is expressed in ticks of the clock's own resolution. Implemented by
+/// [`Timer`] for hardware and by `testing::FakeClock` (behind the `testing`
+/// feature), whose delays advance virtual time instead of waiting.
+#[allow(async_fn_in_trait)]
+pub trait Pace {
+    /// Returns the number of ticks in one period of the given frequency.
+    ///
+    /// # Arguments
+    /// * `freq` - Frequency in Hz, must be positive.
+    ///
+    /// # Returns
+    /// The period length in ticks.
+    fn period(&self, freq: u64) -> u64;
+
+    /// Returns the current time.
+    ///
+    /// # Returns
+    /// The elapsed time in ticks since the clock was created.
+    fn now_ticks(&self) -> u64;
+
+    /// Delays execution until the given absolute deadline.
+    ///
+    /// # Arguments
+    /// * `deadline` - Absolute deadline in ticks, as returned by
+    ///   [`Pace::now_ticks`].
+    ///
+    /// # Returns
+    /// `Ok(())` when the deadline is reached, immediately if it has passed.
+    ///
+    /// # Errors
+    /// Returns an error if the delay cannot be performed.
+    async fn delay_until(&mut self, deadline: u64) -> Result<()>;
+}
+
+/// Computes the next deadline of a fixed-rate schedule.
+///
+/// The next deadline is `previous + period`, so the schedule does not drift by
+/// the time spent working between delays. If that deadline has already passed
+/// (the work overran one or more periods), the missed deadlines are skipped and
+/// the first deadline after `now` on the same grid is returned, rather than
+/// firing back-to-back to catch up.
+///
+/// # Arguments
+/// * `previous` - The previous deadline, in timer ticks.
+/// * `period` - The schedule period, in timer ticks.
+/// * `now` - The current time, in timer ticks.
+///
+/// # Returns
+/// The next deadline, in timer ticks.
+#[must_use]
+pub fn next_deadline(previous: u64, period: u64, now: u64) -> u64 {
+    let next = previous + period;
+    if next > now || period == 0 {
+        next
+    } else {
+        next + period * ((now - next) / period + 1)
+    }
+}
+
+/// Tick statistics of a [`Timer`] interrupt, suitable for telemetry.
+///
+/// # Fields
+/// * `ticks` - Total number of interrupt ticks fired so far.
+/// * `missed` - Number of ticks that coalesced before being processed.
+/// * `dropped` - Number of ticks whose notification failed.
+#[derive(Clone, Copy, Debug)]
+pub struct TimerStats {
+    ticks: u32,
+    missed: u32,
+    dropped: u32,
+}
+
+impl TimerStats {
+    /// Returns the total number of interrupt ticks fired so far.
+    ///
+    /// # Returns
+    /// The tick count, wrapping on overflow.
+    #[must_use]
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Returns the number of ticks that were never processed individually.
+    ///
+    /// # Returns
+    /// The missed tick count.
+    #[must_use]
+    pub fn missed(&self) -> u32 {
+        self.missed
+    }
+
+    /// Returns the number of ticks that were skipped because their
+    /// notification failed.
+    ///
+    /// # Returns
+    /// The dropped tick count.
+    #[must_use]
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
 /// Represents a timer that can be used for various operations.
 ///
 /// # Type Parameters
 /// * `'a` - Lifetime of the timer.
 /// * `T` - The trigger type implementing the `Trigger` trait.
+#[cfg(feature = "hardware")]
 pub struct Timer<'a, T: Trigger> {
     timer: TimerDriver<'a>,
+    epoch: Instant,
+    configured: bool,
+    enabled: bool,
+    correction: f32,
+    ticks: Arc<AtomicU32>,
+    processed: u32,
+    missed: u32,
+    dropped: Arc<AtomicU32>,
+    reported_dropped: u32,
     _marker: std::marker::PhantomData<T>,
 }
 
+#[cfg(feature = "hardware")]
 impl<'a, T: Trigger> Timer<'a, T> {
     /// Creates a new `Timer` instance.
     ///
@@ -30,6 +284,15 @@ impl<'a, T: Trigger> Timer<'a, T> {
     pub fn new(timer: TimerDriver<'a>) -> Result<Self> {
         Ok(Self {
             timer,
+            epoch: Instant::now(),
+            configured: false,
+            enabled: false,
+            correction: 1.0,
+            ticks: Arc::new(AtomicU32::new(0)),
+            processed: 0,
+            missed: 0,
+            dropped: Arc::new(AtomicU32::new(0)),
+            reported_dropped: 0,
             _marker: std::marker::PhantomData,
         })
     }
@@ -45,25 +308,103 @@ impl<'a, T: Trigger> Timer<'a, T> {
     /// `Ok(())` on success.
     ///
     /// # Errors
-    /// Returns an error if the interrupt cannot be configured.
+    /// Returns an error if `freq` is zero or if the interrupt cannot be
+    /// configured.
     pub fn configure_interrupt(
         &mut self,
         freq: u64,
         notifier: Notifier<T>,
         trigger: &'static T,
     ) -> Result<()> {
+        ensure!(freq > 0, "Timer frequency must be positive");
+        self.subscribe(self.timer.tick_hz() / freq, notifier, trigger)
+    }
+
+    /// Configures the timer interrupt to fire once every `period_ms` milliseconds.
+    ///
+    /// Unlike [`Timer::configure_interrupt`], this supports periods longer than
+    /// one second, e.g. for slow heartbeats.
+    ///
+    /// # Arguments
+    /// * `period_ms` - Period of the timer interrupt in milliseconds.
+    /// * `notifier` - A notifier to send timer tick events.
+    /// * `trigger` - The trigger to emit when the timer ticks.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if `period_ms` is zero or if the interrupt cannot be
+    /// configured.
+    pub fn configure_interval(
+        &mut self,
+        period_ms: u64,
+        notifier: Notifier<T>,
+        trigger: &'static T,
+    ) -> Result<()> {
+        ensure!(period_ms > 0, "Timer period must be positive");
+        self.subscribe(self.timer.tick_hz() * period_ms / 1000, notifier, trigger)
+    }
+
+    /// Subscribes the notifier to the timer interrupt with the given alarm value.
+    ///
+    /// A failed notification only skips the tick, the device restarts once
+    /// [`MAX_NOTIFY_FAILURES`] consecutive notifications failed. Logging is not
+    /// possible from the interrupt, so skipped ticks are reported by
+    /// [`Clock::process`].
+    ///
+    /// # Errors
+    /// Returns an error if the interrupt cannot be configured.
+    fn subscribe(
+        &mut self,
+        alarm: u64,
+        notifier: Notifier<T>,
+        trigger: &'static T,
+    ) -> Result<()> {
+        let ticks = Arc::clone(&self.ticks);
+        let dropped = Arc::clone(&self.dropped);
+        let mut failures = 0;
         unsafe {
             self.timer.subscribe(move || {
-                notifier.notify(trigger).unwrap_or_else(|_| failure());
+                ticks.fetch_add(1, Ordering::Relaxed);
+                if !notify_tick(&notifier, trigger, &mut failures) {
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                }
             })?;
         }
 
-        self.timer.set_alarm(self.timer.tick_hz() / freq)?;
+        self.timer.set_alarm(self.corrected(alarm))?;
         self.timer.enable_interrupt()?;
+        self.configured = true;
 
         Ok(())
     }
 
+    /// Returns the number of interrupt ticks fired since the interrupt was configured.
+    ///
+    /// Notifications are a bitmask, so several ticks may be delivered as a single
+    /// trigger; comparing this counter against processed ticks reveals missed ones.
+    ///
+    /// # Returns
+    /// The tick count, wrapping on overflow.
+    #[must_use]
+    pub fn ticks(&self) -> u32 {
+        self.ticks.load(Ordering::Relaxed)
+    }
+
+    /// Returns the tick statistics of the timer interrupt.
+    ///
+    /// # Returns
+    /// A [`TimerStats`] snapshot.
+    #[must_use]
+    pub fn stats(&self) -> TimerStats {
+        TimerStats {
+            ticks: self.ticks(),
+            missed: self.missed,
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
     /// Enables or disables the timer.
     ///
     /// # Arguments
@@ -74,30 +415,75 @@ impl<'a, T: Trigger> Timer<'a, T> {
     fn enable(&mut self, enable: bool) -> Result<()> {
         self.timer.enable(enable)?;
         self.timer.enable_alarm(enable)?;
+        self.enabled = enable;
 
         Ok(())
     }
 
-    /// Turns on the timer.
+    /// Measures the actual tick rate of the timer against the system clock.
+    ///
+    /// Lets the counter run freely for `duration_ms` of wall-clock time, then
+    /// restores the previous on/off state. Any pending alarm is disabled while
+    /// measuring, so this is meant to be run at startup.
+    ///
+    /// # Arguments
+    /// * `duration_ms` - How long to measure for, in milliseconds.
     ///
     /// # Returns
-    /// `Ok(())` on success.
+    /// The ratio of measured to nominal tick rate, to be passed to
+    /// [`Timer::apply_correction`].
     ///
     /// # Errors
-    /// Returns an error if the timer cannot be turned on.
-    pub fn on(&mut self) -> Result<()> {
-        self.enable(true)
+    /// Returns an error if the counter cannot be controlled or read.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn calibrate(&mut self, duration_ms: u32) -> Result<f32> {
+        let was_on = self.enabled;
+
+        self.timer.enable_alarm(false)?;
+        self.timer.enable(false)?;
+        self.timer.set_counter(0)?;
+        self.timer.enable(true)?;
+        let start = Instant::now();
+
+        sleep(duration_ms);
+
+        let counted = self.timer.counter()?;
+        let elapsed = start.elapsed();
+        self.timer.enable(false)?;
+        self.timer.set_counter(0)?;
+        self.enable(was_on)?;
+
+        let nominal = elapsed.as_secs_f64() * self.timer.tick_hz() as f64;
+        Ok((counted as f64 / nominal) as f32)
     }
 
-    /// Turns off the timer.
+    /// Scales subsequent alarm and delay values by a measured correction ratio.
     ///
-    /// # Returns
-    /// `Ok(())` on success.
+    /// Alarms that are already configured keep their current value.
     ///
-    /// # Errors
-    /// Returns an error if the timer cannot be turned off.
-    pub fn off(&mut self) -> Result<()> {
-        self.enable(false)
+    /// # Arguments
+    /// * `ratio` - Measured to nominal tick rate ratio, as returned by [`Timer::calibrate`].
+    pub fn apply_correction(&mut self, ratio: f32) {
+        self.correction = ratio;
+    }
+
+    /// Scales a nominal tick count by the correction ratio.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn corrected(&self, ticks: u64) -> u64 {
+        (ticks as f64 * f64::from(self.correction)).round() as u64
+    }
+
+    /// Returns whether the timer is currently turned on.
+    ///
+    /// # Returns
+    /// `true` if the timer is on, `false` otherwise.
+    #[must_use]
+    pub fn is_on(&self) -> bool {
+        self.enabled
     }
 
     /// Delays execution for a period determined by the given frequency.
@@ -111,10 +497,643 @@ impl<'a, T: Trigger> Timer<'a, T> {
     /// `Ok(())` when the delay completes.
     ///
     /// # Errors
-    /// Returns an error if the delay cannot be performed.
+    /// Returns an error if `freq` is zero or if the delay cannot be performed.
     pub async fn delay(&mut self, freq: u64) -> Result<()> {
-        self.timer.delay(self.timer.tick_hz() / freq).await?;
+        ensure!(freq > 0, "Timer frequency must be positive");
+        self.timer.delay(self.corrected(self.period(freq))).await?;
+
+        Ok(())
+    }
+
+    /// Returns the number of ticks in one period of the given frequency.
+    ///
+    /// # Arguments
+    /// * `freq` - Frequency in Hz.
+    ///
+    /// # Returns
+    /// The period length in timer ticks.
+    #[must_use]
+    pub fn period(&self, freq: u64) -> u64 {
+        self.timer.tick_hz() / freq
+    }
+
+    /// Returns the current time in timer ticks since the `Timer` was created.
+    ///
+    /// The hardware counter is reset by every delay, so this is derived from the
+    /// monotonic system clock and expressed at the timer's tick rate.
+    ///
+    /// # Returns
+    /// The elapsed time in timer ticks.
+    #[must_use]
+    pub fn now_ticks(&self) -> u64 {
+        let elapsed_us = self.epoch.elapsed().as_micros();
+        u64::try_from(elapsed_us * u128::from(self.timer.tick_hz()) / 1_000_000)
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Delays execution until the given absolute deadline.
+    ///
+    /// Returns immediately if the deadline has already passed. See
+    /// [`next_deadline`] for computing drift-free periodic deadlines.
+    ///
+    /// # Arguments
+    /// * `deadline` - Absolute deadline in ticks, as returned by [`Timer::now_ticks`].
+    ///
+    /// # Returns
+    /// `Ok(())` when the deadline is reached.
+    ///
+    /// # Errors
+    /// Returns an error if the delay cannot be performed.
+    pub async fn delay_until(&mut self, deadline: u64) -> Result<()> {
+        let remaining = deadline.saturating_sub(self.now_ticks());
+        if remaining > 0 {
+            self.timer.delay(self.corrected(remaining)).await?;
+        }
 
         Ok(())
     }
 }
+
+#[cfg(feature = "hardware")]
+impl<T: Trigger> Clock for Timer<'_, T> {
+    /// Turns on the timer. Does nothing if it is already on.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the interrupt has not been configured yet, or if the
+    /// timer cannot be turned on.
+    fn on(&mut self) -> Result<()> {
+        ensure!(
+            self.configured,
+            "Timer turned on before its interrupt was configured"
+        );
+
+        if self.enabled {
+            Ok(())
+        } else {
+            self.enable(true)
+        }
+    }
+
+    /// Turns off the timer. Does nothing if it is already off.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the timer cannot be turned off.
+    fn off(&mut self) -> Result<()> {
+        if self.enabled {
+            self.enable(false)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Changes the alarm period of the timer interrupt.
+    ///
+    /// The counter is reset so the new period applies from now on.
+    ///
+    /// # Arguments
+    /// * `freq` - New frequency of the timer interrupt in Hz.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if `freq` is zero or if the alarm or counter cannot be
+    /// set.
+    fn set_freq(&mut self, freq: u64) -> Result<()> {
+        ensure!(freq > 0, "Timer frequency must be positive");
+        self.timer.set_alarm(self.corrected(self.period(freq)))?;
+        self.timer.set_counter(0)?;
+
+        Ok(())
+    }
+
+    /// Marks every tick fired so far as processed.
+    ///
+    /// # Returns
+    /// The number of ticks fired since the previous call. Any tick beyond the
+    /// first is counted as missed in [`Timer::stats`].
+    fn process(&mut self) -> u32 {
+        let ticks = self.ticks();
+        let pending = ticks.wrapping_sub(self.processed);
+        self.processed = ticks;
+        self.missed = self.missed.wrapping_add(pending.saturating_sub(1));
+
+        report_dropped(
+            self.dropped.load(Ordering::Relaxed),
+            &mut self.reported_dropped,
+        );
+
+        pending
+    }
+
+    fn missed(&self) -> u32 {
+        self.missed
+    }
+}
+
+#[cfg(feature = "hardware")]
+impl<T: Trigger> Pace for Timer<'_, T> {
+    fn period(&self, freq: u64) -> u64 {
+        Timer::period(self, freq)
+    }
+
+    fn now_ticks(&self) -> u64 {
+        Timer::now_ticks(self)
+    }
+
+    async fn delay_until(&mut self, deadline: u64) -> Result<()> {
+        Timer::delay_until(self, deadline).await
+    }
+}
+
+/// Identifies a periodic task registered on a [`Scheduler`].
+#[cfg(feature = "hardware")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TaskId(u32);
+
+/// A software timer driven by the [`Scheduler`] base tick.
+#[cfg(feature = "hardware")]
+struct Task<T: Trigger> {
+    id: TaskId,
+    period: u32,
+    remaining: u32,
+    enabled: bool,
+    fired: u32,
+    failures: u32,
+    dropped: u32,
+    notifier: Notifier<T>,
+    trigger: &'static T,
+}
+
+/// Software timers shared between a [`Scheduler`] and its interrupt handler.
+#[cfg(feature = "hardware")]
+type Tasks<T> = Arc<critical_section::Mutex<RefCell<Vec<Task<T>>>>>;
+
+/// Fans a single hardware timer out to multiple periodic triggers.
+///
+/// The timer ticks at a fixed base rate and every registered task counts down
+/// in base ticks, emitting its trigger when due. Periods are therefore accurate
+/// to within one base tick.
+///
+/// # Type Parameters
+/// * `'a` - Lifetime of the underlying timer driver.
+/// * `T` - The trigger type implementing the `Trigger` trait.
+#[cfg(feature = "hardware")]
+pub struct Scheduler<'a, T: Trigger> {
+    _timer: TimerDriver<'a>,
+    tasks: Tasks<T>,
+    base_hz: u64,
+    next_id: AtomicU32,
+}
+
+#[cfg(feature = "hardware")]
+impl<'a, T: Trigger> Scheduler<'a, T> {
+    /// Creates a new `Scheduler` and starts its base tick.
+    ///
+    /// # Arguments
+    /// * `timer` - The timer driver to tick at the base rate.
+    /// * `base_hz` - Base tick frequency in Hz, bounding the scheduling accuracy.
+    ///
+    /// # Returns
+    /// A new `Scheduler` with no tasks registered.
+    ///
+    /// # Errors
+    /// Returns an error if `base_hz` is zero or if the timer interrupt cannot
+    /// be configured or enabled.
+    pub fn new(mut timer: TimerDriver<'a>, base_hz: u64) -> Result<Self> {
+        ensure!(base_hz > 0, "Scheduler base frequency must be positive");
+        let tasks: Tasks<T> =
+            Arc::new(critical_section::Mutex::new(RefCell::new(Vec::new())));
+
+        let isr_tasks = Arc::clone(&tasks);
+        unsafe {
+            timer.subscribe(move || {
+                critical_section::with(|cs| {
+                    isr_tasks
+                        .borrow_ref_mut(cs)
+                        .iter_mut()
+                        .filter(|task| task.enabled)
+                        .for_each(Task::tick);
+                });
+            })?;
+        }
+
+        timer.set_alarm(timer.tick_hz() / base_hz)?;
+        timer.enable_interrupt()?;
+        timer.enable_alarm(true)?;
+        timer.enable(true)?;
+
+        Ok(Self {
+            _timer: timer,
+            tasks,
+            base_hz,
+            next_id: AtomicU32::new(0),
+        })
+    }
+
+    /// Registers a trigger to be emitted every `period_ms` milliseconds.
+    ///
+    /// # Arguments
+    /// * `period_ms` - Period of the task in milliseconds.
+    /// * `notifier` - A notifier to send the trigger with.
+    /// * `trigger` - The trigger to emit when the task is due.
+    ///
+    /// # Returns
+    /// The [`TaskId`] of the registered task, to be passed to [`Scheduler::cancel`].
+    pub fn every(
+        &self,
+        period_ms: u64,
+        notifier: Notifier<T>,
+        trigger: &'static T,
+    ) -> TaskId {
+        self.register(self.period_ticks(period_ms), notifier, trigger, true)
+    }
+
+    /// Unregisters a task.
+    ///
+    /// # Arguments
+    /// * `id` - The task to cancel.
+    ///
+    /// # Returns
+    /// `true` if the task was registered, `false` otherwise.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        critical_section::with(|cs| {
+            let mut tasks = self.tasks.borrow_ref_mut(cs);
+            let len = tasks.len();
+            tasks.retain(|task| task.id != id);
+            tasks.len() != len
+        })
+    }
+
+    /// Registers a paused task driven through the [`Clock`] interface.
+    ///
+    /// The returned clock keeps the scheduler alive, so the scheduler does not
+    /// need to be stored separately.
+    ///
+    /// # Arguments
+    /// * `scheduler` - The scheduler to register the task on.
+    /// * `freq` - Initial tick frequency of the clock in Hz.
+    /// * `notifier` - A notifier to send the trigger with.
+    /// * `trigger` - The trigger to emit on each clock tick.
+    ///
+    /// # Returns
+    /// A [`ScheduledClock`], initially off.
+    ///
+    /// # Errors
+    /// Returns an error if `freq` is zero.
+    pub fn clock(
+        scheduler: &Rc<Self>,
+        freq: u64,
+        notifier: Notifier<T>,
+        trigger: &'static T,
+    ) -> Result<ScheduledClock<'a, T>> {
+        let id = scheduler.register(
+            scheduler.freq_ticks(freq)?,
+            notifier,
+            trigger,
+            false,
+        );
+
+        Ok(ScheduledClock {
+            scheduler: Rc::clone(scheduler),
+            id,
+            processed: 0,
+            missed: 0,
+            reported_dropped: 0,
+        })
+    }
+
+    fn register(
+        &self,
+        period: u32,
+        notifier: Notifier<T>,
+        trigger: &'static T,
+        enabled: bool,
+    ) -> TaskId {
+        let id = TaskId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        critical_section::with(|cs| {
+            self.tasks.borrow_ref_mut(cs).push(Task {
+                id,
+                period,
+                remaining: period,
+                enabled,
+                fired: 0,
+                failures: 0,
+                dropped: 0,
+                notifier,
+                trigger,
+            });
+        });
+
+        id
+    }
+
+    fn update<R>(&self, id: TaskId, f: impl FnOnce(&mut Task<T>) -> R) -> Option<R> {
+        critical_section::with(|cs| {
+            self.tasks
+                .borrow_ref_mut(cs)
+                .iter_mut()
+                .find(|task| task.id == id)
+                .map(f)
+        })
+    }
+
+    fn period_ticks(&self, period_ms: u64) -> u32 {
+        u32::try_from(self.base_hz * period_ms / 1000)
+            .unwrap_or(u32::MAX)
+            .max(1)
+    }
+
+    fn freq_ticks(&self, freq: u64) -> Result<u32> {
+        ensure!(freq > 0, "Scheduled clock frequency must be positive");
+
+        Ok(u32::try_from(self.base_hz / freq)
+            .unwrap_or(u32::MAX)
+            .max(1))
+    }
+}
+
+#[cfg(feature = "hardware")]
+impl<T: Trigger> Task<T> {
+    /// Counts down one base tick, emitting the trigger when due. A failed
+    /// notification only drops the tick, see [`notify_tick`].
+    fn tick(&mut self) {
+        self.remaining = self.remaining.saturating_sub(1);
+        if self.remaining == 0 {
+            self.remaining = self.period;
+            self.fired = self.fired.wrapping_add(1);
+            if !notify_tick(&self.notifier, self.trigger, &mut self.failures) {
+                self.dropped = self.dropped.wrapping_add(1);
+            }
+        }
+    }
+}
+
+/// A [`Clock`] backed by a task of a shared [`Scheduler`].
+///
+/// # Type Parameters
+/// * `'a` - Lifetime of the scheduler's timer driver.
+/// * `T` - The trigger type implementing the `Trigger` trait.
+#[cfg(feature = "hardware")]
+pub struct ScheduledClock<'a, T: Trigger> {
+    scheduler: Rc<Scheduler<'a, T>>,
+    id: TaskId,
+    processed: u32,
+    missed: u32,
+    reported_dropped: u32,
+}
+
+#[cfg(feature = "hardware")]
+impl<T: Trigger> Clock for ScheduledClock<'_, T> {
+    /// Resumes the scheduled task, restarting its period.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the task is no longer registered.
+    fn on(&mut self) -> Result<()> {
+        self.scheduler
+            .update(self.id, |task| {
+                if !task.enabled {
+                    task.enabled = true;
+                    task.remaining = task.period;
+                }
+            })
+            .ok_or_else(|| anyhow!("Scheduled task {:?} not found", self.id))
+    }
+
+    /// Pauses the scheduled task.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the task is no longer registered.
+    fn off(&mut self) -> Result<()> {
+        self.scheduler
+            .update(self.id, |task| task.enabled = false)
+            .ok_or_else(|| anyhow!("Scheduled task {:?} not found", self.id))
+    }
+
+    /// Changes the period of the scheduled task.
+    ///
+    /// # Arguments
+    /// * `freq` - New tick frequency in Hz, rounded to whole base ticks.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if `freq` is zero or if the task is no longer
+    /// registered.
+    fn set_freq(&mut self, freq: u64) -> Result<()> {
+        let period = self.scheduler.freq_ticks(freq)?;
+        self.scheduler
+            .update(self.id, |task| {
+                task.period = period;
+                task.remaining = period;
+            })
+            .ok_or_else(|| anyhow!("Scheduled task {:?} not found", self.id))
+    }
+
+    /// Marks every tick fired so far as processed, and logs the ticks whose
+    /// notification failed since the previous call.
+    ///
+    /// # Returns
+    /// The number of ticks fired since the previous call.
+    fn process(&mut self) -> u32 {
+        let (fired, dropped) = self
+            .scheduler
+            .update(self.id, |task| (task.fired, task.dropped))
+            .unwrap_or((self.processed, self.reported_dropped));
+        let pending = fired.wrapping_sub(self.processed);
+        self.processed = fired;
+        self.missed = self.missed.wrapping_add(pending.saturating_sub(1));
+        report_dropped(dropped, &mut self.reported_dropped);
+
+        pending
+    }
+
+    fn missed(&self) -> u32 {
+        self.missed
+    }
+}
+
+/// Represents a periodic timer backed by the ESP-IDF high resolution timer.
+///
+/// Offers the same surface as [`Timer`] without consuming a hardware timer group.
+/// Unlike timer groups, which stop in light sleep, the underlying `esp_timer`
+/// keeps time across light sleep and wakes the chip from automatic light sleep
+/// when a period elapses, so periodic triggers keep arriving on schedule.
+///
+/// # Type Parameters
+/// * `T` - The trigger type implementing the `Trigger` trait.
+#[cfg(feature = "hardware")]
+pub struct RtcTimer<T: Trigger> {
+    service: EspTaskTimerService,
+    timer: Option<EspTimer<'static>>,
+    period: Duration,
+    ticks: Arc<AtomicU32>,
+    processed: u32,
+    missed: u32,
+    dropped: Arc<AtomicU32>,
+    reported_dropped: u32,
+    _marker: std::marker::PhantomData<T>,
+}
+
+#[cfg(feature = "hardware")]
+impl<T: Trigger> RtcTimer<T> {
+    /// Creates a new `RtcTimer` instance.
+    ///
+    /// # Returns
+    /// A new `RtcTimer` with no interrupt configured.
+    ///
+    /// # Errors
+    /// Returns an error if the timer service cannot be taken.
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            service: EspTaskTimerService::new()?,
+            timer: None,
+            period: Duration::from_secs(1),
+            ticks: Arc::new(AtomicU32::new(0)),
+            processed: 0,
+            missed: 0,
+            dropped: Arc::new(AtomicU32::new(0)),
+            reported_dropped: 0,
+            _marker: std::marker::PhantomData,
+        })
+    }
+
+    /// Configures the periodic callback of the timer.
+    ///
+    /// A failed notification only skips the tick, the device restarts once
+    /// [`MAX_NOTIFY_FAILURES`] consecutive notifications failed. Skipped ticks
+    /// are reported by [`Clock::process`].
+    ///
+    /// # Arguments
+    /// * `freq` - Frequency of the timer callback in Hz.
+    /// * `notifier` - A notifier to send timer tick events.
+    /// * `trigger` - The trigger to emit when the timer ticks.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if `freq` is zero or if the timer callback cannot be
+    /// registered.
+    pub fn configure_interrupt(
+        &mut self,
+        freq: u64,
+        notifier: Notifier<T>,
+        trigger: &'static T,
+    ) -> Result<()> {
+        ensure!(freq > 0, "RtcTimer frequency must be positive");
+        let ticks = Arc::clone(&self.ticks);
+        let dropped = Arc::clone(&self.dropped);
+        let mut failures = 0;
+        self.timer = Some(self.service.timer(move || {
+            ticks.fetch_add(1, Ordering::Relaxed);
+            if !notify_tick(&notifier, trigger, &mut failures) {
+                dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        })?);
+        self.period = Duration::from_nanos(1_000_000_000 / freq);
+
+        Ok(())
+    }
+
+    /// Returns the configured timer.
+    ///
+    /// # Errors
+    /// Returns an error if [`RtcTimer::configure_interrupt`] was not called.
+    fn timer(&self) -> Result<&EspTimer<'static>> {
+        self.timer
+            .as_ref()
+            .ok_or_else(|| anyhow!("RtcTimer interrupt is not configured"))
+    }
+}
+
+#[cfg(feature = "hardware")]
+impl<T: Trigger> Clock for RtcTimer<T> {
+    /// Starts the periodic timer if it is not already running.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the timer is not configured or cannot be started.
+    fn on(&mut self) -> Result<()> {
+        let timer = self.timer()?;
+        if !timer.is_scheduled()? {
+            timer.every(self.period)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stops the periodic timer.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the timer is not configured or cannot be stopped.
+    fn off(&mut self) -> Result<()> {
+        self.timer()?.cancel()?;
+
+        Ok(())
+    }
+
+    /// Changes the period of the timer, restarting it if it is running.
+    ///
+    /// # Arguments
+    /// * `freq` - New frequency of the timer callback in Hz.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if `freq` is zero, or if the timer is not configured or
+    /// cannot be restarted.
+    fn set_freq(&mut self, freq: u64) -> Result<()> {
+        ensure!(freq > 0, "RtcTimer frequency must be positive");
+        self.period = Duration::from_nanos(1_000_000_000 / freq);
+
+        let timer = self.timer()?;
+        if timer.is_scheduled()? {
+            timer.cancel()?;
+            timer.every(self.period)?;
+        }
+
+        Ok(())
+    }
+
+    /// Marks every tick fired so far as processed, and logs the ticks whose
+    /// notification failed since the previous call.
+    ///
+    /// # Returns
+    /// The number of ticks fired since the previous call.
+    fn process(&mut self) -> u32 {
+        let ticks = self.ticks.load(Ordering::Relaxed);
+        let pending = ticks.wrapping_sub(self.processed);
+        self.processed = ticks;
+        self.missed = self.missed.wrapping_add(pending.saturating_sub(1));
+        report_dropped(
+            self.dropped.load(Ordering::Relaxed),
+            &mut self.reported_dropped,
+        );
+
+        pending
+    }
+
+    fn missed(&self) -> u32 {
+        self.missed
+    }
+}
@@ -1,9 +1,23 @@
+use anyhow::{anyhow, ensure, Result};
+#[cfg(feature = "hardware")]
+use esp_idf_svc::nvs::{EspNvs, NvsPartitionId};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    hash::Hash,
+    ops::{Add, Mul},
+};
+
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
 /// Represents an RGB color value.
 ///
 /// # Fields
 /// * `r` - Red component of the color.
 /// * `g` - Green component of the color.
 /// * `b` - Blue component of the color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Rgb {
     r: u8,
     g: u8,
@@ -21,9 +35,475 @@ impl Rgb {
     /// # Returns
     /// A new `Rgb` instance.
     #[must_use]
-    pub fn new(r: u8, g: u8, b: u8) -> Self {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Creates the color of a black body at the given temperature.
+    ///
+    /// Implements Tanner Helland's approximation of the black body spectrum in
+    /// fixed-point arithmetic, within one unit per channel of the floating-point
+    /// formula. Colors are at full brightness.
+    ///
+    /// # Arguments
+    /// * `kelvin` - The color temperature in kelvin, clamped to `1000..=12000`.
+    ///
+    /// # Returns
+    /// The `Rgb` color of the temperature.
+    #[must_use]
+    pub fn from_kelvin(kelvin: u16) -> Self {
+        let kelvin = u32::from(kelvin.clamp(1000, 12000));
+        let log2_100 = log2_fixed(100);
+        // ln(x / 100), and (x / 100) raised to the power of -exponent
+        let ln = |x: u32| ((log2_fixed(x) - log2_100) * LN_2) >> 16;
+        let pow = |x: u32, exponent: i64| {
+            exp2_fixed(-(((log2_fixed(x) - log2_100) * exponent) >> 16))
+        };
+        let channel =
+            |fixed: i64| to_u8(((fixed + FIXED_ONE / 2) >> 16).clamp(0, 255));
+
+        let r = if kelvin <= 6600 {
+            255
+        } else {
+            channel(
+                (KELVIN_RED_SCALE * pow(kelvin - 6000, KELVIN_RED_EXPONENT)) >> 16,
+            )
+        };
+        let g = if kelvin <= 6600 {
+            channel(
+                ((KELVIN_GREEN_LOW_SCALE * ln(kelvin)) >> 16)
+                    - KELVIN_GREEN_LOW_OFFSET,
+            )
+        } else {
+            channel(
+                (KELVIN_GREEN_HIGH_SCALE
+                    * pow(kelvin - 6000, KELVIN_GREEN_HIGH_EXPONENT))
+                    >> 16,
+            )
+        };
+        let b = if kelvin >= 6600 {
+            255
+        } else if kelvin <= 1900 {
+            0
+        } else {
+            channel(
+                ((KELVIN_BLUE_SCALE * ln(kelvin - 1000)) >> 16) - KELVIN_BLUE_OFFSET,
+            )
+        };
+
+        Self::new(r, g, b)
+    }
+
+    /// Creates an `Rgb` from a logical `0x00RRGGBB` value.
+    ///
+    /// Unlike `From<u32>`, which unpacks the GRB layout sent to the LED, this uses
+    /// the conventional RGB order found in configuration files and web colors.
+    /// The most significant byte is ignored.
+    ///
+    /// # Arguments
+    /// * `value` - The color as `0x00RRGGBB`.
+    ///
+    /// # Returns
+    /// The `Rgb` color encoded by the value.
+    #[must_use]
+    pub fn from_rgb_u32(value: u32) -> Self {
+        let [_, r, g, b] = value.to_be_bytes();
         Self { r, g, b }
     }
+
+    /// Converts the color to a logical `0x00RRGGBB` value.
+    ///
+    /// This is the inverse of [`Rgb::from_rgb_u32`], not the GRB layout of `From<&Rgb> for u32`.
+    ///
+    /// # Returns
+    /// The color as `0x00RRGGBB`.
+    #[must_use]
+    pub fn to_rgb_u32(&self) -> u32 {
+        u32::from_be_bytes([0, self.r, self.g, self.b])
+    }
+
+    /// Packs the color into the 24-bit word sent to an LED.
+    ///
+    /// # Arguments
+    /// * `order` - The order in which the LED chip expects the channels.
+    ///
+    /// # Returns
+    /// The color as a `u32`, first channel in the most significant of the low three bytes.
+    #[must_use]
+    pub fn pack(&self, order: ChannelOrder) -> u32 {
+        let [first, second, third] = match order {
+            ChannelOrder::Rgb => [self.r, self.g, self.b],
+            ChannelOrder::Rbg => [self.r, self.b, self.g],
+            ChannelOrder::Grb => [self.g, self.r, self.b],
+            ChannelOrder::Gbr => [self.g, self.b, self.r],
+            ChannelOrder::Brg => [self.b, self.r, self.g],
+            ChannelOrder::Bgr => [self.b, self.g, self.r],
+        };
+        u32::from_be_bytes([0, first, second, third])
+    }
+
+    /// Parses a color from a hexadecimal string.
+    ///
+    /// Accepts `"#RRGGBB"`, `"RRGGBB"`, and the shorthand `"#RGB"`, where each
+    /// digit is doubled (e.g. `"#F80"` is `"#FF8800"`).
+    ///
+    /// # Arguments
+    /// * `hex` - The string to parse.
+    ///
+    /// # Returns
+    /// The parsed `Rgb` color.
+    ///
+    /// # Errors
+    /// Returns an error if the string has the wrong number of digits or contains
+    /// non-hexadecimal characters.
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        ensure!(
+            matches!(digits.len(), 3 | 6),
+            "Invalid hex color {hex:?}: expected 3 or 6 digits, got {}",
+            digits.len()
+        );
+        ensure!(
+            digits.chars().all(|c| c.is_ascii_hexdigit()),
+            "Invalid hex color {hex:?}: contains non-hexadecimal characters"
+        );
+
+        let value = u32::from_str_radix(digits, 16)?;
+        if digits.len() == 6 {
+            Ok(Self::from_rgb_u32(value))
+        } else {
+            let nibble = |shift: u32| to_u8((value >> shift) & 0xF) * 0x11;
+            Ok(Self::new(nibble(8), nibble(4), nibble(0)))
+        }
+    }
+
+    /// Returns the red component of the color.
+    ///
+    /// # Returns
+    /// The red component as `u8`.
+    #[must_use]
+    pub const fn r(&self) -> u8 {
+        self.r
+    }
+
+    /// Returns the green component of the color.
+    ///
+    /// # Returns
+    /// The green component as `u8`.
+    #[must_use]
+    pub const fn g(&self) -> u8 {
+        self.g
+    }
+
+    /// Returns the blue component of the color.
+    ///
+    /// # Returns
+    /// The blue component as `u8`.
+    #[must_use]
+    pub const fn b(&self) -> u8 {
+        self.b
+    }
+
+    /// Applies gamma correction to each channel.
+    ///
+    /// # Arguments
+    /// * `gamma` - The gamma curve to apply.
+    ///
+    /// # Returns
+    /// The gamma-corrected `Rgb` color.
+    #[must_use]
+    pub fn gamma_corrected(&self, gamma: Gamma) -> Rgb {
+        let table = gamma.table();
+        Rgb::new(
+            table[usize::from(self.r)],
+            table[usize::from(self.g)],
+            table[usize::from(self.b)],
+        )
+    }
+
+    /// Converts the color to its HSV representation.
+    ///
+    /// # Returns
+    /// The equivalent [`Hsv`] color, with hue `0` for grays.
+    #[must_use]
+    pub fn to_hsv(&self) -> Hsv {
+        let (red, green, blue) =
+            (i32::from(self.r), i32::from(self.g), i32::from(self.b));
+        let max = red.max(green).max(blue);
+        let delta = max - red.min(green).min(blue);
+
+        let hue = match delta {
+            0 => 0,
+            _ if max == red => (60 * (green - blue) / delta).rem_euclid(360),
+            _ if max == green => 60 * (blue - red) / delta + 120,
+            _ => 60 * (red - green) / delta + 240,
+        };
+        let saturation = if max == 0 { 0 } else { 255 * delta / max };
+
+        Hsv::new(to_u16(hue), to_u8(saturation), to_u8(max))
+    }
+
+    /// Looks up a predefined color by name, e.g. `"orange"`.
+    ///
+    /// Names are matched case-insensitively against the color constants of
+    /// this module. Strings starting with `#` are parsed with [`Rgb::from_hex`]
+    /// instead, so configuration values can hold either form.
+    ///
+    /// # Arguments
+    /// * `name` - The color name, or a `#`-prefixed hex color.
+    ///
+    /// # Returns
+    /// The color, or `None` if the name is unknown or the hex color invalid.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Rgb> {
+        if name.starts_with('#') {
+            Rgb::from_hex(name).ok()
+        } else {
+            NAMED_COLORS
+                .iter()
+                .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                .map(|&(_, color)| color)
+        }
+    }
+
+    /// Linearly interpolates between this color and another, channel by channel.
+    ///
+    /// # Arguments
+    /// * `other` - The color to interpolate towards.
+    /// * `t` - Interpolation factor, clamped to `0.0..=1.0`; `0.0` yields `self`
+    ///   and `1.0` yields `other`.
+    ///
+    /// # Returns
+    /// The interpolated `Rgb` color.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn lerp(&self, other: &Rgb, t: f32) -> Rgb {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |from: u8, to: u8| {
+            let from = f32::from(from);
+            (from + (f32::from(to) - from) * t).round() as u8
+        };
+
+        Rgb::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+
+    /// Linearly interpolates between this color and another with integer math
+    /// only, channel by channel.
+    ///
+    /// # Arguments
+    /// * `other` - The color to interpolate towards.
+    /// * `t` - Interpolation factor; `0` yields `self` and `255` yields `other`.
+    ///
+    /// # Returns
+    /// The interpolated `Rgb` color, rounded to the nearest channel value.
+    #[must_use]
+    pub fn lerp_u8(&self, other: &Rgb, t: u8) -> Rgb {
+        let t = u16::from(t);
+        let mix = |from: u8, to: u8| {
+            to_u8((u16::from(from) * (255 - t) + u16::from(to) * t + 127) / 255)
+        };
+
+        Rgb::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+
+    /// Composites another color over this one.
+    ///
+    /// # Arguments
+    /// * `other` - The color drawn on top.
+    /// * `alpha` - Opacity of `other`, from `0` (transparent) to `255` (opaque).
+    ///
+    /// # Returns
+    /// The blended `Rgb` color.
+    #[must_use]
+    pub fn blend(&self, other: &Rgb, alpha: u8) -> Rgb {
+        self.lerp_u8(other, alpha)
+    }
+
+    /// Dims a full-brightness color to a perceived brightness level.
+    ///
+    /// # Arguments
+    /// * `level` - Perceived brightness, see [`perceptual`].
+    ///
+    /// # Returns
+    /// The color scaled through the perceptual dimming curve.
+    #[must_use]
+    pub fn at_level(&self, level: u8) -> Rgb {
+        BLACK.lerp_u8(self, perceptual(level))
+    }
+
+    /// Scales the color towards black, preserving its hue.
+    ///
+    /// Each channel is multiplied by the factor and rounded to the nearest
+    /// value, so that repeated dimming does not drift darker than expected.
+    ///
+    /// # Arguments
+    /// * `factor` - Brightness factor, clamped to `0.0..=1.0`; `1.0` keeps the
+    ///   color unchanged and `0.0` gives black.
+    ///
+    /// # Returns
+    /// The dimmed color.
+    #[must_use]
+    pub fn dimmed(&self, factor: f32) -> Rgb {
+        *self * factor.clamp(0.0, 1.0)
+    }
+
+    /// Adds two colors channel by channel, saturating at `255`.
+    ///
+    /// # Arguments
+    /// * `other` - The color to add.
+    ///
+    /// # Returns
+    /// The sum of both colors.
+    #[must_use]
+    pub const fn saturating_add(self, other: Rgb) -> Rgb {
+        Rgb::new(
+            self.r.saturating_add(other.r),
+            self.g.saturating_add(other.g),
+            self.b.saturating_add(other.b),
+        )
+    }
+
+    /// Multiplies each channel by a factor, saturating at `255`.
+    ///
+    /// # Arguments
+    /// * `factor` - The factor to multiply the channels by.
+    ///
+    /// # Returns
+    /// The scaled color.
+    #[must_use]
+    pub const fn saturating_mul(self, factor: u8) -> Rgb {
+        Rgb::new(
+            self.r.saturating_mul(factor),
+            self.g.saturating_mul(factor),
+            self.b.saturating_mul(factor),
+        )
+    }
+}
+
+impl Add for Rgb {
+    type Output = Rgb;
+
+    /// Adds two colors channel by channel, saturating at `255`.
+    fn add(self, other: Rgb) -> Rgb {
+        self.saturating_add(other)
+    }
+}
+
+impl Mul<u8> for Rgb {
+    type Output = Rgb;
+
+    /// Multiplies each channel by a factor, saturating at `255`.
+    fn mul(self, factor: u8) -> Rgb {
+        self.saturating_mul(factor)
+    }
+}
+
+impl Mul<f32> for Rgb {
+    type Output = Rgb;
+
+    /// Scales each channel by a factor, rounding to the nearest value and
+    /// saturating to `0..=255`; e.g. `color * 0.5` halves the brightness.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn mul(self, factor: f32) -> Rgb {
+        let scale = |channel: u8| {
+            (f32::from(channel) * factor).round().clamp(0.0, 255.0) as u8
+        };
+
+        Rgb::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+}
+
+/// Produces an evenly spaced sequence of colors between two endpoints.
+///
+/// # Arguments
+/// * `from` - The first color of the sequence.
+/// * `to` - The last color of the sequence.
+/// * `steps` - The number of colors produced, endpoints included; `0` yields
+///   nothing and `1` yields `from` only.
+///
+/// # Returns
+/// An iterator over the interpolated colors, starting at `from` and ending at `to`.
+pub fn gradient(from: Rgb, to: Rgb, steps: u8) -> impl Iterator<Item = Rgb> {
+    let last = u16::from(steps.saturating_sub(1)).max(1);
+    (0..u16::from(steps))
+        .map(move |step| from.lerp_u8(&to, to_u8(step * 255 / last)))
+}
+
+/// Maps a value to a color by interpolating between gradient stops.
+///
+/// Values between two stops are linearly interpolated, while values below the
+/// first stop or above the last one are clamped to that stop's color, e.g.
+/// `gradient_map(percent, &BATTERY_GRADIENT)` goes from red to green.
+///
+/// # Arguments
+/// * `value` - The value to map.
+/// * `stops` - `(value, color)` pairs, sorted by strictly increasing value.
+///
+/// # Returns
+/// The color of the gradient at `value`.
+///
+/// # Errors
+/// Returns an error if `stops` is empty, unsorted, or contains duplicate values.
+pub fn gradient_map(value: u8, stops: &[(u8, Rgb)]) -> Result<Rgb> {
+    ensure!(!stops.is_empty(), "Gradient has no stops");
+    ensure!(
+        stops.windows(2).all(|w| w[0].0 < w[1].0),
+        "Gradient stops must be sorted by strictly increasing value"
+    );
+
+    let upper = stops.partition_point(|&(at, _)| at < value);
+    Ok(match upper {
+        0 => stops[0].1,
+        i if i == stops.len() => stops[i - 1].1,
+        i => {
+            let ((lo_at, lo), (hi_at, hi)) = (stops[i - 1], stops[i]);
+            let t = u16::from(value - lo_at) * 255 / u16::from(hi_at - lo_at);
+            lo.lerp_u8(&hi, to_u8(t))
+        }
+    })
+}
+
+/// Maps a position around the hue circle to a fully saturated color.
+///
+/// Uses the classic three-segment formula: red fades to green over `0..=85`,
+/// green to blue over `85..=170`, and blue back to red over `170..=255`, so
+/// `255` wraps around to the red of `0` and advancing the position on each
+/// tick produces a seamless rainbow.
+///
+/// # Arguments
+/// * `pos` - Position on the color wheel, `0` (red) to `255` (red again).
+///
+/// # Returns
+/// The `Rgb` color at that position, at full brightness.
+#[must_use]
+pub const fn wheel(pos: u8) -> Rgb {
+    let pos = 255 - pos;
+    match pos {
+        0..85 => Rgb::new(255 - pos * 3, 0, pos * 3),
+        85..170 => Rgb::new(0, (pos - 85) * 3, 255 - (pos - 85) * 3),
+        _ => Rgb::new((pos - 170) * 3, 255 - (pos - 170) * 3, 0),
+    }
+}
+
+/// Maps a position around the hue circle to a color at a given brightness.
+///
+/// # Arguments
+/// * `pos` - Position on the color wheel, see [`wheel`].
+/// * `brightness` - Brightness, `0` (off) to `255` (full).
+///
+/// # Returns
+/// The [`wheel`] color scaled down to `brightness`.
+#[must_use]
+pub fn wheel_dim(pos: u8, brightness: u8) -> Rgb {
+    BLACK.lerp_u8(&wheel(pos), brightness)
 }
 
 impl From<&Rgb> for u32 {
@@ -36,26 +516,684 @@ impl From<&Rgb> for u32 {
     /// # Returns
     /// A `u32` representation of the RGB color.
     fn from(rgb: &Rgb) -> Self {
-        (u32::from(rgb.g) << 16) | (u32::from(rgb.r) << 8) | u32::from(rgb.b)
+        rgb.pack(ChannelOrder::Grb)
+    }
+}
+
+impl From<u32> for Rgb {
+    /// Converts a `u32` color value back to an `Rgb` instance.
+    ///
+    /// This is the inverse of `From<&Rgb> for u32` and expects the same GRB layout.
+    /// Each channel is masked to 8 bits and the most significant byte is ignored.
+    /// e.g. 0x00020104 -> rgb: (1,2,4)
+    ///
+    /// # Returns
+    /// The `Rgb` color encoded by the value.
+    fn from(color: u32) -> Self {
+        let [_, g, r, b] = color.to_be_bytes();
+        Self { r, g, b }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Rgb {
+    /// Serializes the color as a `"#RRGGBB"` string.
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("#{:06X}", self.to_rgb_u32()))
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Rgb {
+    /// Deserializes a color from either a hex string accepted by
+    /// [`Rgb::from_hex`] or an `{r, g, b}` map.
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Hex(String),
+            Channels { r: u8, g: u8, b: u8 },
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Hex(hex) => Rgb::from_hex(&hex).map_err(de::Error::custom),
+            Repr::Channels { r, g, b } => Ok(Rgb::new(r, g, b)),
+        }
+    }
+}
+
+/// Order in which an LED chip expects the color channels on the wire.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChannelOrder {
+    /// Red, green, blue, used by WS2811 chips.
+    Rgb,
+    /// Red, blue, green.
+    Rbg,
+    /// Green, red, blue, used by WS2812 chips.
+    #[default]
+    Grb,
+    /// Green, blue, red.
+    Gbr,
+    /// Blue, red, green, used by some WS2812 clones.
+    Brg,
+    /// Blue, green, red.
+    Bgr,
+}
+
+/// Gamma curves compensating for the non-linear brightness perception of LEDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gamma {
+    /// γ = 1.8
+    Low,
+    /// γ = 2.2, a good default for WS2812 LEDs.
+    Medium,
+    /// γ = 2.8
+    High,
+}
+
+impl Gamma {
+    /// Returns the lookup table of the curve.
+    ///
+    /// # Returns
+    /// A table mapping each linear channel value to its corrected value.
+    fn table(self) -> &'static [u8; 256] {
+        match self {
+            Gamma::Low => &GAMMA_1_8,
+            Gamma::Medium => &GAMMA_2_2,
+            Gamma::High => &GAMMA_2_8,
+        }
+    }
+}
+
+/// Builds a gamma lookup table for γ = `fifths` / 5 at compile time.
+///
+/// Each entry is `255 * (x / 255)^γ` rounded to the nearest integer. Writing
+/// `y` for the entry, this is the `y` for which `y^5 * 255^(fifths - 5)` is
+/// closest to `x^fifths`, which only needs exact integer arithmetic.
+#[allow(clippy::cast_possible_truncation)]
+const fn gamma_table(fifths: u32) -> [u8; 256] {
+    let scale = 255u128.pow(fifths - 5);
+    let mut table = [0u8; 256];
+    let mut x = 0;
+    while x < 256 {
+        let target = (x as u128).pow(fifths);
+        let mut y: u128 = 0;
+        while y < 255 && (2 * y + 1).pow(5) * scale <= 32 * target {
+            y += 1;
+        }
+        table[x] = y as u8;
+        x += 1;
+    }
+    table
+}
+
+/// Gamma lookup table for γ = 1.8.
+static GAMMA_1_8: [u8; 256] = gamma_table(9);
+
+/// Gamma lookup table for γ = 2.2.
+static GAMMA_2_2: [u8; 256] = gamma_table(11);
+
+/// Gamma lookup table for γ = 2.8.
+static GAMMA_2_8: [u8; 256] = gamma_table(14);
+
+/// Builds the perceptual dimming lookup table at compile time.
+///
+/// Each level is taken as a CIE 1976 lightness `L* = 100 * level / 255`, and
+/// mapped to the luminance producing it: `((L* + 16) / 116)^3` above `L* = 8`,
+/// and `L* / 903.3` on the linear segment below, scaled to `0..=255` and
+/// rounded to the nearest integer. Both branches are rewritten over integers,
+/// so `level` 255 maps exactly to 255.
+#[allow(clippy::cast_possible_truncation)]
+const fn perceptual_table() -> [u8; 256] {
+    const DENOMINATOR: u64 = 29_580 * 29_580 * 29_580; // (255 * 116)^3
+    let mut table = [0u8; 256];
+    let mut level = 0;
+    while level < 256 {
+        let l = level as u64;
+        table[level] = if l * 100 > 8 * 255 {
+            let n = 100 * l + 16 * 255;
+            ((2 * 255 * n * n * n + DENOMINATOR) / (2 * DENOMINATOR)) as u8
+        } else {
+            ((2000 * l + 9033) / 18_066) as u8
+        };
+        level += 1;
+    }
+    table
+}
+
+/// Perceptual dimming lookup table, see [`perceptual_table`].
+static PERCEPTUAL: [u8; 256] = perceptual_table();
+
+/// Maps a brightness level to a channel scale that looks evenly spaced.
+///
+/// Unlike [`Gamma`], which corrects channel values of a given color, this
+/// follows the CIE lightness curve so that stepping `level` in equal
+/// increments produces visually equal brightness steps, e.g. `128` maps to
+/// `47`, and levels below `21` stay within the dimmest few values.
+///
+/// # Arguments
+/// * `level` - Perceived brightness, `0` (off) to `255` (full).
+///
+/// # Returns
+/// The channel scale to apply, `0` to `255`.
+#[must_use]
+pub fn perceptual(level: u8) -> u8 {
+    PERCEPTUAL[usize::from(level)]
+}
+
+/// Represents a color in the HSV (hue, saturation, value) space.
+///
+/// # Fields
+/// * `h` - Hue in degrees, `0..360`.
+/// * `s` - Saturation, `0` (gray) to `255` (fully saturated).
+/// * `v` - Value (brightness), `0` (black) to `255`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Hsv {
+    h: u16,
+    s: u8,
+    v: u8,
+}
+
+impl Hsv {
+    /// Creates a new `Hsv` instance.
+    ///
+    /// # Arguments
+    /// * `h` - Hue in degrees, wrapped into `0..360`.
+    /// * `s` - Saturation.
+    /// * `v` - Value (brightness).
+    ///
+    /// # Returns
+    /// A new `Hsv` instance.
+    #[must_use]
+    pub fn new(h: u16, s: u8, v: u8) -> Self {
+        Self { h: h % 360, s, v }
+    }
+
+    /// Returns the hue in degrees.
+    ///
+    /// # Returns
+    /// The hue, in `0..360`.
+    #[must_use]
+    pub fn h(&self) -> u16 {
+        self.h
+    }
+
+    /// Returns the saturation.
+    ///
+    /// # Returns
+    /// The saturation as `u8`.
+    #[must_use]
+    pub fn s(&self) -> u8 {
+        self.s
+    }
+
+    /// Returns the value (brightness).
+    ///
+    /// # Returns
+    /// The value as `u8`.
+    #[must_use]
+    pub fn v(&self) -> u8 {
+        self.v
+    }
+
+    /// Rotates the hue around the color wheel.
+    ///
+    /// # Arguments
+    /// * `degrees` - Rotation in degrees; negative values rotate backwards.
+    ///
+    /// # Returns
+    /// A new `Hsv` with the rotated hue and the same saturation and value.
+    #[must_use]
+    pub fn shift_hue(&self, degrees: i16) -> Hsv {
+        let h = (i32::from(self.h) + i32::from(degrees)).rem_euclid(360);
+        Hsv::new(to_u16(h), self.s, self.v)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Hsv {
+    /// Deserializes a color from an `{h, s, v}` map, wrapping the hue into
+    /// `0..360` like [`Hsv::new`].
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Repr {
+            h: u16,
+            s: u8,
+            v: u8,
+        }
+
+        let Repr { h, s, v } = Repr::deserialize(deserializer)?;
+        Ok(Hsv::new(h, s, v))
+    }
+}
+
+impl From<Hsv> for Rgb {
+    /// Converts an `Hsv` color to `Rgb` using integer math only.
+    ///
+    /// # Returns
+    /// The equivalent `Rgb` color.
+    fn from(hsv: Hsv) -> Self {
+        let (hue, saturation, value) =
+            (u32::from(hsv.h), u32::from(hsv.s), u32::from(hsv.v));
+        let offset = (hue % 60) * 255 / 60;
+        let floor = to_u8(value * (255 - saturation) / 255);
+        let falling = to_u8(value * (255 - saturation * offset / 255) / 255);
+        let rising = to_u8(value * (255 - saturation * (255 - offset) / 255) / 255);
+        let peak = hsv.v;
+
+        let (red, green, blue) = match hue / 60 {
+            0 => (peak, rising, floor),
+            1 => (falling, peak, floor),
+            2 => (floor, peak, rising),
+            3 => (floor, falling, peak),
+            4 => (rising, floor, peak),
+            _ => (peak, floor, falling),
+        };
+
+        Rgb::new(red, green, blue)
+    }
+}
+
+/// Represents an RGBW color value, for pixels with a dedicated white channel
+/// such as the SK6812 RGBW.
+///
+/// # Fields
+/// * `r` - Red component of the color.
+/// * `g` - Green component of the color.
+/// * `b` - Blue component of the color.
+/// * `w` - White component of the color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgbw {
+    r: u8,
+    g: u8,
+    b: u8,
+    w: u8,
+}
+
+impl Rgbw {
+    /// Creates a new `Rgbw` instance.
+    ///
+    /// # Arguments
+    /// * `r` - Red component of the color.
+    /// * `g` - Green component of the color.
+    /// * `b` - Blue component of the color.
+    /// * `w` - White component of the color.
+    ///
+    /// # Returns
+    /// A new `Rgbw` instance.
+    #[must_use]
+    pub const fn new(r: u8, g: u8, b: u8, w: u8) -> Self {
+        Self { r, g, b, w }
+    }
+
+    /// Combines an RGB color with a white level.
+    ///
+    /// # Arguments
+    /// * `rgb` - The color of the RGB channels.
+    /// * `w` - White component of the color.
+    ///
+    /// # Returns
+    /// A new `Rgbw` instance.
+    #[must_use]
+    pub fn from_rgb(rgb: &Rgb, w: u8) -> Self {
+        Self::new(rgb.r, rgb.g, rgb.b, w)
+    }
+
+    /// Converts an RGB color, moving its gray part to the white channel.
+    ///
+    /// The white level is the smallest of the three channels, which is then
+    /// subtracted from each of them, so whites are rendered by the white LED.
+    ///
+    /// # Arguments
+    /// * `rgb` - The color to convert.
+    ///
+    /// # Returns
+    /// The equivalent `Rgbw` color.
+    #[must_use]
+    pub fn extract_white(rgb: &Rgb) -> Self {
+        let w = rgb.r.min(rgb.g).min(rgb.b);
+        Self::new(rgb.r - w, rgb.g - w, rgb.b - w, w)
+    }
+
+    /// Returns the RGB channels of the color.
+    ///
+    /// # Returns
+    /// The `Rgb` color, without the white component.
+    #[must_use]
+    pub fn rgb(&self) -> Rgb {
+        Rgb::new(self.r, self.g, self.b)
+    }
+
+    /// Returns the white component.
+    ///
+    /// # Returns
+    /// The white channel value.
+    #[must_use]
+    pub fn w(&self) -> u8 {
+        self.w
+    }
+
+    /// Applies gamma correction to each channel.
+    ///
+    /// # Arguments
+    /// * `gamma` - The gamma curve to apply.
+    ///
+    /// # Returns
+    /// The gamma-corrected `Rgbw` color.
+    #[must_use]
+    pub fn gamma_corrected(&self, gamma: Gamma) -> Rgbw {
+        Rgbw::from_rgb(
+            &self.rgb().gamma_corrected(gamma),
+            gamma.table()[usize::from(self.w)],
+        )
+    }
+
+    /// Packs the color into the 32-bit word sent to an RGBW LED.
+    ///
+    /// # Arguments
+    /// * `order` - The order in which the LED chip expects the color channels,
+    ///   the white channel always comes last.
+    ///
+    /// # Returns
+    /// The color as a `u32`, first channel in the most significant byte.
+    #[must_use]
+    pub fn pack(&self, order: ChannelOrder) -> u32 {
+        (self.rgb().pack(order) << 8) | u32::from(self.w)
+    }
+}
+
+impl From<&Rgb> for Rgbw {
+    /// Converts an `Rgb` instance to an `Rgbw` instance with the white channel off.
+    ///
+    /// # Returns
+    /// An `Rgbw` with the same RGB channels and a white component of `0`.
+    fn from(rgb: &Rgb) -> Self {
+        Rgbw::from_rgb(rgb, 0)
+    }
+}
+
+/// Maps application states (or any other key) to the colors shown on the LED.
+///
+/// Keys without a color map to a fallback color, so lookups never fail;
+/// [`Palette::validate`] checks upfront that every expected key is mapped.
+///
+/// # Type Parameters
+/// * `K` - The key type, typically the application `State`.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "K: Deserialize<'de> + Eq + Hash"))
+)]
+pub struct Palette<K> {
+    colors: HashMap<K, Rgb>,
+    fallback: Rgb,
+}
+
+impl<K: Eq + Hash> Palette<K> {
+    /// Creates a new, empty `Palette`.
+    ///
+    /// # Arguments
+    /// * `fallback` - The color of keys without a mapping.
+    ///
+    /// # Returns
+    /// A new `Palette` mapping every key to `fallback`.
+    #[must_use]
+    pub fn new(fallback: Rgb) -> Self {
+        Self {
+            colors: HashMap::new(),
+            fallback,
+        }
+    }
+
+    /// Maps a key to a color, replacing any previous mapping.
+    ///
+    /// # Arguments
+    /// * `key` - The key to map.
+    /// * `color` - The color of the key.
+    ///
+    /// # Returns
+    /// The `Palette` with the mapping added.
+    #[must_use]
+    pub fn with(mut self, key: K, color: Rgb) -> Self {
+        self.set(key, color);
+        self
+    }
+
+    /// Maps a key to a color, replacing any previous mapping.
+    ///
+    /// # Arguments
+    /// * `key` - The key to map.
+    /// * `color` - The color of the key.
+    pub fn set(&mut self, key: K, color: Rgb) {
+        self.colors.insert(key, color);
+    }
+
+    /// Maps a key to a color given as a hexadecimal string, e.g. from an
+    /// environment variable.
+    ///
+    /// # Arguments
+    /// * `key` - The key to map.
+    /// * `hex` - The color, in any format accepted by [`Rgb::from_hex`].
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if `hex` is not a valid color.
+    pub fn set_hex(&mut self, key: K, hex: &str) -> Result<()> {
+        self.set(key, Rgb::from_hex(hex)?);
+        Ok(())
+    }
+
+    /// Maps a key to the color of a black body at the given temperature.
+    ///
+    /// # Arguments
+    /// * `key` - The key to map.
+    /// * `kelvin` - The color temperature, as accepted by [`Rgb::from_kelvin`].
+    pub fn set_kelvin(&mut self, key: K, kelvin: u16) {
+        self.set(key, Rgb::from_kelvin(kelvin));
+    }
+
+    /// Maps a key to a color stored in NVS, if any.
+    ///
+    /// Colors are stored as `u32` values in the logical `0x00RRGGBB` order of
+    /// [`Rgb::from_rgb_u32`]. The mapping is left untouched when the entry is missing.
+    ///
+    /// # Arguments
+    /// * `key` - The key to map.
+    /// * `nvs` - The NVS namespace to read from.
+    /// * `name` - The name of the NVS entry.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the NVS entry cannot be read.
+    #[cfg(feature = "hardware")]
+    pub fn load<P: NvsPartitionId>(
+        &mut self,
+        key: K,
+        nvs: &EspNvs<P>,
+        name: &str,
+    ) -> Result<()> {
+        if let Some(value) = nvs.get_u32(name)? {
+            self.set(key, Rgb::from_rgb_u32(value));
+        }
+        Ok(())
+    }
+
+    /// Looks up the color of a key.
+    ///
+    /// # Arguments
+    /// * `key` - The key to look up.
+    ///
+    /// # Returns
+    /// The color mapped to `key`, or the fallback color if it is unmapped.
+    #[must_use]
+    pub fn color(&self, key: &K) -> Rgb {
+        self.colors.get(key).copied().unwrap_or(self.fallback)
+    }
+
+    /// Checks that every given key has a color.
+    ///
+    /// # Arguments
+    /// * `keys` - The keys expected to be mapped, e.g. every application state.
+    ///
+    /// # Returns
+    /// `Ok(())` if all keys are mapped.
+    ///
+    /// # Errors
+    /// Returns an error naming the first unmapped key.
+    pub fn validate<'k>(&self, keys: impl IntoIterator<Item = &'k K>) -> Result<()>
+    where
+        K: Debug + 'k,
+    {
+        keys.into_iter()
+            .find(|key| !self.colors.contains_key(key))
+            .map_or(Ok(()), |key| Err(anyhow!("No color mapped for {key:?}")))
+    }
+}
+
+/// One in the Q16.16 fixed-point format used by [`Rgb::from_kelvin`].
+const FIXED_ONE: i64 = 1 << 16;
+
+/// ln(2), in Q16.16.
+const LN_2: i64 = 45_426;
+
+/// 2^(2^-i) for i in `1..=16`, in Q16.16.
+const EXP2_FRACTIONS: [i64; 16] = [
+    92_682, 77_936, 71_468, 68_438, 66_971, 66_250, 65_892, 65_714, 65_625, 65_580,
+    65_558, 65_547, 65_542, 65_539, 65_537, 65_537,
+];
+
+/// Coefficients of Tanner Helland's color temperature approximation, in Q16.16.
+const KELVIN_RED_SCALE: i64 = 21_607_136; // 329.698727446
+const KELVIN_RED_EXPONENT: i64 = 8730; // 0.1332047592
+const KELVIN_GREEN_LOW_SCALE: i64 = 6_518_919; // 99.4708025861
+const KELVIN_GREEN_LOW_OFFSET: i64 = 10_559_132; // 161.1195681661
+const KELVIN_GREEN_HIGH_SCALE: i64 = 18_882_375; // 288.1221695283
+const KELVIN_GREEN_HIGH_EXPONENT: i64 = 4949; // 0.0755148492
+const KELVIN_BLUE_SCALE: i64 = 9_077_898; // 138.5177312231
+const KELVIN_BLUE_OFFSET: i64 = 19_991_416; // 305.0447927307
+
+/// Computes the base-2 logarithm of a positive integer, in Q16.16.
+fn log2_fixed(x: u32) -> i64 {
+    let int = x.ilog2();
+    let mut mantissa = (u64::from(x) << 16) >> int;
+    let mut frac = 0;
+    for bit in (0..16).rev() {
+        mantissa = (mantissa * mantissa) >> 16;
+        if mantissa >= 2 << 16 {
+            mantissa >>= 1;
+            frac |= 1 << bit;
+        }
+    }
+    (i64::from(int) << 16) | frac
+}
+
+/// Computes two to the power of a Q16.16 exponent, in Q16.16.
+fn exp2_fixed(x: i64) -> i64 {
+    let (int, frac) = (x >> 16, x & 0xFFFF);
+    let mantissa = EXP2_FRACTIONS
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| frac & (1 << (15 - i)) != 0)
+        .fold(FIXED_ONE, |acc, (_, factor)| (acc * factor) >> 16);
+    if int >= 0 {
+        mantissa << int
+    } else {
+        mantissa >> -int
+    }
+}
+
+/// Narrows an integer known to be in `0..=255`, saturating otherwise.
+fn to_u8<T: TryInto<u8>>(value: T) -> u8 {
+    value.try_into().unwrap_or(u8::MAX)
+}
+
+/// Narrows an integer known to be in `0..360`, saturating otherwise.
+fn to_u16<T: TryInto<u16>>(value: T) -> u16 {
+    value.try_into().unwrap_or(u16::MAX)
+}
+
 /// Default brightness level for predefined colors.
 const DEFAULT_BRIGHTNESS: u8 = 25;
 
 /// Predefined black color.
-pub const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+pub const BLACK: Rgb = Rgb::new(0, 0, 0);
 
 /// Predefined green color with default brightness.
-pub const GREEN: Rgb = Rgb {
-    r: 0,
-    g: DEFAULT_BRIGHTNESS,
-    b: 0,
-};
+pub const GREEN: Rgb = Rgb::new(0, DEFAULT_BRIGHTNESS, 0);
 
 /// Predefined red color with default brightness.
-pub const RED: Rgb = Rgb {
-    r: DEFAULT_BRIGHTNESS,
-    g: 0,
-    b: 0,
-};
+pub const RED: Rgb = Rgb::new(DEFAULT_BRIGHTNESS, 0, 0);
+
+/// Predefined blue color with default brightness.
+pub const BLUE: Rgb = Rgb::new(0, 0, DEFAULT_BRIGHTNESS);
+
+/// Predefined yellow color with default brightness.
+pub const YELLOW: Rgb = Rgb::new(DEFAULT_BRIGHTNESS, DEFAULT_BRIGHTNESS, 0);
+
+/// Predefined cyan color with default brightness.
+pub const CYAN: Rgb = Rgb::new(0, DEFAULT_BRIGHTNESS, DEFAULT_BRIGHTNESS);
+
+/// Predefined magenta color with default brightness.
+pub const MAGENTA: Rgb = Rgb::new(DEFAULT_BRIGHTNESS, 0, DEFAULT_BRIGHTNESS);
+
+/// Predefined white color with default brightness.
+pub const WHITE: Rgb =
+    Rgb::new(DEFAULT_BRIGHTNESS, DEFAULT_BRIGHTNESS, DEFAULT_BRIGHTNESS);
+
+/// Predefined orange color with default brightness.
+pub const ORANGE: Rgb = Rgb::new(DEFAULT_BRIGHTNESS, DEFAULT_BRIGHTNESS / 2, 0);
+
+/// Predefined amber color with default brightness.
+pub const AMBER: Rgb = Rgb::new(DEFAULT_BRIGHTNESS, DEFAULT_BRIGHTNESS * 3 / 4, 0);
+
+/// Predefined colors by name, as looked up by [`Rgb::from_name`].
+static NAMED_COLORS: [(&str, Rgb); 10] = [
+    ("black", BLACK),
+    ("red", RED),
+    ("green", GREEN),
+    ("blue", BLUE),
+    ("yellow", YELLOW),
+    ("cyan", CYAN),
+    ("magenta", MAGENTA),
+    ("white", WHITE),
+    ("orange", ORANGE),
+    ("amber", AMBER),
+];
+
+/// Battery percentage gradient with default brightness: red when empty,
+/// yellow at half charge, and green when full. See [`gradient_map`].
+pub const BATTERY_GRADIENT: [(u8, Rgb); 3] = [(0, RED), (50, YELLOW), (100, GREEN)];
+
+/// Incandescent light (2700 K) at full brightness, as computed by [`Rgb::from_kelvin`].
+pub const WARM_WHITE_2700K: Rgb = Rgb::new(255, 167, 87);
+
+/// Daylight (6500 K) at full brightness, as computed by [`Rgb::from_kelvin`].
+pub const DAYLIGHT_6500K: Rgb = Rgb::new(255, 254, 250);
+
+/// Predefined warm white, tinting the white LED towards red.
+pub const WARM_WHITE: Rgbw = Rgbw::new(
+    DEFAULT_BRIGHTNESS / 2,
+    DEFAULT_BRIGHTNESS / 5,
+    0,
+    DEFAULT_BRIGHTNESS,
+);
+
+/// Predefined cool white, tinting the white LED towards blue.
+pub const COOL_WHITE: Rgbw = Rgbw::new(
+    0,
+    DEFAULT_BRIGHTNESS / 5,
+    DEFAULT_BRIGHTNESS / 2,
+    DEFAULT_BRIGHTNESS,
+);
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use embedded_svc::{http::Method, io::Write};
+use esp_idf_svc::http::server::EspHttpServer;
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    infra,
+    time::{now_ms, now_unix},
+};
+
+/// Path of the HTTP endpoint registered by [`EventLog::serve`].
+pub const EVENTS_PATH: &str = "/diag/events";
+
+/// Kind of a diagnostic event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    /// The application state changed.
+    Transition,
+    /// Triggers were collected by the dispatcher.
+    Trigger,
+    /// An error occurred.
+    Error,
+}
+
+impl EventKind {
+    /// Returns the name of the kind as it appears in JSON.
+    ///
+    /// # Returns
+    /// `"transition"`, `"trigger"`, or `"error"`.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Transition => "transition",
+            EventKind::Trigger => "trigger",
+            EventKind::Error => "error",
+        }
+    }
+}
+
+/// A diagnostic event.
+///
+/// # Fields
+/// * `timestamp_ms` - Milliseconds since boot when the event was recorded.
+/// * `unix_s` - Seconds since the Unix epoch when the event was recorded, if the
+///   system clock was synchronized.
+/// * `kind` - The kind of event.
+/// * `message` - A human-readable description of the event.
+#[derive(Clone, Debug)]
+pub struct Event {
+    timestamp_ms: u64,
+    unix_s: Option<u64>,
+    kind: EventKind,
+    message: String,
+}
+
+impl Event {
+    /// Returns when the event was recorded.
+    ///
+    /// # Returns
+    /// The number of milliseconds since boot.
+    #[must_use]
+    pub fn timestamp_ms(&self) -> u64 {
+        self.timestamp_ms
+    }
+
+    /// Returns the real-world time the event was recorded at, if known.
+    ///
+    /// # Returns
+    /// The number of seconds since the Unix epoch, or `None` if the system
+    /// clock was not synchronized yet.
+    #[must_use]
+    pub fn unix_s(&self) -> Option<u64> {
+        self.unix_s
+    }
+
+    /// Returns the kind of event.
+    ///
+    /// # Returns
+    /// The [`EventKind`] of the event.
+    #[must_use]
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// Returns the description of the event.
+    ///
+    /// # Returns
+    /// The message as a string slice.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Appends `value` to `out` as a JSON string literal.
+fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A fixed-capacity, in-memory log of the most recent diagnostic events.
+///
+/// Once full, recording an event overwrites the oldest one. The log is cheap
+/// to clone: clones share the same events, so one can be handed to the HTTP
+/// server while the application keeps recording into another. Events live in
+/// RAM only and are lost when the device restarts.
+#[derive(Clone)]
+pub struct EventLog {
+    events: Arc<Mutex<VecDeque<Event>>>,
+    capacity: usize,
+}
+
+impl EventLog {
+    /// Creates a new, empty `EventLog`.
+    ///
+    /// # Arguments
+    /// * `capacity` - Maximum number of events kept.
+    ///
+    /// # Returns
+    /// A new `EventLog` with room for `capacity` events.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Records an event, timestamped with the current time.
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of event.
+    /// * `message` - A description of the event.
+    pub fn record(&self, kind: EventKind, message: impl Into<String>) {
+        let mut events = infra::lock(&self.events);
+
+        if events.len() >= self.capacity {
+            events.pop_front();
+        }
+        if self.capacity > 0 {
+            events.push_back(Event {
+                timestamp_ms: now_ms(),
+                unix_s: now_unix(),
+                kind,
+                message: message.into(),
+            });
+        }
+    }
+
+    /// Returns a snapshot of the recorded events.
+    ///
+    /// # Returns
+    /// The events, oldest first.
+    #[must_use]
+    pub fn events(&self) -> Vec<Event> {
+        infra::lock(&self.events).iter().cloned().collect()
+    }
+
+    /// Serializes the recorded events to JSON.
+    ///
+    /// The document has the shape
+    /// `{"capacity":64,"events":[{"t_ms":1234,"unix":1700000000,"kind":"transition","message":"..."}]}`,
+    /// with events oldest first, `unix` `null` until the system clock is
+    /// synchronized, and `kind` one of `transition`, `trigger`, or `error`.
+    ///
+    /// # Returns
+    /// The JSON document as a `String`.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"capacity\":{},\"events\":[", self.capacity);
+        for (i, event) in self.events().iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                "{{\"t_ms\":{},\"unix\":{},\"kind\":\"{}\",\"message\":",
+                event.timestamp_ms,
+                event
+                    .unix_s
+                    .map_or_else(|| "null".to_string(), |unix| unix.to_string()),
+                event.kind.as_str()
+            );
+            write_json_string(&mut json, &event.message);
+            json.push('}');
+        }
+        json.push_str("]}");
+
+        json
+    }
+
+    /// Exposes the log on an HTTP server at [`EVENTS_PATH`].
+    ///
+    /// `GET` requests are answered with the JSON document described in
+    /// [`EventLog::to_json`].
+    ///
+    /// # Arguments
+    /// * `server` - The HTTP server to register the endpoint on.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the handler cannot be registered.
+    pub fn serve(&self, server: &mut EspHttpServer<'_>) -> Result<()> {
+        let log = self.clone();
+        server.fn_handler(
+            EVENTS_PATH,
+            Method::Get,
+            move |request| -> Result<()> {
+                let body = log.to_json();
+                request
+                    .into_response(
+                        200,
+                        None,
+                        &[("content-type", "application/json")],
+                    )?
+                    .write_all(body.as_bytes())?;
+                Ok(())
+            },
+        )?;
+
+        Ok(())
+    }
+}
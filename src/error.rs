@@ -0,0 +1,38 @@
+#[cfg(feature = "hardware")]
+use esp_idf_svc::{io::EspIOError, sys::EspError};
+
+/// Errors callers may need to tell apart, e.g. to retry a request only when
+/// Wi-Fi is down.
+///
+/// Functions returning `anyhow::Result` wrap these errors, so they can be
+/// recovered with `error.downcast_ref::<Error>()`. Conversely, any `anyhow`
+/// error converts into [`Error::Other`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Wi-Fi is not connected.
+    #[error("WIFI is off")]
+    WifiDown,
+    /// An HTTP request completed with a non-success status.
+    #[error("Request failed with status: {0}")]
+    HttpStatus(u16),
+    /// The BLE stack failed.
+    #[error("BLE failure: {0}")]
+    BleFailure(String),
+    /// GPS data could not be parsed, e.g. a corrupt UBX frame.
+    #[error("GPS parse error: {0}")]
+    GpsParse(String),
+    /// An ESP-IDF call failed.
+    #[cfg(feature = "hardware")]
+    #[error(transparent)]
+    Esp(#[from] EspError),
+    /// An ESP-IDF I/O operation failed.
+    #[cfg(feature = "hardware")]
+    #[error(transparent)]
+    Io(#[from] EspIOError),
+    /// Any other error.
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// A `Result` whose error is [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -1,90 +1,2327 @@
-use anyhow::{anyhow, Result};
-use esp_idf_hal::uart::UartRxDriver;
-use nmea::{Nmea, SentenceType};
+use anyhow::{bail, ensure, Result};
+use chrono::NaiveTime;
+#[cfg(feature = "hardware")]
+use embedded_svc::io::Write;
+#[cfg(feature = "hardware")]
+use esp_idf_hal::{
+    delay::TickType,
+    gpio::{AnyOutputPin, Output, PinDriver},
+    uart::{self, UartRxDriver, UartTxDriver},
+    units::Hertz,
+};
+use log::{debug, warn};
+#[cfg(feature = "hardware")]
+use log::{info, log_enabled, Level};
+use nmea::{sentences::FixType, Nmea, SentenceType};
+#[cfg(feature = "hardware")]
+use std::time::Duration;
 use std::{
+    cmp::Reverse,
     fmt::Display,
     sync::{Arc, Mutex},
 };
 
+use crate::{
+    infra,
+    protocol::crc8,
+    time::{now_ms, sleep},
+};
+#[cfg(feature = "hardware")]
 use crate::{
     infra::{Poller, State},
     message::{Notifier, Trigger},
-    time::yield_now,
 };
 
-const READ_TIMEOUT: u32 = 1000;
+/// UBX protocol support: frame parsing, NAV-PVT decoding and configuration
+/// messages for u-blox modules.
+pub mod ubx;
+
+/// Default time a UART read waits for data, in milliseconds.
+pub const DEFAULT_READ_TIMEOUT_MS: u32 = 1000;
+
+/// Time the sensor sleeps between checks of the shared state while off, in
+/// milliseconds.
+#[cfg(feature = "hardware")]
+const IDLE_POLL_MS: u32 = 200;
+
+/// Time the module is given to boot once powered up through its enable pin,
+/// before it is configured again, in milliseconds.
+#[cfg(feature = "hardware")]
+const POWER_UP_DELAY_MS: u32 = 500;
+
+/// Default number of consecutive failed reads after which the sensor reports
+/// an error and backs off, see [`Sensor::with_error_triggers`].
+pub const DEFAULT_READ_FAILURE_THRESHOLD: u32 = 5;
+
+/// First delay between reads once they keep failing, in milliseconds.
+#[cfg(feature = "hardware")]
+const READ_BACKOFF_INITIAL_MS: u32 = 1000;
+
+/// Longest delay between reads once they keep failing, in milliseconds.
+#[cfg(feature = "hardware")]
+const READ_BACKOFF_MAX_MS: u32 = 30_000;
+
+/// Interval between two logs of the poll loop rate, in milliseconds.
+#[cfg(feature = "hardware")]
+const LOOP_RATE_LOG_INTERVAL_MS: u64 = 60_000;
+
+/// Baud rates commonly supported by GPS modules.
+const SUPPORTED_BAUD_RATES: [u32; 9] = [
+    4800, 9600, 19_200, 38_400, 57_600, 115_200, 230_400, 460_800, 921_600,
+];
+
+/// Default baud rate of the GPS UART.
+const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+/// Highest fix rate supported by common GPS modules, in Hz.
+const MAX_UPDATE_HZ: f32 = 10.0;
+
+/// Lowest fix rate that can be set, in Hz, i.e. one fix every 10 seconds.
+const MIN_UPDATE_HZ: f32 = 0.1;
+
+/// How long to wait for a `PMTK001` acknowledgment, in milliseconds.
+#[cfg(feature = "hardware")]
+const PMTK_ACK_TIMEOUT_MS: u64 = 1000;
+
+/// `PMTK314` command enabling only GGA and RMC sentences, once per fix.
+#[cfg(feature = "hardware")]
+const PMTK_GGA_RMC_ONLY: &str = "PMTK314,0,1,0,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0";
+
+/// `PMTK314` command enabling GGA and RMC sentences once per fix, and GSV
+/// sentences once every 5 fixes for the satellite logs.
+#[cfg(feature = "hardware")]
+const PMTK_GGA_RMC_GSV: &str = "PMTK314,0,1,0,1,0,5,0,0,0,0,0,0,0,0,0,0,0,0,0";
+
+/// u-blox `PUBX,40` commands enabling only GGA and RMC sentences on the UART,
+/// once per fix, as understood by e.g. the NEO-6M and NEO-8M.
+#[cfg(feature = "hardware")]
+const PUBX_GGA_RMC_ONLY: [&str; 6] = [
+    "PUBX,40,GGA,0,1,0,0,0,0",
+    "PUBX,40,RMC,0,1,0,0,0,0",
+    "PUBX,40,GSA,0,0,0,0,0,0",
+    "PUBX,40,GSV,0,0,0,0,0,0",
+    "PUBX,40,GLL,0,0,0,0,0,0",
+    "PUBX,40,VTG,0,0,0,0,0,0",
+];
+
+/// u-blox `PUBX,40` command enabling GSV sentences on the UART once every 5
+/// fixes, for the satellite logs.
+#[cfg(feature = "hardware")]
+const PUBX_GSV_EVERY_5_FIXES: &str = "PUBX,40,GSV,0,5,0,0,0,0";
+
+/// Default GPIO the GPS module transmits to.
+const DEFAULT_RX_PIN: i32 = 22;
+
+/// Meters per second in one knot.
+const MPS_PER_KNOT: f32 = 0.514_444;
+
+/// Kilometers per hour in one knot.
+const KMH_PER_KNOT: f32 = 1.852;
+
+/// Miles per hour in one knot.
+const MPH_PER_KNOT: f32 = 1.150_779;
+
+/// Mean radius of the Earth, in meters.
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// Base 32 alphabet used by geohashes.
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Longest geohash produced, 12 characters being already finer than GPS accuracy.
+const GEOHASH_MAX_PRECISION: usize = 12;
+
+/// Version of the binary encoding produced by [`Reading::to_bytes`].
+const ENCODING_VERSION: u8 = 1;
+
+/// Size of the binary encoding produced by [`Reading::to_bytes`].
+pub const ENCODED_LEN: usize = 18;
+
+/// Degrees per unit of the encoded latitude and longitude.
+const ENCODED_DEG_SCALE: f64 = 1e7;
+
+/// Default time without a reading after which the fix is considered lost.
+pub const DEFAULT_FIX_TIMEOUT_MS: u64 = 10_000;
+
+/// Default speed below which a tracked reading is considered stationary.
+pub const DEFAULT_MIN_MOVING_MPS: f32 = 0.5;
+
+/// Default distance below which a tracked step is considered jitter.
+pub const DEFAULT_MIN_STEP_M: f64 = 2.0;
+
+/// Default altitude change below which a tracked climb or descent is
+/// considered noise.
+pub const DEFAULT_MIN_CLIMB_M: f64 = 1.5;
+
+/// Number of recent altitudes whose median is tracked, so that single-sample
+/// spikes are discarded.
+const ALTITUDE_WINDOW: usize = 3;
+
+/// Number of recent bearings averaged into the heading of a [`SpeedTracker`].
+const HEADING_WINDOW: usize = 3;
+
+/// Speed above which a tracked step is considered a GPS glitch, in meters per
+/// second.
+const MAX_PLAUSIBLE_SPEED_MPS: f64 = 100.0;
+
+/// Default HDOP above which readings are dropped, see [`Decoder::max_hdop`].
+pub const DEFAULT_MAX_HDOP: f32 = 5.0;
+
+/// Default speed above which a receiver starts moving, in km/h.
+pub const DEFAULT_MOTION_START_KMH: f32 = 2.0;
+
+/// Default number of consecutive readings above the start speed needed to
+/// start moving.
+pub const DEFAULT_MOTION_START_READINGS: u32 = 3;
+
+/// Default speed below which a receiver stops moving, in km/h.
+pub const DEFAULT_MOTION_STOP_KMH: f32 = 1.0;
+
+/// Default number of consecutive readings below the stop speed needed to stop
+/// moving.
+pub const DEFAULT_MOTION_STOP_READINGS: u32 = 10;
+
+/// Number of lines over which the failure ratio is checked.
+const STATS_WINDOW_LINES: u32 = 50;
+
+/// Failure ratio above which the serial link is reported as unhealthy.
+const STATS_MAX_FAILURE_RATIO: f64 = 0.2;
+
+/// Maximum number of satellites kept in [`Stats::satellites`].
+pub const MAX_SATELLITES_IN_VIEW: usize = 16;
+
+/// Interval between logs of the satellites in view, in milliseconds.
+const SATELLITE_LOG_INTERVAL_MS: u64 = 300_000;
+
+/// Computes the NMEA checksum of a sentence body, the XOR of all its bytes.
+fn nmea_checksum(body: &str) -> u8 {
+    body.bytes().fold(0, |checksum, byte| checksum ^ byte)
+}
+
+/// Returns the flag of a `PMTK001` acknowledgment of the given command, from 0
+/// (invalid command) to 3 (success), or `None` for any other line.
+#[cfg(feature = "hardware")]
+fn pmtk_ack_flag(line: &str, command: u16) -> Option<u8> {
+    let body = line.strip_prefix("$PMTK001,")?.split('*').next()?;
+    let (acked, flag) = body.split_once(',')?;
+
+    (checksum_valid(line) && acked.parse::<u16>().ok()? == command)
+        .then(|| flag.parse().ok())
+        .flatten()
+}
+
+/// Converts a fix rate into the period between fixes, in milliseconds.
+#[cfg(feature = "hardware")]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn update_period_ms(update_hz: f32) -> u16 {
+    (1000.0 / update_hz.clamp(MIN_UPDATE_HZ, MAX_UPDATE_HZ)).round() as u16
+}
+
+/// Checks that a sentence such as `$GPGGA,...*47` carries a valid checksum.
+fn checksum_valid(line: &str) -> bool {
+    line.strip_prefix('$')
+        .and_then(|sentence| sentence.rsplit_once('*'))
+        .and_then(|(body, checksum)| {
+            u8::from_str_radix(checksum.trim(), 16)
+                .ok()
+                .map(|checksum| checksum == nmea_checksum(body))
+        })
+        .unwrap_or(false)
+}
+
+/// Computes the great-circle distance between two readings with the
+/// haversine formula.
+///
+/// # Arguments
+/// * `a` - The first reading.
+/// * `b` - The second reading.
+///
+/// # Returns
+/// The distance between both positions in meters, ignoring altitude.
+#[must_use]
+pub fn haversine(a: &Reading, b: &Reading) -> f64 {
+    let (lat_a, lat_b) = (a.latitude().to_radians(), b.latitude().to_radians());
+    let half_dlat = (lat_b - lat_a) / 2.0;
+    let half_dlon = (b.longitude() - a.longitude()).to_radians() / 2.0;
+
+    let h = half_dlat.sin().powi(2)
+        + lat_a.cos() * lat_b.cos() * half_dlon.sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()
+}
+
+/// Computes the initial great-circle bearing from one reading to another.
+///
+/// # Arguments
+/// * `a` - The starting reading.
+/// * `b` - The destination reading.
+///
+/// # Returns
+/// The bearing in degrees clockwise from true north, in `[0, 360)`, and 0.0
+/// when both positions are the same.
+#[must_use]
+pub fn bearing(a: &Reading, b: &Reading) -> f64 {
+    let (lat_a, lat_b) = (a.latitude().to_radians(), b.latitude().to_radians());
+    let dlon = (b.longitude() - a.longitude()).to_radians();
+
+    let y = dlon.sin() * lat_b.cos();
+    let x = lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * dlon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Returns the UTC time of the last fix in seconds since the Unix epoch.
+///
+/// The time comes from either GGA or RMC sentences, but the date only from
+/// RMC ones, so this is `None` until an RMC sentence has been parsed. Callers
+/// must check that it is from the same fix, the date of the previous one
+/// being a day off around midnight.
+fn fix_utc_s(parser: &Nmea) -> Option<u64> {
+    let (date, time) = (parser.fix_date?, parser.fix_time?);
+    u64::try_from(date.and_time(time).and_utc().timestamp()).ok()
+}
+
+/// Returns a formatted JSON number, or `null` when unknown.
+fn json_number(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "null".to_string())
+}
+
+/// Returns whether a sentence updates the position, whatever its talker ID
+/// (`GP`, `GN`, `GL`, ...).
+fn is_position_sentence(kind: SentenceType) -> bool {
+    matches!(
+        kind,
+        SentenceType::GGA
+            | SentenceType::RMC
+            | SentenceType::GNS
+            | SentenceType::GLL
+    )
+}
+
+/// Returns whether a parse error only means the sentence is of no use to the
+/// parser, rather than malformed: its type or talker ID is not handled.
+fn is_ignored_sentence(error: &nmea::Error<'_>) -> bool {
+    matches!(
+        error,
+        nmea::Error::Unsupported(_)
+            | nmea::Error::Unknown(_)
+            | nmea::Error::UnknownGnssType(_)
+    )
+}
+
+/// Builds a reading from the state accumulated by the parser.
+///
+/// Positions without a valid fix are the last known ones, so they are only
+/// trusted when no GGA sentence said otherwise. The UTC time is only set when
+/// `dated`, i.e. when the parser's date comes from an RMC sentence of the
+/// same fix.
+fn fix_reading(parser: &Nmea, dated: bool) -> Option<Reading> {
+    match (
+        parser.latitude(),
+        parser.longitude(),
+        parser.fix_type.is_none_or(|fix| fix.is_valid()),
+    ) {
+        (Some(lat), Some(lon), true) => Some(
+            Reading::new(lat, lon)
+                .with_motion(parser.speed_over_ground, parser.true_course)
+                .with_quality(
+                    parser.fix_type,
+                    parser.num_of_fix_satellites,
+                    parser.hdop,
+                )
+                .with_altitude(parser.altitude)
+                .with_utc(fix_utc_s(parser).filter(|_| dated)),
+        ),
+        _ => None,
+    }
+}
+
+/// GPS UART configuration.
+///
+/// # Fields
+/// * `baud_rate` - The UART baud rate, which must match the GPS module.
+/// * `rx_pin` - The GPIO number receiving data from the GPS module.
+/// * `tx_pin` - The GPIO number sending commands to the GPS module, if wired.
+/// * `smoothing` - The weight of a new position in the smoothed one, 1.0
+///   disabling smoothing.
+/// * `update_hz` - The fix rate to set on the GPS module, if any.
+/// * `read_timeout_ms` - How long a UART read waits for data, in milliseconds.
+/// * `enable_pin` - The GPIO number powering the GPS module, if wired.
+/// * `protocol` - The protocol the GPS module reports fixes with.
+pub struct Config {
+    baud_rate: u32,
+    rx_pin: i32,
+    tx_pin: Option<i32>,
+    smoothing: f64,
+    update_hz: Option<f32>,
+    read_timeout_ms: u32,
+    enable_pin: Option<i32>,
+    protocol: Protocol,
+}
+
+impl Config {
+    /// Creates a new GPS `Config`.
+    ///
+    /// # Arguments
+    /// * `baud_rate` - The UART baud rate, which must match the GPS module.
+    /// * `rx_pin` - The GPIO number receiving data from the GPS module.
+    /// * `tx_pin` - The GPIO number sending commands to the GPS module, or `None`
+    ///   for receive-only wiring.
+    ///
+    /// # Returns
+    /// A new `Config` instance.
+    ///
+    /// # Errors
+    /// Returns an error if the baud rate is not a supported one.
+    pub fn new(baud_rate: u32, rx_pin: i32, tx_pin: Option<i32>) -> Result<Self> {
+        ensure!(
+            SUPPORTED_BAUD_RATES.contains(&baud_rate),
+            "Unsupported GPS baud rate {baud_rate}, expected one of {SUPPORTED_BAUD_RATES:?}"
+        );
+
+        Ok(Self {
+            baud_rate,
+            rx_pin,
+            tx_pin,
+            smoothing: 1.0,
+            update_hz: None,
+            read_timeout_ms: DEFAULT_READ_TIMEOUT_MS,
+            enable_pin: None,
+            protocol: Protocol::default(),
+        })
+    }
+
+    /// Sets the protocol the GPS module reports fixes with.
+    ///
+    /// # Arguments
+    /// * `protocol` - The protocol, [`Protocol::Nmea`] by default.
+    ///
+    /// # Returns
+    /// The updated `Config`.
+    #[must_use]
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Sets the pin driving the enable input of the GPS module, see
+    /// [`Sensor::with_enable_pin`].
+    ///
+    /// # Arguments
+    /// * `enable_pin` - The GPIO number, or `None` when the module is always
+    ///   powered.
+    ///
+    /// # Returns
+    /// The updated `Config`.
+    #[must_use]
+    pub fn with_enable_pin(mut self, enable_pin: Option<i32>) -> Self {
+        self.enable_pin = enable_pin;
+        self
+    }
+
+    /// Sets how long a UART read waits for data.
+    ///
+    /// The read timeout paces the sensor loop while the UART is idle, so
+    /// shorter timeouts notice a lost fix or a state change sooner at the
+    /// cost of more wake-ups.
+    ///
+    /// # Arguments
+    /// * `read_timeout_ms` - The timeout in milliseconds, at least 1.
+    ///
+    /// # Returns
+    /// The updated `Config`.
+    ///
+    /// # Errors
+    /// Returns an error if the timeout is zero.
+    pub fn with_read_timeout(mut self, read_timeout_ms: u32) -> Result<Self> {
+        ensure!(read_timeout_ms > 0, "GPS read timeout must be positive");
+        self.read_timeout_ms = read_timeout_ms;
+
+        Ok(self)
+    }
+
+    /// Sets the fix rate applied when the sensor starts polling, see
+    /// [`Sensor::set_update_rate`].
+    ///
+    /// # Arguments
+    /// * `update_hz` - The number of fixes per second, from 0.1 to 10, e.g.
+    ///   5 while riding or 0.2 for idle tracking.
+    ///
+    /// # Returns
+    /// The updated `Config`.
+    ///
+    /// # Errors
+    /// Returns an error if the rate is out of range.
+    pub fn with_update_rate(mut self, update_hz: f32) -> Result<Self> {
+        ensure!(
+            (MIN_UPDATE_HZ..=MAX_UPDATE_HZ).contains(&update_hz),
+            "Unsupported GPS update rate {update_hz} Hz, expected {MIN_UPDATE_HZ} to {MAX_UPDATE_HZ}"
+        );
+        self.update_hz = Some(update_hz);
+
+        Ok(self)
+    }
+
+    /// Enables exponential smoothing of the reported positions.
+    ///
+    /// Each reported position moves from the previous one towards the measured
+    /// one by `alpha`, which damps the jitter of a stationary receiver. This
+    /// trades responsiveness for stability: the position lags behind when
+    /// moving, so distances computed from smoothed readings are underestimated
+    /// and should not feed a speed or distance tracker without care. Speed and
+    /// course are reported as measured. The filter restarts from the measured
+    /// position after the fix is lost.
+    ///
+    /// # Arguments
+    /// * `alpha` - The weight of a new position, in `(0.0, 1.0]`. Lower values
+    ///   smooth more, 1.0 disables smoothing.
+    ///
+    /// # Returns
+    /// The updated `Config`.
+    ///
+    /// # Errors
+    /// Returns an error if `alpha` is out of range.
+    pub fn with_smoothing(mut self, alpha: f64) -> Result<Self> {
+        ensure!(
+            alpha > 0.0 && alpha <= 1.0,
+            "Invalid GPS smoothing factor {alpha}, expected a value in (0, 1]"
+        );
+        self.smoothing = alpha;
+
+        Ok(self)
+    }
+
+    /// Returns the configured baud rate.
+    ///
+    /// # Returns
+    /// The baud rate as `u32`.
+    #[must_use]
+    pub fn baud_rate(&self) -> u32 {
+        self.baud_rate
+    }
+
+    /// Returns the configured RX pin.
+    ///
+    /// # Returns
+    /// The GPIO number as `i32`.
+    #[must_use]
+    pub fn rx_pin(&self) -> i32 {
+        self.rx_pin
+    }
+
+    /// Returns the configured TX pin, if any.
+    ///
+    /// # Returns
+    /// The GPIO number as `i32`, or `None` for receive-only wiring.
+    #[must_use]
+    pub fn tx_pin(&self) -> Option<i32> {
+        self.tx_pin
+    }
+
+    /// Returns the configured enable pin, if any.
+    ///
+    /// # Returns
+    /// The GPIO number as `i32`, or `None` when the module is always powered.
+    #[must_use]
+    pub fn enable_pin(&self) -> Option<i32> {
+        self.enable_pin
+    }
+
+    /// Returns the position smoothing factor.
+    ///
+    /// # Returns
+    /// The weight of a new position, 1.0 when smoothing is disabled.
+    #[must_use]
+    pub fn smoothing(&self) -> f64 {
+        self.smoothing
+    }
+
+    /// Returns the protocol the GPS module reports fixes with.
+    ///
+    /// # Returns
+    /// The configured [`Protocol`].
+    #[must_use]
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Returns how long a UART read waits for data.
+    ///
+    /// # Returns
+    /// The timeout in milliseconds.
+    #[must_use]
+    pub fn read_timeout_ms(&self) -> u32 {
+        self.read_timeout_ms
+    }
+
+    /// Returns the fix rate to set on the GPS module.
+    ///
+    /// # Returns
+    /// The number of fixes per second, or `None` to keep the module's default.
+    #[must_use]
+    pub fn update_hz(&self) -> Option<f32> {
+        self.update_hz
+    }
+
+    /// Returns the UART driver configuration matching this configuration.
+    ///
+    /// # Returns
+    /// A [`uart::config::Config`] using the configured baud rate.
+    #[cfg(feature = "hardware")]
+    #[must_use]
+    pub fn uart_config(&self) -> uart::config::Config {
+        uart::config::Config::new().baudrate(Hertz(self.baud_rate))
+    }
+
+    /// Creates a `Config` from compile-time environment variables.
+    ///
+    /// Reads `GPS_BAUD_RATE` (default: 115200), `GPS_RX_PIN` (default: 22),
+    /// `GPS_TX_PIN` (default: none), `GPS_EN_PIN` (default: none),
+    /// `GPS_SMOOTHING` (default: 1.0, no smoothing), `GPS_PROTOCOL` (`nmea`
+    /// or `ubx`, default: `nmea`) and `GPS_UPDATE_HZ` (default: none, module
+    /// default) via `option_env!`.
+    ///
+    /// # Returns
+    /// A `Config` populated from environment variables.
+    ///
+    /// # Errors
+    /// Returns an error if a variable is not a number, or the baud rate,
+    /// smoothing factor, protocol or update rate is not supported.
+    pub fn from_env() -> Result<Self> {
+        let baud_rate = option_env!("GPS_BAUD_RATE")
+            .map(str::parse::<u32>)
+            .transpose()?
+            .unwrap_or(DEFAULT_BAUD_RATE);
+        let rx_pin = option_env!("GPS_RX_PIN")
+            .map(str::parse::<i32>)
+            .transpose()?
+            .unwrap_or(DEFAULT_RX_PIN);
+        let tx_pin = option_env!("GPS_TX_PIN")
+            .map(str::parse::<i32>)
+            .transpose()?;
+        let enable_pin = option_env!("GPS_EN_PIN")
+            .map(str::parse::<i32>)
+            .transpose()?;
+
+        let smoothing = option_env!("GPS_SMOOTHING")
+            .map(str::parse::<f64>)
+            .transpose()?
+            .unwrap_or(1.0);
+
+        let read_timeout_ms = option_env!("GPS_READ_TIMEOUT_MS")
+            .map(str::parse::<u32>)
+            .transpose()?
+            .unwrap_or(DEFAULT_READ_TIMEOUT_MS);
+
+        let protocol = match option_env!("GPS_PROTOCOL") {
+            None | Some("nmea") => Protocol::Nmea,
+            Some("ubx") => Protocol::Ubx,
+            Some(other) => {
+                bail!("Unsupported GPS protocol {other}, expected nmea or ubx")
+            }
+        };
+
+        let config = Self::new(baud_rate, rx_pin, tx_pin)?
+            .with_smoothing(smoothing)?
+            .with_read_timeout(read_timeout_ms)?
+            .with_enable_pin(enable_pin)
+            .with_protocol(protocol);
+        match option_env!("GPS_UPDATE_HZ") {
+            Some(update_hz) => config.with_update_rate(update_hz.parse()?),
+            None => Ok(config),
+        }
+    }
+}
+
+/// Protocol a GPS module reports fixes with, see [`Config::with_protocol`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// NMEA GGA and RMC sentences, understood by most modules.
+    #[default]
+    Nmea,
+    /// u-blox UBX-NAV-PVT messages, which carry a whole fix in one binary
+    /// message but no HDOP, so that the HDOP filter does not apply.
+    Ubx,
+}
+
+/// Units speeds are shown in by [`Reading::format_with`].
+///
+/// Serializations such as [`Reading::to_json`] always use SI-derived units,
+/// whatever the display units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Units {
+    /// Kilometers per hour.
+    #[default]
+    Metric,
+    /// Miles per hour.
+    Imperial,
+}
+
+impl Units {
+    /// Converts a speed in knots to these units.
+    fn speed_from_knots(self, knots: f32) -> f32 {
+        match self {
+            Units::Metric => knots * KMH_PER_KNOT,
+            Units::Imperial => knots * MPH_PER_KNOT,
+        }
+    }
+
+    /// Returns the symbol of the speed unit.
+    ///
+    /// # Returns
+    /// `"km/h"` or `"mph"`.
+    #[must_use]
+    pub fn speed_symbol(self) -> &'static str {
+        match self {
+            Units::Metric => "km/h",
+            Units::Imperial => "mph",
+        }
+    }
+}
 
-/// A GPS reading containing position and optional speed data.
+/// A GPS reading containing position, optional speed data, and fix quality.
+///
+/// Speed and course come from RMC sentences, so they are `None` for readings
+/// built from GGA sentences only. Conversely, fix quality comes from GGA
+/// sentences.
 ///
 /// # Fields
 /// * `latitude` - Latitude in decimal degrees.
 /// * `longitude` - Longitude in decimal degrees.
-/// * `speed_mps` - Speed in meters per second, if available from the GPS fix.
+/// * `speed_knots` - Speed over ground in knots, if available from the GPS fix.
+/// * `course_deg` - True course over ground in degrees, if available from the GPS fix.
+/// * `fix_type` - Type of the GPS fix, if known.
+/// * `num_satellites` - Number of satellites used for the fix, if known.
+/// * `hdop` - Horizontal dilution of precision, if known; lower is more accurate.
+/// * `altitude_m` - Altitude above mean sea level in meters, if known.
+/// * `utc_s` - UTC time of the fix in seconds since the Unix epoch, if known.
+/// * `timestamp_ms` - Time of the reading, in milliseconds since boot.
+#[derive(Clone, Debug)]
 pub struct Reading {
     latitude: f64,
     longitude: f64,
-    speed_mps: Option<f32>,
+    speed_knots: Option<f32>,
+    course_deg: Option<f32>,
+    fix_type: Option<FixType>,
+    num_satellites: Option<u32>,
+    hdop: Option<f32>,
+    altitude_m: Option<f32>,
+    utc_s: Option<u64>,
+    timestamp_ms: u64,
+}
+
+impl Reading {
+    /// Creates a new `Reading` at the given position, timestamped with the
+    /// current time.
+    ///
+    /// # Arguments
+    /// * `latitude` - Latitude in decimal degrees.
+    /// * `longitude` - Longitude in decimal degrees.
+    ///
+    /// # Returns
+    /// A new `Reading` instance, without speed data nor fix quality.
+    #[must_use]
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+            speed_knots: None,
+            course_deg: None,
+            fix_type: None,
+            num_satellites: None,
+            hdop: None,
+            altitude_m: None,
+            utc_s: None,
+            timestamp_ms: now_ms(),
+        }
+    }
+
+    /// Sets the speed and course over ground of the reading.
+    ///
+    /// # Arguments
+    /// * `speed_knots` - Speed over ground in knots, or `None` if unavailable.
+    /// * `course_deg` - True course over ground in degrees, or `None` if unavailable.
+    ///
+    /// # Returns
+    /// The `Reading` with speed data.
+    #[must_use]
+    pub fn with_motion(
+        mut self,
+        speed_knots: Option<f32>,
+        course_deg: Option<f32>,
+    ) -> Self {
+        self.speed_knots = speed_knots;
+        self.course_deg = course_deg;
+        self
+    }
+
+    /// Sets the fix quality of the reading.
+    ///
+    /// # Arguments
+    /// * `fix_type` - Type of the GPS fix, or `None` if unknown.
+    /// * `num_satellites` - Number of satellites used for the fix, or `None` if unknown.
+    /// * `hdop` - Horizontal dilution of precision, or `None` if unknown.
+    ///
+    /// # Returns
+    /// The `Reading` with fix quality.
+    #[must_use]
+    pub fn with_quality(
+        mut self,
+        fix_type: Option<FixType>,
+        num_satellites: Option<u32>,
+        hdop: Option<f32>,
+    ) -> Self {
+        self.fix_type = fix_type;
+        self.num_satellites = num_satellites;
+        self.hdop = hdop;
+        self
+    }
+
+    /// Sets the altitude of the reading.
+    ///
+    /// # Arguments
+    /// * `altitude_m` - Altitude above mean sea level in meters, or `None` if unknown.
+    ///
+    /// # Returns
+    /// The `Reading` with its altitude.
+    #[must_use]
+    pub fn with_altitude(mut self, altitude_m: Option<f32>) -> Self {
+        self.altitude_m = altitude_m;
+        self
+    }
+
+    /// Sets the UTC time of the fix, as reported by the GPS module.
+    ///
+    /// # Arguments
+    /// * `utc_s` - Seconds since the Unix epoch, or `None` if unknown.
+    ///
+    /// # Returns
+    /// The `Reading` with its UTC time.
+    #[must_use]
+    pub fn with_utc(mut self, utc_s: Option<u64>) -> Self {
+        self.utc_s = utc_s;
+        self
+    }
+
+    /// Returns the latitude in decimal degrees.
+    ///
+    /// # Returns
+    /// The latitude as `f64`.
+    #[must_use]
+    pub fn latitude(&self) -> f64 {
+        self.latitude
+    }
+
+    /// Returns the longitude in decimal degrees.
+    ///
+    /// # Returns
+    /// The longitude as `f64`.
+    #[must_use]
+    pub fn longitude(&self) -> f64 {
+        self.longitude
+    }
+
+    /// Returns the altitude above mean sea level in meters, if known.
+    ///
+    /// # Returns
+    /// `Some(altitude)` if a GGA sentence reported it, `None` otherwise.
+    #[must_use]
+    pub fn altitude(&self) -> Option<f32> {
+        self.altitude_m
+    }
+
+    /// Returns the speed over ground in knots, if available.
+    ///
+    /// # Returns
+    /// `Some(speed)` if the GPS fix includes speed data, `None` otherwise.
+    #[must_use]
+    pub fn speed_knots(&self) -> Option<f32> {
+        self.speed_knots
+    }
+
+    /// Returns the speed over ground in meters per second, if available.
+    ///
+    /// # Returns
+    /// `Some(speed)` if the GPS fix includes speed data, `None` otherwise.
+    #[must_use]
+    pub fn speed_mps(&self) -> Option<f32> {
+        self.speed_knots.map(|knots| knots * MPS_PER_KNOT)
+    }
+
+    /// Returns the speed over ground in meters per second, if available.
+    ///
+    /// Same as [`Reading::speed_mps`], alongside [`Reading::speed_kmh`] and
+    /// [`Reading::speed_mph`].
+    ///
+    /// # Returns
+    /// `Some(speed)` if the GPS fix includes speed data, `None` otherwise.
+    #[must_use]
+    pub fn speed_ms(&self) -> Option<f32> {
+        self.speed_mps()
+    }
+
+    /// Returns the speed over ground in kilometers per hour, if available.
+    ///
+    /// # Returns
+    /// `Some(speed)` if the GPS fix includes speed data, `None` otherwise.
+    #[must_use]
+    pub fn speed_kmh(&self) -> Option<f32> {
+        self.speed_knots.map(|knots| knots * KMH_PER_KNOT)
+    }
+
+    /// Returns the speed over ground in miles per hour, if available.
+    ///
+    /// # Returns
+    /// `Some(speed)` if the GPS fix includes speed data, `None` otherwise.
+    #[must_use]
+    pub fn speed_mph(&self) -> Option<f32> {
+        self.speed_knots.map(|knots| knots * MPH_PER_KNOT)
+    }
+
+    /// Returns the true course over ground, if available.
+    ///
+    /// # Returns
+    /// `Some(course)` in degrees clockwise from true north if the GPS fix
+    /// includes course data, `None` otherwise.
+    #[must_use]
+    pub fn course_deg(&self) -> Option<f32> {
+        self.course_deg
+    }
+
+    /// Returns the type of the GPS fix, if known.
+    ///
+    /// # Returns
+    /// `Some(fix_type)` if a GGA sentence reported it, `None` otherwise.
+    #[must_use]
+    pub fn fix_type(&self) -> Option<FixType> {
+        self.fix_type
+    }
+
+    /// Returns the number of satellites used for the fix, if known.
+    ///
+    /// # Returns
+    /// `Some(count)` if a GGA sentence reported it, `None` otherwise.
+    #[must_use]
+    pub fn num_satellites(&self) -> Option<u32> {
+        self.num_satellites
+    }
+
+    /// Returns the horizontal dilution of precision, if known.
+    ///
+    /// # Returns
+    /// `Some(hdop)` if a GGA sentence reported it, `None` otherwise. Values
+    /// below 2 are good, above 5 the position is unreliable.
+    #[must_use]
+    pub fn hdop(&self) -> Option<f32> {
+        self.hdop
+    }
+
+    /// Returns the UTC time of the fix, if known.
+    ///
+    /// Unlike [`Reading::timestamp_ms`], this is the real-world time given by
+    /// the satellites, which does not require the system clock to be synchronized.
+    ///
+    /// # Returns
+    /// `Some(seconds)` since the Unix epoch once both the time and date of the
+    /// fix were received, `None` otherwise.
+    #[must_use]
+    pub fn utc_s(&self) -> Option<u64> {
+        self.utc_s
+    }
+
+    /// Returns the time of the reading.
+    ///
+    /// # Returns
+    /// The number of milliseconds since boot when the reading was taken.
+    #[must_use]
+    pub fn timestamp_ms(&self) -> u64 {
+        self.timestamp_ms
+    }
+
+    /// Encodes the reading into a fixed-size binary form, e.g. for a BLE
+    /// characteristic.
+    ///
+    /// The little-endian layout is a version byte, the latitude and longitude
+    /// as `i32` in 1e-7 degrees, the altitude as `i16` in meters (`i16::MIN`
+    /// when unknown), the speed as `u16` in cm/s (`u16::MAX` when unknown),
+    /// the UTC time of the fix as `u32` seconds since the Unix epoch (0 when
+    /// unknown), and a CRC-8 of the preceding bytes. Other fields are not
+    /// encoded. At 18 bytes, it does not fit in a legacy BLE advertisement
+    /// next to the advertised name, so it is sent in the scan response.
+    ///
+    /// # Returns
+    /// The encoded reading.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn to_bytes(&self) -> [u8; ENCODED_LEN] {
+        let degrees = |value: f64| (value * ENCODED_DEG_SCALE).round() as i32;
+        let altitude = self.altitude_m.map_or(i16::MIN, |altitude| {
+            altitude
+                .round()
+                .clamp(f32::from(i16::MIN + 1), f32::from(i16::MAX))
+                as i16
+        });
+        let speed = self.speed_mps().map_or(u16::MAX, |speed| {
+            (speed * 100.0).round().clamp(0.0, f32::from(u16::MAX - 1)) as u16
+        });
+        let utc = self
+            .utc_s
+            .and_then(|utc_s| u32::try_from(utc_s).ok())
+            .unwrap_or_default();
+
+        let mut bytes = [0; ENCODED_LEN];
+        bytes[0] = ENCODING_VERSION;
+        bytes[1..5].copy_from_slice(&degrees(self.latitude).to_le_bytes());
+        bytes[5..9].copy_from_slice(&degrees(self.longitude).to_le_bytes());
+        bytes[9..11].copy_from_slice(&altitude.to_le_bytes());
+        bytes[11..13].copy_from_slice(&speed.to_le_bytes());
+        bytes[13..17].copy_from_slice(&utc.to_le_bytes());
+        bytes[17] = crc8(&bytes[..17]);
+
+        bytes
+    }
+
+    /// Decodes a reading encoded by [`Reading::to_bytes`].
+    ///
+    /// The decoded reading is timestamped with the current time, and has no
+    /// course nor fix quality.
+    ///
+    /// # Arguments
+    /// * `bytes` - The encoded reading.
+    ///
+    /// # Returns
+    /// The decoded `Reading`.
+    ///
+    /// # Errors
+    /// Returns an error if the length, version or checksum does not match.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == ENCODED_LEN,
+            "Invalid encoded GPS reading length {}, expected {ENCODED_LEN}",
+            bytes.len()
+        );
+        ensure!(
+            bytes[0] == ENCODING_VERSION,
+            "Unsupported encoded GPS reading version {}",
+            bytes[0]
+        );
+        ensure!(
+            bytes[17] == crc8(&bytes[..17]),
+            "Invalid encoded GPS reading checksum"
+        );
+
+        let degrees = |at: usize| {
+            f64::from(i32::from_le_bytes([
+                bytes[at],
+                bytes[at + 1],
+                bytes[at + 2],
+                bytes[at + 3],
+            ])) / ENCODED_DEG_SCALE
+        };
+        let altitude = i16::from_le_bytes([bytes[9], bytes[10]]);
+        let speed = u16::from_le_bytes([bytes[11], bytes[12]]);
+        let utc = u32::from_le_bytes([bytes[13], bytes[14], bytes[15], bytes[16]]);
+
+        Ok(Reading::new(degrees(1), degrees(5))
+            .with_motion(
+                (speed != u16::MAX)
+                    .then_some(f32::from(speed) / 100.0 / MPS_PER_KNOT),
+                None,
+            )
+            .with_altitude((altitude != i16::MIN).then_some(f32::from(altitude)))
+            .with_utc((utc != 0).then_some(u64::from(utc))))
+    }
+
+    /// Serializes the reading to JSON, e.g. for an HTTP upload.
+    ///
+    /// The document has the shape
+    /// `{"lat":48.8584,"lon":2.2945,"alt":35.0,"ts":1700000000,"speed_kmh":12.34}`,
+    /// with `alt` in meters, `ts` the UTC time of the fix in seconds since the
+    /// Unix epoch, and unknown values `null`.
+    ///
+    /// # Returns
+    /// The JSON document as a `String`.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"lat\":{:.7},\"lon\":{:.7},\"alt\":{},\"ts\":{},\"speed_kmh\":{}}}",
+            self.latitude,
+            self.longitude,
+            json_number(self.altitude_m.map(|altitude| format!("{altitude:.1}"))),
+            json_number(self.utc_s.map(|utc_s| utc_s.to_string())),
+            json_number(self.speed_kmh().map(|speed| format!("{speed:.2}")))
+        )
+    }
+
+    /// Encodes the position as a geohash, a compact string suited to small
+    /// payloads such as BLE manufacturer data.
+    ///
+    /// Each character narrows the cell containing the position: 5 characters
+    /// are about 5 km wide, 8 about 40 m and 10 about 1 m. Decoding a geohash
+    /// gives back that cell, not the exact position.
+    ///
+    /// # Arguments
+    /// * `precision` - Number of characters, capped to 12.
+    ///
+    /// # Returns
+    /// The geohash, empty when `precision` is 0.
+    #[must_use]
+    pub fn geohash(&self, precision: usize) -> String {
+        let precision = precision.min(GEOHASH_MAX_PRECISION);
+        let (mut lat, mut lon) = ((-90.0, 90.0), (-180.0, 180.0));
+        let mut hash = String::with_capacity(precision);
+        let mut is_lon = true;
+
+        for _ in 0..precision {
+            let mut index = 0;
+            for _ in 0..5 {
+                let (range, value) = if is_lon {
+                    (&mut lon, self.longitude)
+                } else {
+                    (&mut lat, self.latitude)
+                };
+                let mid = (range.0 + range.1) / 2.0;
+                index <<= 1;
+                if value >= mid {
+                    index |= 1;
+                    range.0 = mid;
+                } else {
+                    range.1 = mid;
+                }
+                is_lon = !is_lon;
+            }
+            hash.push(char::from(GEOHASH_ALPHABET[index]));
+        }
+
+        hash
+    }
+
+    /// Checks whether the reading is too old to describe the current position,
+    /// e.g. after the GPS lost its fix.
+    ///
+    /// # Arguments
+    /// * `max_age_ms` - The maximum age of a fresh reading, in milliseconds.
+    ///
+    /// # Returns
+    /// `true` if the reading is older than `max_age_ms`, `false` otherwise.
+    #[must_use]
+    pub fn is_stale(&self, max_age_ms: u64) -> bool {
+        self.age_ms() > max_age_ms
+    }
+
+    /// Returns the age of the reading, based on the uptime at capture so that
+    /// it does not depend on the system clock being synchronized.
+    ///
+    /// # Returns
+    /// The number of milliseconds elapsed since the reading was taken.
+    #[must_use]
+    pub fn age_ms(&self) -> u64 {
+        now_ms().saturating_sub(self.timestamp_ms)
+    }
+
+    /// Formats the reading like [`Display`], with the speed in the given
+    /// units instead of meters per second.
+    ///
+    /// # Arguments
+    /// * `units` - The units of the speed.
+    ///
+    /// # Returns
+    /// The formatted reading, e.g. `Lat: 48.8584, Lon: 2.2945, Speed: 12.34 km/h`.
+    #[must_use]
+    pub fn format_with(&self, units: Units) -> String {
+        let speed = self.speed_knots.map(|knots| {
+            format!(
+                "{:.2} {}",
+                units.speed_from_knots(knots),
+                units.speed_symbol()
+            )
+        });
+        format!("{}", ReadingDisplay(self, speed))
+    }
+}
+
+/// Formats a reading with its speed already formatted, `None` when unknown.
+struct ReadingDisplay<'a>(&'a Reading, Option<String>);
+
+impl Display for ReadingDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self(reading, speed) = self;
+        write!(
+            f,
+            "Lat: {}, Lon: {}, Speed: {}",
+            reading.latitude,
+            reading.longitude,
+            speed.as_deref().unwrap_or("N/A")
+        )?;
+        if let Some(c) = reading.course_deg {
+            write!(f, ", Course: {c:.1}°")?;
+        }
+        if let Some(n) = reading.num_satellites {
+            write!(f, ", Sats: {n}")?;
+        }
+        match reading.utc_s {
+            Some(s) => write!(f, ", UTC: {s}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for Reading {
+    /// Formats the reading as `Lat: {lat}, Lon: {lon}, Speed: {speed} m/s`
+    /// (or `N/A` if no speed), followed by `, Course: {course}°`,
+    /// `, Sats: {count}` and `, UTC: {seconds since epoch}` when known.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ReadingDisplay(self, self.speed_mps().map(|s| format!("{s:.2} m/s"))).fmt(f)
+    }
+}
+
+/// A satellite in view of the GPS module, as reported by GSV sentences.
+///
+/// # Fields
+/// * `prn` - The satellite's PRN number.
+/// * `elevation_deg` - Elevation above the horizon in degrees, if known.
+/// * `azimuth_deg` - Azimuth from true north in degrees, if known.
+/// * `snr_db` - Signal to noise ratio in dB-Hz, or `None` when the satellite
+///   is not tracked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SatelliteInView {
+    prn: u32,
+    elevation_deg: Option<u8>,
+    azimuth_deg: Option<u16>,
+    snr_db: Option<u8>,
+}
+
+impl SatelliteInView {
+    /// Returns the satellite's PRN number.
+    ///
+    /// # Returns
+    /// The PRN as `u32`.
+    #[must_use]
+    pub fn prn(&self) -> u32 {
+        self.prn
+    }
+
+    /// Returns the elevation above the horizon.
+    ///
+    /// # Returns
+    /// The elevation in degrees, from 0 to 90, or `None` if unknown.
+    #[must_use]
+    pub fn elevation_deg(&self) -> Option<u8> {
+        self.elevation_deg
+    }
+
+    /// Returns the azimuth from true north.
+    ///
+    /// # Returns
+    /// The azimuth in degrees, from 0 to 359, or `None` if unknown.
+    #[must_use]
+    pub fn azimuth_deg(&self) -> Option<u16> {
+        self.azimuth_deg
+    }
+
+    /// Returns the signal to noise ratio.
+    ///
+    /// # Returns
+    /// The SNR in dB-Hz, or `None` when the satellite is not tracked.
+    #[must_use]
+    pub fn snr_db(&self) -> Option<u8> {
+        self.snr_db
+    }
+}
+
+/// Counters describing the health of the serial link to the GPS module.
+///
+/// A high share of checksum failures usually means the UART baud rate does
+/// not match the module's, while parse failures point at unsupported or
+/// truncated sentences.
+///
+/// # Fields
+/// * `lines` - Number of non-empty lines received.
+/// * `checksum_failures` - Number of lines with a missing or wrong checksum.
+/// * `parse_failures` - Number of lines with a valid checksum that could not
+///   be parsed.
+/// * `hdop_rejections` - Number of readings dropped for an HDOP above the
+///   sensor's ceiling.
+/// * `time_to_first_fix_ms` - Time from the start of polling, or from the
+///   last power-up of the module, to the first reading, in milliseconds.
+/// * `last_reacquisition_ms` - Duration of the last fix outage, from the last
+///   reading before the fix was lost to the first one after, in milliseconds.
+/// * `satellites` - The satellites in view from the last complete GSV
+///   sequence, strongest first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    lines: u32,
+    checksum_failures: u32,
+    parse_failures: u32,
+    hdop_rejections: u32,
+    time_to_first_fix_ms: Option<u64>,
+    last_reacquisition_ms: Option<u64>,
+    satellites: [Option<SatelliteInView>; MAX_SATELLITES_IN_VIEW],
+}
+
+impl Stats {
+    /// Returns the number of lines received.
+    ///
+    /// # Returns
+    /// The number of non-empty lines read from the UART.
+    #[must_use]
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// Returns the number of checksum failures.
+    ///
+    /// # Returns
+    /// The number of lines rejected because of a missing or wrong checksum.
+    #[must_use]
+    pub fn checksum_failures(&self) -> u32 {
+        self.checksum_failures
+    }
+
+    /// Returns the number of parse failures.
+    ///
+    /// # Returns
+    /// The number of lines with a valid checksum that could not be parsed.
+    #[must_use]
+    pub fn parse_failures(&self) -> u32 {
+        self.parse_failures
+    }
+
+    /// Returns the number of readings dropped for a poor HDOP.
+    ///
+    /// # Returns
+    /// The number of readings whose HDOP exceeded the sensor's ceiling.
+    #[must_use]
+    pub fn hdop_rejections(&self) -> u32 {
+        self.hdop_rejections
+    }
+
+    /// Returns how long the module took to get its first fix.
+    ///
+    /// A long time to first fix after power-on is a cold start, which a
+    /// backup battery on the module avoids.
+    ///
+    /// # Returns
+    /// The time in milliseconds, or `None` until the first reading.
+    #[must_use]
+    pub fn time_to_first_fix_ms(&self) -> Option<u64> {
+        self.time_to_first_fix_ms
+    }
+
+    /// Returns how long the last fix outage lasted.
+    ///
+    /// # Returns
+    /// The time in milliseconds, or `None` if the fix was never lost and
+    /// reacquired. Requires [`Sensor::with_fix_triggers`].
+    #[must_use]
+    pub fn last_reacquisition_ms(&self) -> Option<u64> {
+        self.last_reacquisition_ms
+    }
+
+    /// Returns the satellites in view, e.g. to check the antenna placement.
+    ///
+    /// GSV sentences are only parsed when the module sends them, which
+    /// [`Sensor::configure_module`] limits to once every 5 fixes when debug
+    /// logs are enabled, and disables otherwise.
+    ///
+    /// # Returns
+    /// An iterator over at most [`MAX_SATELLITES_IN_VIEW`] satellites,
+    /// strongest first.
+    pub fn satellites(&self) -> impl Iterator<Item = &SatelliteInView> {
+        self.satellites.iter().flatten()
+    }
+
+    /// Returns the average signal to noise ratio of the tracked satellites.
+    ///
+    /// # Returns
+    /// The average SNR in dB-Hz, or `None` when no satellite is tracked.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn average_snr(&self) -> Option<f32> {
+        let (count, total) = self
+            .satellites()
+            .filter_map(SatelliteInView::snr_db)
+            .fold((0u32, 0u32), |(count, total), snr| {
+                (count + 1, total + u32::from(snr))
+            });
+
+        (count > 0).then(|| total as f32 / count as f32)
+    }
+
+    /// Returns the share of lines that failed either check.
+    ///
+    /// # Returns
+    /// A ratio between 0.0 and 1.0, 0.0 when no line was received.
+    #[must_use]
+    pub fn failure_ratio(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            f64::from(self.checksum_failures.saturating_add(self.parse_failures))
+                / f64::from(self.lines)
+        }
+    }
+}
+
+impl Display for Stats {
+    /// Formats the counters for display.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Lines: {}, Checksum failures: {}, Parse failures: {}, HDOP rejections: {}, TTFF: {:?} ms",
+            self.lines,
+            self.checksum_failures,
+            self.parse_failures,
+            self.hdop_rejections,
+            self.time_to_first_fix_ms
+        )
+    }
+}
+
+/// Summary of the speeds and distance covered since a [`SpeedTracker`] was
+/// created or reset.
+///
+/// # Fields
+/// * `max_speed_mps` - Highest speed seen, in meters per second.
+/// * `average_speed_mps` - Average speed while moving, in meters per second.
+/// * `distance_m` - Distance covered while moving, in meters.
+/// * `moving_time_ms` - Time spent moving, in milliseconds.
+/// * `elevation_gain_m` - Total climb, in meters.
+/// * `elevation_loss_m` - Total descent, in meters.
+/// * `heading_deg` - Current heading, in degrees clockwise from true north.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpeedSummary {
+    max_speed_mps: f32,
+    average_speed_mps: f32,
+    distance_m: f64,
+    moving_time_ms: u64,
+    elevation_gain_m: f64,
+    elevation_loss_m: f64,
+    heading_deg: Option<f64>,
+}
+
+impl SpeedSummary {
+    /// Returns the highest speed seen.
+    ///
+    /// # Returns
+    /// The speed in meters per second.
+    #[must_use]
+    pub fn max_speed_mps(&self) -> f32 {
+        self.max_speed_mps
+    }
+
+    /// Returns the average speed while moving, i.e. the distance covered over
+    /// the time spent moving, so that stops do not lower it.
+    ///
+    /// # Returns
+    /// The speed in meters per second, 0.0 before any movement.
+    #[must_use]
+    pub fn average_speed_mps(&self) -> f32 {
+        self.average_speed_mps
+    }
+
+    /// Returns the distance covered while moving.
+    ///
+    /// # Returns
+    /// The distance in meters.
+    #[must_use]
+    pub fn distance_m(&self) -> f64 {
+        self.distance_m
+    }
+
+    /// Returns the time spent moving.
+    ///
+    /// # Returns
+    /// The number of milliseconds spent above the moving speed threshold.
+    #[must_use]
+    pub fn moving_time_ms(&self) -> u64 {
+        self.moving_time_ms
+    }
+
+    /// Returns the total climb.
+    ///
+    /// # Returns
+    /// The sum of the altitude gains, in meters.
+    #[must_use]
+    pub fn elevation_gain_m(&self) -> f64 {
+        self.elevation_gain_m
+    }
+
+    /// Returns the total descent.
+    ///
+    /// # Returns
+    /// The sum of the altitude losses, in meters, as a positive number.
+    #[must_use]
+    pub fn elevation_loss_m(&self) -> f64 {
+        self.elevation_loss_m
+    }
+
+    /// Returns the current heading.
+    ///
+    /// # Returns
+    /// The heading in degrees clockwise from true north, `None` until the
+    /// receiver moved.
+    #[must_use]
+    pub fn heading_deg(&self) -> Option<f64> {
+        self.heading_deg
+    }
+
+    /// Serializes the summary to JSON, e.g. for an HTTP upload.
+    ///
+    /// The document has the shape
+    /// `{"max_speed_kmh":32.5,"avg_speed_kmh":18.25,"distance_m":5230.4,"moving_s":1032,"gain_m":120.5,"loss_m":98.0,"heading_deg":271.5}`,
+    /// with an unknown heading `null`.
+    ///
+    /// # Returns
+    /// The JSON document as a `String`.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"max_speed_kmh\":{:.2},\"avg_speed_kmh\":{:.2},\"distance_m\":{:.1},\"moving_s\":{},\"gain_m\":{:.1},\"loss_m\":{:.1},\"heading_deg\":{}}}",
+            self.max_speed_mps * 3.6,
+            self.average_speed_mps * 3.6,
+            self.distance_m,
+            self.moving_time_ms / 1000,
+            self.elevation_gain_m,
+            self.elevation_loss_m,
+            json_number(self.heading_deg.map(|heading| format!("{heading:.1}")))
+        )
+    }
+}
+
+impl Display for SpeedSummary {
+    /// Formats the summary for display, with speeds in km/h, followed by
+    /// `, Heading: {heading}°` when known.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Max: {:.2} km/h, Average: {:.2} km/h, Distance: {:.1} m, Moving: {} s, Climb: +{:.1}/-{:.1} m",
+            self.max_speed_mps * 3.6,
+            self.average_speed_mps * 3.6,
+            self.distance_m,
+            self.moving_time_ms / 1000,
+            self.elevation_gain_m,
+            self.elevation_loss_m
+        )?;
+        match self.heading_deg {
+            Some(heading) => write!(f, ", Heading: {heading:.0}°"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Tracks the maximum and average speeds and the distance covered from
+/// successive readings.
+///
+/// Speeds come from the readings when known, and are otherwise derived from
+/// the distance to the previous reading. The interval leading to a reading
+/// only counts towards the moving time when that speed reaches the moving
+/// threshold. Distance accumulates with [`haversine`] from the last counted
+/// position, once the receiver moved at least the minimum step away from it,
+/// so that the position jitter of a stationary receiver does not add up.
+/// Readings implying a speed above 100 m/s are GPS glitches and are ignored.
+///
+/// Climbs and descents are tracked from the median of the last three
+/// altitudes, which discards single-sample spikes, and likewise only
+/// accumulate once the altitude moved at least the minimum climb away from the
+/// last counted one.
+///
+/// The heading averages the bearings of the last three steps of at least the
+/// minimum step, so that it neither follows the jitter of a slow receiver nor
+/// lags far behind turns.
+pub struct SpeedTracker {
+    min_moving_mps: f32,
+    min_step_m: f64,
+    min_climb_m: f64,
+    last: Option<Reading>,
+    anchor: Option<Reading>,
+    max_speed_mps: f32,
+    distance_m: f64,
+    moving_time_ms: u64,
+    altitudes: Vec<f32>,
+    altitude_anchor: Option<f64>,
+    elevation_gain_m: f64,
+    elevation_loss_m: f64,
+    heading_anchor: Option<Reading>,
+    bearings: Vec<f64>,
+    heading_deg: Option<f64>,
+}
+
+impl SpeedTracker {
+    /// Creates a new `SpeedTracker`.
+    ///
+    /// # Arguments
+    /// * `min_moving_mps` - Speed below which readings are considered
+    ///   stationary, in meters per second.
+    ///
+    /// # Returns
+    /// A new `SpeedTracker` with nothing tracked yet.
+    #[must_use]
+    pub fn new(min_moving_mps: f32) -> Self {
+        Self {
+            min_moving_mps,
+            min_step_m: DEFAULT_MIN_STEP_M,
+            min_climb_m: DEFAULT_MIN_CLIMB_M,
+            last: None,
+            anchor: None,
+            max_speed_mps: 0.0,
+            distance_m: 0.0,
+            moving_time_ms: 0,
+            altitudes: Vec::with_capacity(ALTITUDE_WINDOW),
+            altitude_anchor: None,
+            elevation_gain_m: 0.0,
+            elevation_loss_m: 0.0,
+            heading_anchor: None,
+            bearings: Vec::with_capacity(HEADING_WINDOW),
+            heading_deg: None,
+        }
+    }
+
+    /// Sets the distance below which steps are considered jitter.
+    ///
+    /// # Arguments
+    /// * `min_step_m` - The minimum step, in meters. Defaults to
+    ///   [`DEFAULT_MIN_STEP_M`].
+    ///
+    /// # Returns
+    /// The updated `SpeedTracker`.
+    #[must_use]
+    pub fn with_min_step(mut self, min_step_m: f64) -> Self {
+        self.min_step_m = min_step_m;
+        self
+    }
+
+    /// Sets the altitude change below which climbs and descents are
+    /// considered noise.
+    ///
+    /// # Arguments
+    /// * `min_climb_m` - The minimum climb, in meters. Defaults to
+    ///   [`DEFAULT_MIN_CLIMB_M`].
+    ///
+    /// # Returns
+    /// The updated `SpeedTracker`.
+    #[must_use]
+    pub fn with_min_climb(mut self, min_climb_m: f64) -> Self {
+        self.min_climb_m = min_climb_m;
+        self
+    }
+
+    // Accumulates the climb or descent to the median of the recent altitudes.
+    fn update_elevation(&mut self, altitude_m: f32) {
+        if self.altitudes.len() == ALTITUDE_WINDOW {
+            self.altitudes.remove(0);
+        }
+        self.altitudes.push(altitude_m);
+
+        let median_m = (self.altitudes.len() == ALTITUDE_WINDOW).then(|| {
+            let mut sorted = self.altitudes.clone();
+            sorted.sort_by(f32::total_cmp);
+            f64::from(sorted[ALTITUDE_WINDOW / 2])
+        });
+
+        match (self.altitude_anchor, median_m) {
+            (None, Some(median_m)) => self.altitude_anchor = Some(median_m),
+            (Some(anchor_m), Some(median_m))
+                if (median_m - anchor_m).abs() >= self.min_climb_m =>
+            {
+                let delta_m = median_m - anchor_m;
+                if delta_m > 0.0 {
+                    self.elevation_gain_m += delta_m;
+                } else {
+                    self.elevation_loss_m -= delta_m;
+                }
+                self.altitude_anchor = Some(median_m);
+            }
+            _ => (),
+        }
+    }
+
+    // Averages the bearing from the last heading position into the heading,
+    // once the receiver moved at least the minimum step away from it.
+    fn update_heading(&mut self, reading: &Reading) {
+        match self.heading_anchor.as_ref() {
+            Some(anchor) if haversine(anchor, reading) < self.min_step_m => (),
+            Some(anchor) => {
+                if self.bearings.len() == HEADING_WINDOW {
+                    self.bearings.remove(0);
+                }
+                self.bearings.push(bearing(anchor, reading));
+
+                let (sin, cos) =
+                    self.bearings.iter().fold((0.0, 0.0), |(sin, cos), deg| {
+                        let rad = deg.to_radians();
+                        (sin + rad.sin(), cos + rad.cos())
+                    });
+                self.heading_deg =
+                    Some(sin.atan2(cos).to_degrees().rem_euclid(360.0));
+                self.heading_anchor = Some(reading.clone());
+            }
+            None => self.heading_anchor = Some(reading.clone()),
+        }
+    }
+
+    /// Returns how far the heading turned away from a reference one, e.g. a
+    /// stored route heading.
+    ///
+    /// # Arguments
+    /// * `deg` - The reference heading, in degrees clockwise from true north.
+    ///
+    /// # Returns
+    /// The change in degrees, in `[-180, 180)` and positive when turned
+    /// clockwise, `None` until the receiver moved.
+    #[must_use]
+    pub fn heading_change_since(&self, deg: f64) -> Option<f64> {
+        self.heading_deg
+            .map(|heading| (heading - deg + 180.0).rem_euclid(360.0) - 180.0)
+    }
+
+    /// Accounts for a new reading.
+    ///
+    /// # Arguments
+    /// * `reading` - The reading, expected to be more recent than the
+    ///   previous one.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn update(&mut self, reading: &Reading) {
+        let (step_m, elapsed_ms) = self.last.as_ref().map_or((0.0, 0), |last| {
+            (
+                haversine(last, reading),
+                reading.timestamp_ms.saturating_sub(last.timestamp_ms),
+            )
+        });
+        let step_speed_mps = if elapsed_ms > 0 {
+            step_m * 1000.0 / elapsed_ms as f64
+        } else {
+            0.0
+        };
+
+        // Restart from a glitch rather than dropping it, so that a lasting
+        // jump is only ignored once
+        if step_speed_mps > MAX_PLAUSIBLE_SPEED_MPS {
+            debug!("Ignoring GPS glitch of {step_m:.1} m in {elapsed_ms} ms");
+            self.anchor = Some(reading.clone());
+            self.heading_anchor = Some(reading.clone());
+        } else {
+            if let Some(altitude_m) = reading.altitude() {
+                self.update_elevation(altitude_m);
+            }
+            self.update_heading(reading);
+
+            let speed_mps = reading.speed_mps().unwrap_or(step_speed_mps as f32);
+            self.max_speed_mps = self.max_speed_mps.max(speed_mps);
+
+            if speed_mps < self.min_moving_mps {
+                self.anchor = Some(reading.clone());
+            } else {
+                self.moving_time_ms += elapsed_ms;
+                match self
+                    .anchor
+                    .as_ref()
+                    .map(|anchor| haversine(anchor, reading))
+                {
+                    Some(distance_m) if distance_m < self.min_step_m => (),
+                    distance_m => {
+                        self.distance_m += distance_m.unwrap_or_default();
+                        self.anchor = Some(reading.clone());
+                    }
+                }
+            }
+        }
+        self.last = Some(reading.clone());
+    }
+
+    /// Returns what was tracked so far.
+    ///
+    /// # Returns
+    /// A [`SpeedSummary`] of the readings since creation or the last reset.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn summary(&self) -> SpeedSummary {
+        SpeedSummary {
+            max_speed_mps: self.max_speed_mps,
+            average_speed_mps: if self.moving_time_ms > 0 {
+                (self.distance_m * 1000.0 / self.moving_time_ms as f64) as f32
+            } else {
+                0.0
+            },
+            distance_m: self.distance_m,
+            moving_time_ms: self.moving_time_ms,
+            elevation_gain_m: self.elevation_gain_m,
+            elevation_loss_m: self.elevation_loss_m,
+            heading_deg: self.heading_deg,
+        }
+    }
+
+    /// Forgets everything tracked so far, e.g. when starting a new ride.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.min_moving_mps)
+            .with_min_step(self.min_step_m)
+            .with_min_climb(self.min_climb_m);
+    }
+}
+
+impl Default for SpeedTracker {
+    /// Creates a `SpeedTracker` using [`DEFAULT_MIN_MOVING_MPS`].
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_MOVING_MPS)
+    }
+}
+
+/// Detects when a receiver starts and stops moving from the speed of
+/// successive readings.
+///
+/// Movement starts after a number of consecutive readings above the start
+/// speed, and stops after a number of consecutive readings below the stop
+/// speed. Keeping the stop speed below the start speed, and requiring more
+/// readings to stop, avoids flapping at traffic lights. Readings without a
+/// speed are ignored. The detector expects one reading per fix, as returned
+/// by [`Decoder::push`], so that the counts are counts of fixes.
+#[derive(Clone, Copy, Debug)]
+pub struct MotionDetector {
+    start_kmh: f32,
+    start_readings: u32,
+    stop_kmh: f32,
+    stop_readings: u32,
+    moving: bool,
+    streak: u32,
+}
+
+impl MotionDetector {
+    /// Creates a new `MotionDetector`, stationary until proven otherwise.
+    ///
+    /// # Returns
+    /// A `MotionDetector` using [`DEFAULT_MOTION_START_KMH`],
+    /// [`DEFAULT_MOTION_START_READINGS`], [`DEFAULT_MOTION_STOP_KMH`] and
+    /// [`DEFAULT_MOTION_STOP_READINGS`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            start_kmh: DEFAULT_MOTION_START_KMH,
+            start_readings: DEFAULT_MOTION_START_READINGS,
+            stop_kmh: DEFAULT_MOTION_STOP_KMH,
+            stop_readings: DEFAULT_MOTION_STOP_READINGS,
+            moving: false,
+            streak: 0,
+        }
+    }
+
+    /// Sets when movement starts.
+    ///
+    /// # Arguments
+    /// * `kmh` - Speed above which readings count towards starting, in km/h.
+    /// * `readings` - Number of consecutive such readings needed, at least 1.
+    ///
+    /// # Returns
+    /// The updated `MotionDetector`.
+    ///
+    /// # Errors
+    /// Returns an error if `readings` is zero.
+    pub fn with_start(mut self, kmh: f32, readings: u32) -> Result<Self> {
+        ensure!(readings > 0, "Motion start needs at least one reading");
+        self.start_kmh = kmh;
+        self.start_readings = readings;
+        Ok(self)
+    }
+
+    /// Sets when movement stops.
+    ///
+    /// # Arguments
+    /// * `kmh` - Speed below which readings count towards stopping, in km/h.
+    /// * `readings` - Number of consecutive such readings needed, at least 1.
+    ///
+    /// # Returns
+    /// The updated `MotionDetector`.
+    ///
+    /// # Errors
+    /// Returns an error if `readings` is zero.
+    pub fn with_stop(mut self, kmh: f32, readings: u32) -> Result<Self> {
+        ensure!(readings > 0, "Motion stop needs at least one reading");
+        self.stop_kmh = kmh;
+        self.stop_readings = readings;
+        Ok(self)
+    }
+
+    /// Returns whether the receiver is currently moving.
+    ///
+    /// # Returns
+    /// `true` once movement started, until it stops.
+    #[must_use]
+    pub fn is_moving(&self) -> bool {
+        self.moving
+    }
+
+    /// Adds a reading to the detector.
+    ///
+    /// # Arguments
+    /// * `reading` - The new reading.
+    ///
+    /// # Returns
+    /// `Some(true)` if movement just started, `Some(false)` if it just
+    /// stopped, `None` otherwise.
+    pub fn update(&mut self, reading: &Reading) -> Option<bool> {
+        let kmh = reading.speed_kmh()?;
+        let (counts, needed) = if self.moving {
+            (kmh < self.stop_kmh, self.stop_readings)
+        } else {
+            (kmh > self.start_kmh, self.start_readings)
+        };
+        self.streak = if counts {
+            self.streak.saturating_add(1)
+        } else {
+            0
+        };
+
+        (self.streak >= needed).then(|| {
+            self.moving = !self.moving;
+            self.streak = 0;
+            self.moving
+        })
+    }
+}
+
+impl Default for MotionDetector {
+    /// Creates a `MotionDetector` using the default thresholds.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A trait representing the byte stream a [`Sensor`] reads NMEA sentences,
+/// or UBX messages, from.
+///
+/// Implemented by [`UartRxDriver`] for hardware and by [`ReplaySource`] so
+/// that the GPS pipeline can run without a fix.
+pub trait NmeaSource {
+    /// Reads the next bytes of the stream.
+    ///
+    /// # Arguments
+    /// * `buf` - The buffer to fill.
+    /// * `timeout` - How long to wait for data, in `FreeRTOS` ticks.
+    ///
+    /// # Returns
+    /// The number of bytes read, 0 when none arrived in time.
+    ///
+    /// # Errors
+    /// Returns an error if the stream cannot be read.
+    fn read(&mut self, buf: &mut [u8], timeout: u32) -> Result<usize>;
+}
+
+#[cfg(feature = "hardware")]
+impl NmeaSource for UartRxDriver<'_> {
+    /// Reads the bytes received by the UART.
+    ///
+    /// # Errors
+    /// Returns an error if the UART driver fails.
+    fn read(&mut self, buf: &mut [u8], timeout: u32) -> Result<usize> {
+        Ok(UartRxDriver::read(self, buf, timeout)?)
+    }
+}
+
+/// An [`NmeaSource`] replaying canned NMEA sentences, e.g. a recorded ride
+/// embedded with `include_bytes!`, for bench testing indoors.
+///
+/// Each read returns the next line after waiting for the line interval, and
+/// the replay starts over once all lines were read.
+pub struct ReplaySource {
+    data: &'static [u8],
+    position: usize,
+    line_interval_ms: u32,
+}
+
+impl ReplaySource {
+    /// Creates a new `ReplaySource`.
+    ///
+    /// # Arguments
+    /// * `data` - The NMEA sentences to replay, each ending with `\r\n`.
+    /// * `line_interval_ms` - The delay before each line, in milliseconds,
+    ///   e.g. 500 for a GGA and an RMC sentence per second; 0 replays as fast
+    ///   as possible.
+    ///
+    /// # Returns
+    /// A new `ReplaySource` starting at the first line.
+    #[must_use]
+    pub fn new(data: &'static [u8], line_interval_ms: u32) -> Self {
+        Self {
+            data,
+            position: 0,
+            line_interval_ms,
+        }
+    }
+}
+
+impl NmeaSource for ReplaySource {
+    /// Returns the next line, or as much of it as fits in `buf`, ignoring
+    /// `timeout`.
+    fn read(&mut self, buf: &mut [u8], _timeout: u32) -> Result<usize> {
+        if self.line_interval_ms > 0 {
+            sleep(self.line_interval_ms);
+        }
+        if self.position >= self.data.len() {
+            self.position = 0;
+        }
+
+        let rest = &self.data[self.position..];
+        let line_len = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(rest.len(), |i| i + 1);
+        let n = line_len.min(buf.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.position += n;
+
+        Ok(n)
+    }
+}
+
+/// The position sentences received for the fix time of an epoch.
+///
+/// # Fields
+/// * `time` - The fix time of the epoch, `None` for sentences without one.
+/// * `gga` - Whether a GGA sentence of the epoch was parsed.
+/// * `rmc` - Whether an RMC sentence of the epoch was parsed.
+/// * `done` - Whether the reading of the epoch was emitted or dropped.
+/// * `pending` - The reading of the epoch, until it is complete.
+#[derive(Default)]
+struct Epoch {
+    time: Option<NaiveTime>,
+    gga: bool,
+    rmc: bool,
+    done: bool,
+    pending: Option<Reading>,
+}
+
+/// Decodes the byte stream of a GPS module into readings.
+///
+/// This is the read path of a [`Sensor`], free of hardware: NMEA sentences
+/// or UBX messages, depending on the configured [`Protocol`], are parsed as
+/// their bytes arrive, readings above the HDOP ceiling are dropped, positions
+/// are smoothed, and the serial link is accounted for in [`Stats`].
+///
+/// Modules send several position sentences per fix, usually a GGA and an RMC
+/// one, which make a single reading: it is emitted once both were parsed, or
+/// when the next fix starts for modules sending only one of them.
+pub struct Decoder {
+    protocol: Protocol,
+    smoothing: f64,
+    baud_rate: u32,
+    buffer: String,
+    parser: Nmea,
+    frames: ubx::Parser,
+    epoch: Epoch,
+    smoothed: Option<(f64, f64)>,
+    stats: Arc<Mutex<Stats>>,
+    window: Stats,
+    max_hdop: Arc<Mutex<f32>>,
+    satellites_logged_ms: u64,
 }
 
-impl Reading {
-    /// Creates a new `Reading` with the given position and optional speed.
+impl Decoder {
+    /// Creates a new `Decoder`.
     ///
     /// # Arguments
-    /// * `latitude` - Latitude in decimal degrees.
-    /// * `longitude` - Longitude in decimal degrees.
-    /// * `speed_mps` - Speed in meters per second, or `None` if unavailable.
+    /// * `config` - The configuration the protocol, smoothing and baud rate
+    ///   are taken from.
     ///
     /// # Returns
-    /// A new `Reading` instance.
+    /// A new `Decoder` with the [`DEFAULT_MAX_HDOP`] ceiling.
     #[must_use]
-    pub fn new(latitude: f64, longitude: f64, speed_mps: Option<f32>) -> Self {
+    pub fn new(config: &Config) -> Self {
         Self {
-            latitude,
-            longitude,
-            speed_mps,
+            protocol: config.protocol(),
+            smoothing: config.smoothing(),
+            baud_rate: config.baud_rate(),
+            buffer: String::new(),
+            parser: Nmea::default(),
+            frames: ubx::Parser::default(),
+            epoch: Epoch::default(),
+            smoothed: None,
+            stats: Arc::new(Mutex::new(Stats::default())),
+            window: Stats::default(),
+            max_hdop: Arc::new(Mutex::new(DEFAULT_MAX_HDOP)),
+            satellites_logged_ms: now_ms(),
         }
     }
 
-    /// Returns the latitude in decimal degrees.
+    /// Decodes received bytes.
+    ///
+    /// Incomplete sentences or messages are kept until the next call.
+    ///
+    /// # Arguments
+    /// * `bytes` - The received bytes.
     ///
     /// # Returns
-    /// The latitude as `f64`.
-    #[must_use]
-    pub fn latitude(&self) -> f64 {
-        self.latitude
+    /// The last valid reading the bytes completed, if any.
+    pub fn push(&mut self, bytes: &[u8]) -> Option<Reading> {
+        match self.protocol {
+            Protocol::Nmea => self.push_nmea(bytes),
+            Protocol::Ubx => self.push_ubx(bytes),
+        }
     }
 
-    /// Returns the longitude in decimal degrees.
+    /// Returns the serial link counters, shared with the decoder.
     ///
     /// # Returns
-    /// The longitude as `f64`.
+    /// A shared handle to the decoder's [`Stats`].
     #[must_use]
-    pub fn longitude(&self) -> f64 {
-        self.longitude
+    pub fn stats(&self) -> Arc<Mutex<Stats>> {
+        Arc::clone(&self.stats)
     }
 
-    /// Returns the speed in meters per second, if available.
+    /// Returns the HDOP above which readings are dropped, shared with the
+    /// decoder so that it can be changed while decoding.
+    ///
+    /// Dropped readings are not returned by [`Decoder::push`], and are
+    /// counted in [`Stats::hdop_rejections`]. Readings without an HDOP, e.g.
+    /// from RMC-only modules, are always kept. The ceiling is
+    /// [`DEFAULT_MAX_HDOP`] by default, and `f32::INFINITY` disables the
+    /// filter.
     ///
     /// # Returns
-    /// `Some(speed)` if the GPS fix includes speed data, `None` otherwise.
+    /// A shared handle to the HDOP ceiling.
     #[must_use]
-    pub fn speed_mps(&self) -> Option<f32> {
-        self.speed_mps
+    pub fn max_hdop(&self) -> Arc<Mutex<f32>> {
+        Arc::clone(&self.max_hdop)
     }
-}
 
-impl Display for Reading {
-    /// Formats the reading as `Lat: {lat}, Lon: {lon}, Speed: {speed} m/s` (or `N/A` if no speed).
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Lat: {}, Lon: {}, Speed: ",
-            self.latitude, self.longitude
-        )?;
-        match self.speed_mps {
-            Some(s) => write!(f, "{s:.2} m/s"),
-            None => write!(f, "N/A"),
+    /// Drops the bytes of incomplete sentences or messages, the pending
+    /// reading and the smoothed position, e.g. when the module is powered
+    /// down.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.frames.clear();
+        self.epoch = Epoch::default();
+        self.smoothed = None;
+    }
+
+    // Counts a line in the totals and in the current window, warning when too
+    // many lines of a full window failed.
+    fn record_line(&mut self, checksum_ok: bool, parse_ok: bool) {
+        let mut stats = infra::lock(&self.stats);
+
+        for counters in [&mut *stats, &mut self.window] {
+            counters.lines = counters.lines.saturating_add(1);
+            counters.checksum_failures = counters
+                .checksum_failures
+                .saturating_add(u32::from(!checksum_ok));
+            counters.parse_failures = counters
+                .parse_failures
+                .saturating_add(u32::from(checksum_ok && !parse_ok));
+        }
+
+        if self.window.lines >= STATS_WINDOW_LINES {
+            if self.window.failure_ratio() > STATS_MAX_FAILURE_RATIO {
+                warn!(
+                    "{:.0}% of the last {} GPS lines are invalid ({}), check that the baud rate ({}, see GPS_BAUD_RATE) matches the module",
+                    self.window.failure_ratio() * 100.0,
+                    self.window.lines,
+                    self.window,
+                    self.baud_rate
+                );
+            }
+            self.window = Stats::default();
+        }
+    }
+
+    // Refreshes the satellites in view from the parser, which only updates
+    // them once a GSV sequence is complete, and logs them every few minutes.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn update_satellites(&mut self) {
+        let mut satellites: Vec<_> = self
+            .parser
+            .satellites()
+            .iter()
+            .map(|satellite| SatelliteInView {
+                prn: satellite.prn(),
+                elevation_deg: satellite
+                    .elevation()
+                    .map(|deg| deg.clamp(0.0, 90.0) as u8),
+                azimuth_deg: satellite
+                    .azimuth()
+                    .map(|deg| deg.rem_euclid(360.0) as u16),
+                snr_db: satellite.snr().map(|snr| snr.clamp(0.0, 99.0) as u8),
+            })
+            .collect();
+        satellites.sort_by_key(|satellite| Reverse(satellite.snr_db));
+
+        let mut stats = infra::lock(&self.stats);
+        stats.satellites = [None; MAX_SATELLITES_IN_VIEW];
+        for (slot, satellite) in stats.satellites.iter_mut().zip(satellites) {
+            *slot = Some(satellite);
+        }
+
+        if now_ms().saturating_sub(self.satellites_logged_ms)
+            >= SATELLITE_LOG_INTERVAL_MS
+        {
+            let snrs = stats
+                .satellites()
+                .map(|satellite| match satellite.snr_db() {
+                    Some(snr) => format!("{}:{snr}", satellite.prn()),
+                    None => format!("{}:-", satellite.prn()),
+                })
+                .collect::<Vec<_>>();
+            debug!(
+                "GPS satellites in view: {}, average SNR {} dB-Hz [{}]",
+                snrs.len(),
+                stats
+                    .average_snr()
+                    .map_or_else(|| "-".to_string(), |snr| format!("{snr:.1}")),
+                snrs.join(" ")
+            );
+            self.satellites_logged_ms = now_ms();
+        }
+    }
+
+    // Checks whether the reading's HDOP is above the ceiling, given an HDOP.
+    fn exceeds_max_hdop(&self, reading: &Reading) -> bool {
+        reading
+            .hdop()
+            .is_some_and(|hdop| hdop > *infra::lock(&self.max_hdop))
+    }
+
+    // Counts a reading dropped for its HDOP.
+    fn count_hdop_rejection(&self, reading: &Reading) {
+        debug!(
+            "Dropping GPS reading with HDOP {:.1} (max {:.1})",
+            reading.hdop().unwrap_or_default(),
+            *infra::lock(&self.max_hdop)
+        );
+        let mut stats = infra::lock(&self.stats);
+        stats.hdop_rejections = stats.hdop_rejections.saturating_add(1);
+    }
+
+    // Drops the reading if its HDOP is too high, smoothing it otherwise.
+    fn accept(&mut self, reading: Reading) -> Option<Reading> {
+        if self.exceeds_max_hdop(&reading) {
+            self.count_hdop_rejection(&reading);
+            None
+        } else {
+            Some(self.smooth(reading))
+        }
+    }
+
+    // Adds a parsed position sentence to its epoch, returning the readings it
+    // completed: the one of the previous epoch if this sentence starts a new
+    // epoch before that one was complete, and the one of this epoch once its
+    // GGA and RMC sentences were both parsed. Sentences without a fix time
+    // make an epoch each.
+    fn push_position(&mut self, kind: SentenceType) -> Option<Reading> {
+        let time = self.parser.fix_time;
+        let mut ret = None;
+        if time.is_none() || time != self.epoch.time {
+            let previous = std::mem::replace(
+                &mut self.epoch,
+                Epoch {
+                    time,
+                    ..Epoch::default()
+                },
+            );
+            ret = previous.pending.and_then(|reading| self.accept(reading));
+        }
+        // Sentences repeated once the epoch is done are ignored
+        if !self.epoch.done {
+            self.epoch.gga |= kind == SentenceType::GGA;
+            self.epoch.rmc |= kind == SentenceType::RMC;
+            self.epoch.pending = fix_reading(&self.parser, self.epoch.rmc);
+            if self.epoch.pending.is_none() {
+                self.smoothed = None;
+            }
+            if time.is_none() || (self.epoch.gga && self.epoch.rmc) {
+                self.epoch.done = true;
+                if let Some(reading) = self.epoch.pending.take() {
+                    ret = self.accept(reading);
+                }
+            }
+        }
+
+        ret
+    }
+
+    // Moves the reading's position towards the measured one by the smoothing
+    // factor, starting over from the measured position when not smoothing.
+    fn smooth(&mut self, mut reading: Reading) -> Reading {
+        let alpha = self.smoothing;
+        if let (Some((lat, lon)), true) = (self.smoothed, alpha < 1.0) {
+            reading.latitude = lat + alpha * (reading.latitude - lat);
+            reading.longitude = lon + alpha * (reading.longitude - lon);
+        }
+        self.smoothed = Some((reading.latitude, reading.longitude));
+
+        reading
+    }
+
+    // Parses the NAV-PVT messages completed by the received bytes, returning
+    // the last valid reading. Other messages, e.g. acknowledgements, are
+    // ignored.
+    fn push_ubx(&mut self, bytes: &[u8]) -> Option<Reading> {
+        let mut ret = None;
+        for frame in self.frames.push(bytes) {
+            let decoded = frame
+                .as_ref()
+                .ok()
+                .filter(|frame| (frame.class(), frame.id()) == ubx::NAV_PVT)
+                .map(ubx::decode_nav_pvt);
+            self.record_line(frame.is_ok(), !matches!(decoded, Some(Err(_))));
+
+            match decoded {
+                Some(Ok(Some(reading))) => ret = self.accept(reading).or(ret),
+                Some(Ok(None)) => self.smoothed = None,
+                Some(Err(e)) => debug!("Invalid UBX-NAV-PVT message: {e:#}"),
+                None => (),
+            }
+        }
+
+        ret
+    }
+
+    // Parses the NMEA sentences completed by the received bytes, returning the
+    // last valid reading.
+    fn push_nmea(&mut self, bytes: &[u8]) -> Option<Reading> {
+        let mut ret = None;
+        if !bytes.is_empty() {
+            let s = String::from_utf8_lossy(bytes);
+            self.buffer.push_str(&s);
+
+            if let Some(last_idx) = self.buffer.rfind("\r\n") {
+                let range_end = last_idx + 2;
+
+                let complete = self.buffer[..range_end].to_string();
+                for line in complete.split("\r\n") {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    let checksum_ok = checksum_valid(line);
+                    // The parser outlives bursts so that the date, speed and
+                    // course from an RMC sentence still apply to GGA sentences
+                    let parsed = checksum_ok.then(|| self.parser.parse(line));
+                    // Valid sentences the parser does not handle are fine
+                    self.record_line(
+                        checksum_ok,
+                        match &parsed {
+                            Some(Ok(_)) => true,
+                            Some(Err(e)) => is_ignored_sentence(e),
+                            None => false,
+                        },
+                    );
+
+                    if matches!(parsed, Some(Ok(SentenceType::GSV))) {
+                        self.update_satellites();
+                    }
+                    if let Some(Ok(kind)) = parsed {
+                        if is_position_sentence(kind) {
+                            ret = self.push_position(kind).or(ret);
+                        }
+                    }
+                }
+
+                self.buffer.drain(..range_end);
+            }
+
+            if self.buffer.len() > 4096 {
+                self.buffer.clear();
+            }
         }
+
+        ret
     }
 }
 
@@ -93,23 +2330,43 @@ impl Display for Reading {
 /// # Type Parameters
 /// * `'a` - Lifetime of the sensor.
 /// * `T` - The trigger type implementing the `Trigger` trait.
-pub struct Sensor<'a, T: Trigger> {
+/// * `S` - The source of NMEA sentences, the GPS module's UART by default.
+#[cfg(feature = "hardware")]
+pub struct Sensor<'a, T: Trigger, S: NmeaSource = UartRxDriver<'a>> {
     notifier: Notifier<T>,
     trigger: &'static T,
     state: Arc<Mutex<State>>,
-    uart: UartRxDriver<'a>,
+    source: S,
+    tx: Option<UartTxDriver<'a>>,
+    config: Config,
     data: Arc<Mutex<Option<Reading>>>,
-    buffer: String,
+    decoder: Decoder,
+    fix_triggers: Option<(&'static T, &'static T, u64)>,
+    last_fix_ms: Option<u64>,
+    motion: Option<(&'static T, &'static T, MotionDetector)>,
+    error_triggers: Option<(&'static T, &'static T, u32)>,
+    read_failures: u32,
+    iterations: u64,
+    iterations_since_ms: u64,
+    started_ms: u64,
+    lost_at_ms: Option<u64>,
+    enable: Option<PinDriver<'a, AnyOutputPin, Output>>,
+    powered: bool,
 }
 
-impl<'a, T: Trigger> Sensor<'a, T> {
+#[cfg(feature = "hardware")]
+impl<'a, T: Trigger, S: NmeaSource> Sensor<'a, T, S> {
     /// Creates a new GPS `Sensor`.
     ///
     /// # Arguments
     /// * `notifier` - A notifier to send GPS data available events.
     /// * `trigger` - The trigger to emit when a new reading is available.
     /// * `state` - Shared on/off state controlling whether the sensor reads data.
-    /// * `uart` - UART receive driver connected to the GPS module.
+    /// * `source` - The NMEA source, usually the UART receive driver connected
+    ///   to the GPS module.
+    /// * `tx` - UART transmit driver connected to the GPS module, or `None` for
+    ///   receive-only wiring.
+    /// * `config` - The configuration `source` was built from.
     /// * `data` - Shared storage for the latest GPS reading.
     ///
     /// # Returns
@@ -118,91 +2375,601 @@ impl<'a, T: Trigger> Sensor<'a, T> {
         notifier: Notifier<T>,
         trigger: &'static T,
         state: Arc<Mutex<State>>,
-        uart: UartRxDriver<'a>,
+        source: S,
+        tx: Option<UartTxDriver<'a>>,
+        config: Config,
         data: Arc<Mutex<Option<Reading>>>,
     ) -> Self {
         Self {
             notifier,
             trigger,
             state,
-            uart,
+            source,
+            tx,
+            decoder: Decoder::new(&config),
+            config,
             data,
-            buffer: String::new(),
+            fix_triggers: None,
+            last_fix_ms: None,
+            motion: None,
+            error_triggers: None,
+            read_failures: 0,
+            iterations: 0,
+            iterations_since_ms: now_ms(),
+            started_ms: now_ms(),
+            lost_at_ms: None,
+            enable: None,
+            powered: true,
         }
     }
 
-    fn read(&mut self) -> Result<Option<Reading>> {
-        let mut ret = None;
+    /// Powers the GPS module through its enable pin, so that it is powered
+    /// down while the shared state is off rather than just ignored.
+    ///
+    /// The module is powered up right away. Each power-up restarts the time
+    /// to first fix measurement, see [`Sensor::time_to_first_fix`], and
+    /// configures the module again, see [`Sensor::configure_module`], as it
+    /// may have lost its configuration while powered down.
+    ///
+    /// # Arguments
+    /// * `enable` - Output driver of the pin wired to the module's enable
+    ///   input, or `None` to leave the module always powered.
+    ///
+    /// # Returns
+    /// The `Sensor` with power control enabled.
+    ///
+    /// # Errors
+    /// Returns an error if the pin cannot be driven high.
+    pub fn with_enable_pin(
+        mut self,
+        mut enable: Option<PinDriver<'a, AnyOutputPin, Output>>,
+    ) -> Result<Self> {
+        if let Some(enable) = enable.as_mut() {
+            enable.set_high()?;
+        }
+        self.enable = enable;
+        Ok(self)
+    }
+
+    /// Emits distinct triggers when the receiver starts and stops moving.
+    ///
+    /// # Arguments
+    /// * `started` - The trigger to emit when movement starts.
+    /// * `stopped` - The trigger to emit when movement stops.
+    /// * `detector` - The detector deciding when that happens, e.g.
+    ///   [`MotionDetector::default`].
+    ///
+    /// # Returns
+    /// The `Sensor` with motion detection enabled.
+    #[must_use]
+    pub fn with_motion_triggers(
+        mut self,
+        started: &'static T,
+        stopped: &'static T,
+        detector: MotionDetector,
+    ) -> Self {
+        self.motion = Some((started, stopped, detector));
+        self
+    }
+
+    /// Returns the HDOP above which readings are dropped, shared with the
+    /// sensor.
+    ///
+    /// Dropped readings are neither stored in the shared data nor notified,
+    /// see [`Decoder::max_hdop`]. The ceiling can be changed through the
+    /// handle once the sensor is polling, so the handle can be taken before
+    /// moving the sensor to its own thread.
+    ///
+    /// # Returns
+    /// A shared handle to the HDOP ceiling.
+    #[must_use]
+    pub fn max_hdop(&self) -> Arc<Mutex<f32>> {
+        self.decoder.max_hdop()
+    }
+
+    /// Emits distinct triggers when reads keep failing and when they recover,
+    /// instead of failing the poll loop.
+    ///
+    /// A loose wire would otherwise make [`Poller::poll`] fail, which restarts
+    /// the device over and over. Once `threshold` consecutive reads failed,
+    /// `failed` is emitted and reads back off exponentially, from 1 second up
+    /// to 30 seconds apart. `recovered` is emitted with the next successful
+    /// read.
+    ///
+    /// # Arguments
+    /// * `failed` - The trigger to emit when reads keep failing.
+    /// * `recovered` - The trigger to emit when reads succeed again.
+    /// * `threshold` - Number of consecutive failed reads before `failed` is
+    ///   emitted, e.g. [`DEFAULT_READ_FAILURE_THRESHOLD`].
+    ///
+    /// # Returns
+    /// The `Sensor` with read failures tolerated.
+    #[must_use]
+    pub fn with_error_triggers(
+        mut self,
+        failed: &'static T,
+        recovered: &'static T,
+        threshold: u32,
+    ) -> Self {
+        self.error_triggers = Some((failed, recovered, threshold.max(1)));
+        self
+    }
+
+    /// Emits distinct triggers when the fix is lost and when it is acquired.
+    ///
+    /// Once no reading was received for `timeout_ms`, the shared reading is
+    /// cleared so that stale positions are not reported, and `lost` is
+    /// emitted. `acquired` is emitted with the first reading after that, as
+    /// well as with the first reading after boot.
+    ///
+    /// # Arguments
+    /// * `lost` - The trigger to emit when the fix is lost.
+    /// * `acquired` - The trigger to emit when the fix is acquired.
+    /// * `timeout_ms` - How long without a reading before the fix is lost, in
+    ///   milliseconds, e.g. [`DEFAULT_FIX_TIMEOUT_MS`].
+    ///
+    /// # Returns
+    /// The `Sensor` with fix tracking enabled.
+    #[must_use]
+    pub fn with_fix_triggers(
+        mut self,
+        lost: &'static T,
+        acquired: &'static T,
+        timeout_ms: u64,
+    ) -> Self {
+        self.fix_triggers = Some((lost, acquired, timeout_ms));
+        self
+    }
+
+    /// Returns the serial link counters, shared with the sensor.
+    ///
+    /// The counters keep updating once the sensor is polling, so the handle
+    /// can be taken before moving the sensor to its own thread.
+    ///
+    /// # Returns
+    /// A shared handle to the sensor's [`Stats`].
+    #[must_use]
+    pub fn stats(&self) -> Arc<Mutex<Stats>> {
+        self.decoder.stats()
+    }
+
+    /// Returns how long the module took to get its first fix since polling
+    /// started, or since it was last powered up through its enable pin.
+    ///
+    /// # Returns
+    /// The time to first fix, or `None` until the first reading.
+    #[must_use]
+    pub fn time_to_first_fix(&self) -> Option<Duration> {
+        infra::lock(&self.decoder.stats)
+            .time_to_first_fix_ms
+            .map(Duration::from_millis)
+    }
+
+    /// Sends configuration commands to the GPS module, e.g. to change its
+    /// update rate or disable unused sentences.
+    ///
+    /// Each command is the body of an NMEA sentence, such as `PMTK220,200` or
+    /// u-blox's `PUBX,40,GSV,0,0,0,0,0,0`; the `$` prefix, checksum and line
+    /// ending are added. Does nothing when the sensor has no TX driver.
+    ///
+    /// # Arguments
+    /// * `commands` - The sentence bodies to send, in order.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if a command cannot be written to the UART.
+    pub fn configure(&mut self, commands: &[&str]) -> Result<()> {
+        let read_timeout = self.read_timeout();
+        if let Some(tx) = &mut self.tx {
+            for command in commands {
+                let body = command.strip_prefix('$').unwrap_or(command);
+                let sentence = format!("${body}*{:02X}\r\n", nmea_checksum(body));
+                info!("Sending GPS command: {}", sentence.trim_end());
+                tx.write_all(sentence.as_bytes())?;
+            }
+            tx.wait_done(read_timeout)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends UBX configuration messages to the GPS module, see
+    /// [`ubx::Frame::cfg_msg`] and its siblings.
+    ///
+    /// Does nothing when the sensor has no TX driver.
+    ///
+    /// # Arguments
+    /// * `frames` - The messages to send, in order.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if a message cannot be written to the UART.
+    pub fn configure_ubx(&mut self, frames: &[ubx::Frame]) -> Result<()> {
+        let read_timeout = self.read_timeout();
+        if let Some(tx) = &mut self.tx {
+            for frame in frames {
+                info!(
+                    "Sending UBX message class 0x{:02X}, id 0x{:02X}",
+                    frame.class(),
+                    frame.id()
+                );
+                tx.write_all(&frame.to_bytes())?;
+            }
+            tx.wait_done(read_timeout)?;
+        }
+
+        Ok(())
+    }
+
+    /// Configures the GPS module from the sensor's [`Config`]: only GGA and
+    /// RMC sentences are enabled, or only NAV-PVT messages with
+    /// [`Protocol::Ubx`], and the fix rate is set if configured. With debug
+    /// logs enabled, GSV sentences are also sent once every 5 fixes, so that
+    /// the satellites in view can be logged, see [`Stats::satellites`].
+    ///
+    /// With NMEA, both u-blox `PUBX,40` commands, as understood by e.g. the
+    /// NEO-6M and NEO-8M, and MediaTek `PMTK` commands, as understood by e.g.
+    /// the AT6668 and MT3339 based modules, are sent, each module ignoring the
+    /// other's. u-blox modules get their fix rate as a UBX-CFG-RATE message,
+    /// while MediaTek ones get it once polling starts, so that its
+    /// acknowledgment can be read, see [`Sensor::set_update_rate`]. With
+    /// [`Protocol::Ubx`], UBX-CFG messages are sent. Does nothing when the
+    /// sensor has no TX driver.
+    ///
+    /// # Returns
+    /// `Ok(())` on success, or when there is no TX driver.
+    ///
+    /// # Errors
+    /// Returns an error if a command cannot be written to the UART.
+    pub fn configure_module(&mut self) -> Result<()> {
+        match (self.tx.is_some(), self.config.protocol()) {
+            (false, _) => Ok(()),
+            (true, Protocol::Nmea) => {
+                // GSV sentences are only parsed for the satellite logs
+                let with_gsv = log_enabled!(Level::Debug);
+                self.configure(&PUBX_GGA_RMC_ONLY)?;
+                if with_gsv {
+                    self.configure(&[PUBX_GSV_EVERY_5_FIXES])?;
+                }
+                self.configure(&[if with_gsv {
+                    PMTK_GGA_RMC_GSV
+                } else {
+                    PMTK_GGA_RMC_ONLY
+                }])?;
+                if let Some(hz) = self.config.update_hz() {
+                    self.configure_ubx(&[ubx::Frame::cfg_rate(update_period_ms(
+                        hz,
+                    ))])?;
+                }
+
+                Ok(())
+            }
+            (true, Protocol::Ubx) => {
+                let mut frames = vec![
+                    ubx::Frame::cfg_prt_ubx_only(self.config.baud_rate()),
+                    ubx::Frame::cfg_msg(ubx::NAV_PVT, 1),
+                ];
+                frames.extend(
+                    self.config
+                        .update_hz()
+                        .map(|hz| ubx::Frame::cfg_rate(update_period_ms(hz))),
+                );
+
+                self.configure_ubx(&frames)
+            }
+        }
+    }
+
+    /// Sets the fix rate of the GPS module with a `PMTK220` command, and
+    /// waits for the module to acknowledge it.
+    ///
+    /// The sentences received while waiting, for at most one second, are
+    /// dropped. Called when polling starts if the [`Config`] has an update
+    /// rate, in which case a failure is only logged.
+    ///
+    /// # Arguments
+    /// * `update_hz` - The number of fixes per second, from 0.1 to 10.
+    ///
+    /// # Returns
+    /// `Ok(())` once the module acknowledged the new rate.
+    ///
+    /// # Errors
+    /// Returns an error if the rate is out of range, the sensor has no TX
+    /// driver, the command cannot be sent or read back, or the module rejects
+    /// it or does not answer in time, e.g. because it does not speak `PMTK`.
+    pub fn set_update_rate(&mut self, update_hz: f32) -> Result<()> {
+        ensure!(
+            (MIN_UPDATE_HZ..=MAX_UPDATE_HZ).contains(&update_hz),
+            "Unsupported GPS update rate {update_hz} Hz, expected {MIN_UPDATE_HZ} to {MAX_UPDATE_HZ}"
+        );
+        ensure!(
+            self.tx.is_some(),
+            "Setting the GPS update rate needs a TX pin"
+        );
+
+        self.configure(&[&format!("PMTK220,{}", update_period_ms(update_hz))])?;
+        self.wait_pmtk_ack(220)?;
+        info!("GPS update rate set to {update_hz} Hz");
+
+        Ok(())
+    }
+
+    // Reads the source until the module acknowledges the given PMTK command,
+    // failing when it rejects it or after the acknowledgment timeout.
+    fn wait_pmtk_ack(&mut self, command: u16) -> Result<()> {
+        let deadline_ms = now_ms() + PMTK_ACK_TIMEOUT_MS;
+        let mut received = String::new();
         let mut buf = [0u8; 256];
+        let mut flag = None;
 
-        let n = self.uart.read(&mut buf, READ_TIMEOUT)?;
-        if n > 0 {
-            let s = String::from_utf8_lossy(&buf[..n]);
-            self.buffer.push_str(&s);
+        while flag.is_none() && now_ms() < deadline_ms {
+            let n = self.source.read(&mut buf, self.read_timeout())?;
+            received.push_str(&String::from_utf8_lossy(&buf[..n]));
+            flag = received
+                .split("\r\n")
+                .find_map(|line| pmtk_ack_flag(line, command));
+            if let Some(last_idx) = received.rfind("\r\n") {
+                received.drain(..last_idx + 2);
+            }
+        }
 
-            if let Some(last_idx) = self.buffer.rfind("\r\n") {
-                let range_end = last_idx + 2;
+        match flag {
+            Some(3) => Ok(()),
+            Some(flag) => bail!(
+                "GPS module rejected PMTK{command}: {}",
+                match flag {
+                    0 => "invalid command",
+                    1 => "unsupported command",
+                    _ => "action failed",
+                }
+            ),
+            None => bail!(
+                "GPS module did not acknowledge PMTK{command} within {PMTK_ACK_TIMEOUT_MS} ms"
+            ),
+        }
+    }
 
-                let complete = &self.buffer[..range_end];
-                for line in complete.split("\r\n") {
-                    if line.trim().is_empty() {
-                        continue;
-                    }
+    // Records the time to first fix, or the outage duration when the fix comes
+    // back after being lost.
+    #[allow(clippy::cast_precision_loss)]
+    fn record_fix(stats: &Mutex<Stats>, started_ms: u64, lost_at_ms: Option<u64>) {
+        let now = now_ms();
+        let mut stats = infra::lock(stats);
+        match (stats.time_to_first_fix_ms, lost_at_ms) {
+            (None, _) => {
+                let ttff_ms = now.saturating_sub(started_ms);
+                info!("GPS time to first fix: {:.1} s", ttff_ms as f64 / 1000.0);
+                stats.time_to_first_fix_ms = Some(ttff_ms);
+            }
+            (Some(_), Some(lost_at_ms)) => {
+                let outage_ms = now.saturating_sub(lost_at_ms);
+                info!(
+                    "GPS fix reacquired after {:.1} s",
+                    outage_ms as f64 / 1000.0
+                );
+                stats.last_reacquisition_ms = Some(outage_ms);
+            }
+            (Some(_), None) => (),
+        }
+    }
 
-                    let mut parser = Nmea::default();
-                    if let Ok(SentenceType::RMC) = parser.parse(line) {
-                        if let (Some(lat), Some(lon)) =
-                            (parser.latitude(), parser.longitude())
-                        {
-                            let speed_mps = parser
-                                .speed_over_ground
-                                .map(|knots| knots * 0.514_444);
-                            ret = Some(Reading::new(lat, lon, speed_mps));
-                        }
-                    }
+    // Powers the module up or down when it has an enable pin. A powered down
+    // module has no fix, and the time to first fix restarts on power-up.
+    fn set_power(&mut self, on: bool) -> Result<()> {
+        match self.enable.as_mut() {
+            Some(enable) if on != self.powered => {
+                if on {
+                    enable.set_high()?;
+                    info!("GPS module powered up");
+                    self.started_ms = now_ms();
+                    infra::lock(&self.decoder.stats).time_to_first_fix_ms = None;
+                } else {
+                    enable.set_low()?;
+                    info!("GPS module powered down");
+                    *infra::lock(&self.data) = None;
+                    self.decoder.clear();
+                    self.last_fix_ms = None;
+                    self.lost_at_ms = None;
                 }
+                self.powered = on;
+                if on {
+                    self.reconfigure();
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
 
-                self.buffer.drain(..range_end);
+    // Sets the configured fix rate with NMEA, from the polling thread where
+    // the acknowledgment can be read. A failure is only logged.
+    fn apply_update_rate(&mut self) {
+        if let (Protocol::Nmea, Some(update_hz), true) = (
+            self.config.protocol(),
+            self.config.update_hz(),
+            self.tx.is_some(),
+        ) {
+            if let Err(e) = self.set_update_rate(update_hz) {
+                warn!("GPS update rate left unchanged: {e:#}");
+            }
+        }
+    }
+
+    // Configures the module again once powered up, since a module without
+    // backup power restarts with its default sentences and rate. A failure is
+    // only logged.
+    fn reconfigure(&mut self) {
+        if self.tx.is_some() {
+            sleep(POWER_UP_DELAY_MS);
+            if let Err(e) = self.configure_module() {
+                warn!("GPS module left unconfigured after power-up: {e:#}");
             }
+            self.apply_update_rate();
+        }
+    }
 
-            if self.buffer.len() > 4096 {
-                self.buffer.clear();
+    // Counts a failed read, backing off once failures reach the threshold, or
+    // fails when read errors are not tolerated.
+    fn handle_read_failure(&mut self, error: anyhow::Error) -> Result<()> {
+        match self.error_triggers {
+            Some((failed, _, threshold)) => {
+                self.read_failures = self.read_failures.saturating_add(1);
+                warn!(
+                    "GPS read failed ({} in a row): {error:#}",
+                    self.read_failures
+                );
+                if self.read_failures == threshold {
+                    self.notifier.notify(failed)?;
+                }
+                if self.read_failures >= threshold {
+                    let doublings = (self.read_failures - threshold).min(5);
+                    sleep(
+                        (READ_BACKOFF_INITIAL_MS << doublings)
+                            .min(READ_BACKOFF_MAX_MS),
+                    );
+                }
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    // Resets the failed reads, notifying the recovery once they had been
+    // reported.
+    fn handle_read_success(&mut self) -> Result<()> {
+        if let Some((_, recovered, threshold)) = self.error_triggers {
+            if self.read_failures >= threshold {
+                info!("GPS reads recovered");
+                self.notifier.notify(recovered)?;
             }
         }
+        self.read_failures = 0;
+        Ok(())
+    }
+
+    // Converts the configured read timeout to FreeRTOS ticks.
+    fn read_timeout(&self) -> u32 {
+        TickType::new_millis(u64::from(self.config.read_timeout_ms())).ticks()
+    }
+
+    // Counts a poll loop iteration, logging the loop rate once a minute.
+    #[allow(clippy::cast_precision_loss)]
+    fn count_iteration(&mut self) {
+        self.iterations += 1;
+        let elapsed_ms = now_ms().saturating_sub(self.iterations_since_ms);
+        if elapsed_ms >= LOOP_RATE_LOG_INTERVAL_MS {
+            debug!(
+                "GPS poll loop: {:.1} iterations/s",
+                self.iterations as f64 * 1000.0 / elapsed_ms as f64
+            );
+            self.iterations = 0;
+            self.iterations_since_ms = now_ms();
+        }
+    }
+
+    fn read(&mut self) -> Result<Option<Reading>> {
+        let mut buf = [0u8; 256];
+        let n = self.source.read(&mut buf, self.read_timeout())?;
 
-        Ok(ret)
+        Ok(self.decoder.push(&buf[..n]))
     }
 }
 
-impl<T: Trigger> Poller for Sensor<'_, T> {
-    /// Continuously reads NMEA sentences from the UART and publishes GPS readings.
+#[cfg(feature = "hardware")]
+impl<T: Trigger, S: NmeaSource> Poller for Sensor<'_, T, S> {
+    /// Continuously reads NMEA sentences or UBX messages from the source and
+    /// publishes GPS readings.
     ///
-    /// Skips reading when the shared state is off. When a valid RMC sentence is parsed,
+    /// Skips reading when the shared state is off, powering the module down
+    /// when it has an enable pin. When a valid GGA or RMC sentence is parsed,
     /// stores the reading in the shared data mutex and sends a notification.
+    /// When enabled, fix loss and acquisition are notified as well, see
+    /// [`Sensor::with_fix_triggers`], and so are movement start and stop, see
+    /// [`Sensor::with_motion_triggers`]. Read failures can be tolerated, see
+    /// [`Sensor::with_error_triggers`].
     ///
     /// # Errors
-    /// Returns an error if UART reading, mutex locking, or notification fails.
+    /// Returns an error if reading the source (unless tolerated), mutex
+    /// locking, or notification fails.
     fn poll(&mut self) -> Result<!> {
+        info!(
+            "Reading GPS on gpio{} at {} baud",
+            self.config.rx_pin(),
+            self.config.baud_rate()
+        );
+
+        self.apply_update_rate();
+        self.started_ms = now_ms();
+
+        // The read timeout paces the loop while on
         loop {
-            yield_now();
+            self.count_iteration();
 
-            if self
-                .state
-                .lock()
-                .map_err(|e| anyhow!("Mutex lock error: {:?}", e))?
-                .is_off()
-            {
+            let is_off = infra::lock(&self.state).is_off();
+            self.set_power(!is_off)?;
+            if is_off {
+                sleep(IDLE_POLL_MS);
                 continue;
             }
 
-            if let Some(reading) = self.read()? {
-                let mut data = self
-                    .data
-                    .lock()
-                    .map_err(|e| anyhow!("Mutex lock error: {:?}", e))?;
+            let reading = match self.read() {
+                Ok(reading) => {
+                    self.handle_read_success()?;
+                    reading
+                }
+                Err(e) => {
+                    self.handle_read_failure(e)?;
+                    continue;
+                }
+            };
+            let mut data = infra::lock(&self.data);
 
-                *data = Some(reading);
-                self.notifier.notify(self.trigger)?;
+            match (reading, self.fix_triggers, self.last_fix_ms) {
+                (Some(reading), fix_triggers, last_fix_ms) => {
+                    let motion = self.motion.as_mut().and_then(
+                        |(started, stopped, detector)| {
+                            detector.update(&reading).map(|moving| {
+                                if moving {
+                                    *started
+                                } else {
+                                    *stopped
+                                }
+                            })
+                        },
+                    );
+                    *data = Some(reading);
+                    self.notifier.notify(self.trigger)?;
+                    if let Some(trigger) = motion {
+                        self.notifier.notify(trigger)?;
+                    }
+                    if last_fix_ms.is_none() {
+                        Self::record_fix(
+                            &self.decoder.stats,
+                            self.started_ms,
+                            self.lost_at_ms.take(),
+                        );
+                    }
+                    if let (Some((_, acquired, _)), None) =
+                        (fix_triggers, last_fix_ms)
+                    {
+                        self.notifier.notify(acquired)?;
+                    }
+                    self.last_fix_ms = Some(now_ms());
+                }
+                (None, Some((lost, _, timeout_ms)), Some(last_fix_ms))
+                    if now_ms().saturating_sub(last_fix_ms) > timeout_ms =>
+                {
+                    *data = None;
+                    self.decoder.smoothed = None;
+                    self.last_fix_ms = None;
+                    self.lost_at_ms = Some(last_fix_ms);
+                    self.notifier.notify(lost)?;
+                }
+                _ => (),
             }
         }
     }
@@ -0,0 +1,385 @@
+use anyhow::{ensure, Result};
+use nmea::sentences::FixType;
+
+use super::{Reading, MPS_PER_KNOT};
+use crate::error::Error;
+
+/// The two sync characters starting every UBX frame.
+const SYNC: [u8; 2] = [0xB5, 0x62];
+
+/// Length of a frame header: sync characters, class, id and payload length.
+const HEADER_LEN: usize = 6;
+
+/// Length of the checksum ending every frame.
+const CHECKSUM_LEN: usize = 2;
+
+/// Longest payload accepted by [`Parser`], so that a corrupt length cannot
+/// make it buffer without bound.
+const MAX_PAYLOAD_LEN: usize = 1024;
+
+/// Class and id of the UBX-NAV-PVT message, the navigation solution.
+pub const NAV_PVT: (u8, u8) = (0x01, 0x07);
+
+/// Length of a UBX-NAV-PVT payload.
+const NAV_PVT_LEN: usize = 92;
+
+/// Class and id of the UBX-CFG-PRT message, configuring a port.
+const CFG_PRT: (u8, u8) = (0x06, 0x00);
+
+/// Class and id of the UBX-CFG-MSG message, setting a message rate.
+const CFG_MSG: (u8, u8) = (0x06, 0x01);
+
+/// Class and id of the UBX-CFG-RATE message, setting the navigation rate.
+const CFG_RATE: (u8, u8) = (0x06, 0x08);
+
+/// Id of the first UART in UBX-CFG-PRT.
+const UART1_PORT: u8 = 1;
+
+/// UART mode in UBX-CFG-PRT: 8 data bits, no parity, 1 stop bit.
+const UART_MODE_8N1: u32 = 0x0000_08D0;
+
+/// UBX and NMEA protocol bits in UBX-CFG-PRT masks.
+const PROTO_UBX: u16 = 0x0001;
+const PROTO_NMEA: u16 = 0x0002;
+
+/// Computes the 8-bit Fletcher checksum of a frame's class, id, length and
+/// payload.
+///
+/// # Arguments
+/// * `bytes` - The bytes between the sync characters and the checksum.
+///
+/// # Returns
+/// The two checksum bytes, in frame order.
+#[must_use]
+pub fn checksum(bytes: &[u8]) -> [u8; 2] {
+    bytes.iter().fold([0, 0], |[a, b], &byte| {
+        let a = a.wrapping_add(byte);
+        [a, b.wrapping_add(a)]
+    })
+}
+
+// Reads a little-endian field of a payload, which must be long enough.
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn i32_at(bytes: &[u8], at: usize) -> i32 {
+    i32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+// Returns the days from the Unix epoch to a civil date.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year =
+        (153 * ((i64::from(month) + 9) % 12) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+// Returns the UTC time of a NAV-PVT solution, when both its date and time are
+// valid.
+fn nav_pvt_utc_s(payload: &[u8]) -> Option<u64> {
+    let valid = payload[11] & 0x03 == 0x03;
+    let days =
+        days_from_civil(i64::from(u16_at(payload, 4)), payload[6], payload[7]);
+    let seconds = days * 86_400
+        + i64::from(payload[8]) * 3600
+        + i64::from(payload[9]) * 60
+        + i64::from(payload[10]);
+
+    valid.then(|| u64::try_from(seconds).ok()).flatten()
+}
+
+/// A UBX frame, as sent and received by u-blox GPS modules.
+///
+/// # Fields
+/// * `class` - The message class, e.g. 0x01 for navigation results.
+/// * `id` - The message id within its class.
+/// * `payload` - The message payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    class: u8,
+    id: u8,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Creates a new `Frame`.
+    ///
+    /// # Arguments
+    /// * `class` - The message class.
+    /// * `id` - The message id within its class.
+    /// * `payload` - The message payload, at most 1024 bytes.
+    ///
+    /// # Returns
+    /// A new `Frame` instance.
+    ///
+    /// # Errors
+    /// Returns an error if the payload is too long.
+    pub fn new(class: u8, id: u8, payload: Vec<u8>) -> Result<Self> {
+        ensure!(
+            payload.len() <= MAX_PAYLOAD_LEN,
+            "UBX payload too long: {} bytes, expected at most {MAX_PAYLOAD_LEN}",
+            payload.len()
+        );
+
+        Ok(Self { class, id, payload })
+    }
+
+    /// Builds a UBX-CFG-MSG message setting how often a message is output on
+    /// the port receiving it.
+    ///
+    /// # Arguments
+    /// * `message` - The class and id of the message, e.g. [`NAV_PVT`].
+    /// * `rate` - Output the message every `rate` navigation solutions, 0 to
+    ///   disable it.
+    ///
+    /// # Returns
+    /// The configuration `Frame`.
+    #[must_use]
+    pub fn cfg_msg(message: (u8, u8), rate: u8) -> Self {
+        Self {
+            class: CFG_MSG.0,
+            id: CFG_MSG.1,
+            payload: vec![message.0, message.1, rate],
+        }
+    }
+
+    /// Builds a UBX-CFG-PRT message making the first UART output UBX messages
+    /// only, which silences the NMEA sentences. Both protocols are still
+    /// accepted as input.
+    ///
+    /// # Arguments
+    /// * `baud_rate` - The UART baud rate, which must stay the current one.
+    ///
+    /// # Returns
+    /// The configuration `Frame`.
+    #[must_use]
+    pub fn cfg_prt_ubx_only(baud_rate: u32) -> Self {
+        let payload = [UART1_PORT, 0]
+            .into_iter()
+            .chain(0u16.to_le_bytes())
+            .chain(UART_MODE_8N1.to_le_bytes())
+            .chain(baud_rate.to_le_bytes())
+            .chain((PROTO_UBX | PROTO_NMEA).to_le_bytes())
+            .chain(PROTO_UBX.to_le_bytes())
+            .chain([0; 4])
+            .collect();
+
+        Self {
+            class: CFG_PRT.0,
+            id: CFG_PRT.1,
+            payload,
+        }
+    }
+
+    /// Builds a UBX-CFG-RATE message setting the navigation rate, aligned to
+    /// GPS time.
+    ///
+    /// # Arguments
+    /// * `period_ms` - The time between navigation solutions, in
+    ///   milliseconds, e.g. 200 for 5 Hz.
+    ///
+    /// # Returns
+    /// The configuration `Frame`.
+    #[must_use]
+    pub fn cfg_rate(period_ms: u16) -> Self {
+        let payload = period_ms
+            .to_le_bytes()
+            .into_iter()
+            .chain(1u16.to_le_bytes())
+            .chain(1u16.to_le_bytes())
+            .collect();
+
+        Self {
+            class: CFG_RATE.0,
+            id: CFG_RATE.1,
+            payload,
+        }
+    }
+
+    /// Returns the message class.
+    ///
+    /// # Returns
+    /// The class as `u8`.
+    #[must_use]
+    pub fn class(&self) -> u8 {
+        self.class
+    }
+
+    /// Returns the message id within its class.
+    ///
+    /// # Returns
+    /// The id as `u8`.
+    #[must_use]
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Returns the message payload.
+    ///
+    /// # Returns
+    /// The payload bytes.
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Encodes the frame, with its sync characters, length and checksum.
+    ///
+    /// # Returns
+    /// The bytes to send to the module.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = SYNC
+            .into_iter()
+            .chain([self.class, self.id])
+            .chain((self.payload.len() as u16).to_le_bytes())
+            .chain(self.payload.iter().copied())
+            .collect();
+        let checksum = checksum(&bytes[SYNC.len()..]);
+        bytes.extend(checksum);
+
+        bytes
+    }
+}
+
+/// Decodes a UBX-NAV-PVT message into a [`Reading`].
+///
+/// The reading has a position, speed, course of motion, fix type, satellite
+/// count, altitude above mean sea level and, when valid, UTC time. It has no
+/// HDOP, which NAV-PVT does not report.
+///
+/// # Arguments
+/// * `frame` - The NAV-PVT frame.
+///
+/// # Returns
+/// `Some(reading)` for a valid fix, or `None` when the module has no fix.
+///
+/// # Errors
+/// Returns [`Error::GpsParse`] if the frame is not a NAV-PVT message or is
+/// truncated.
+#[allow(clippy::cast_possible_truncation)]
+pub fn decode_nav_pvt(frame: &Frame) -> Result<Option<Reading>> {
+    ensure!(
+        (frame.class, frame.id) == NAV_PVT,
+        Error::GpsParse(format!(
+            "Not a UBX-NAV-PVT message: class 0x{:02X}, id 0x{:02X}",
+            frame.class, frame.id
+        ))
+    );
+    ensure!(
+        frame.payload.len() >= NAV_PVT_LEN,
+        Error::GpsParse(format!(
+            "Truncated UBX-NAV-PVT message: {} bytes, expected {NAV_PVT_LEN}",
+            frame.payload.len()
+        ))
+    );
+
+    let payload = &frame.payload;
+    let (fix_ok, differential) = (payload[21] & 0x01 != 0, payload[21] & 0x02 != 0);
+    let fix_type = match (payload[20], differential) {
+        (1, _) => Some(FixType::Estimated),
+        (2..=4, false) => Some(FixType::Gps),
+        (2..=4, true) => Some(FixType::DGps),
+        _ => None,
+    };
+
+    Ok(fix_type.filter(|_| fix_ok).map(|fix_type| {
+        let speed_mps = f64::from(i32_at(payload, 60)) / 1000.0;
+        Reading::new(
+            f64::from(i32_at(payload, 28)) * 1e-7,
+            f64::from(i32_at(payload, 24)) * 1e-7,
+        )
+        .with_motion(
+            Some(speed_mps as f32 / MPS_PER_KNOT),
+            Some((f64::from(i32_at(payload, 64)) * 1e-5) as f32),
+        )
+        .with_quality(Some(fix_type), Some(u32::from(payload[23])), None)
+        .with_altitude(Some((f64::from(i32_at(payload, 36)) / 1000.0) as f32))
+        .with_utc(nav_pvt_utc_s(payload))
+    }))
+}
+
+/// Splits a byte stream into UBX frames, checking their checksums.
+///
+/// Bytes that are not part of a frame, such as NMEA sentences sent before the
+/// module was configured, are skipped.
+#[derive(Debug, Default)]
+pub struct Parser {
+    buffer: Vec<u8>,
+}
+
+impl Parser {
+    /// Adds received bytes and extracts the frames they complete.
+    ///
+    /// Incomplete frames are kept until the next call.
+    ///
+    /// # Arguments
+    /// * `bytes` - The received bytes.
+    ///
+    /// # Returns
+    /// The completed frames in order, with an [`Error::GpsParse`] for each
+    /// corrupt one.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Result<Frame>> {
+        self.buffer.extend_from_slice(bytes);
+        let mut frames = Vec::new();
+
+        while let Some(start) =
+            self.buffer.windows(SYNC.len()).position(|w| w == SYNC)
+        {
+            self.buffer.drain(..start);
+            if self.buffer.len() < HEADER_LEN {
+                break;
+            }
+
+            let len = usize::from(u16_at(&self.buffer, 4));
+            let end = HEADER_LEN + len;
+            if len > MAX_PAYLOAD_LEN {
+                frames.push(Err(Error::GpsParse(format!(
+                    "Invalid UBX payload length {len}"
+                ))
+                .into()));
+                self.buffer.drain(..SYNC.len());
+            } else if self.buffer.len() < end + CHECKSUM_LEN {
+                break;
+            } else if checksum(&self.buffer[SYNC.len()..end])[..]
+                == self.buffer[end..end + CHECKSUM_LEN]
+            {
+                frames.push(Ok(Frame {
+                    class: self.buffer[2],
+                    id: self.buffer[3],
+                    payload: self.buffer[HEADER_LEN..end].to_vec(),
+                }));
+                self.buffer.drain(..end + CHECKSUM_LEN);
+            } else {
+                frames.push(Err(Error::GpsParse(format!(
+                    "UBX checksum mismatch for class 0x{:02X}, id 0x{:02X}",
+                    self.buffer[2], self.buffer[3]
+                ))
+                .into()));
+                self.buffer.drain(..SYNC.len());
+            }
+        }
+
+        // Without a sync sequence, only a trailing first sync character may
+        // still start a frame
+        if !self.buffer.starts_with(&SYNC) {
+            let keep = usize::from(self.buffer.last() == Some(&SYNC[0]));
+            self.buffer.drain(..self.buffer.len() - keep);
+        }
+
+        frames
+    }
+
+    /// Drops the bytes of an incomplete frame, e.g. when the module is
+    /// powered down.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+}
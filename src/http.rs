@@ -1,8 +1,54 @@
-use anyhow::{ensure, Result};
-use embedded_svc::{http::client::Client as HttpClient, io::Write};
+use anyhow::{anyhow, Result as AnyResult};
+use embedded_svc::{
+    http::{client::Client as HttpClient, Headers},
+    io::{Read, Write},
+};
 use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
+use std::time::Duration;
+#[cfg(feature = "wifi")]
+use {
+    embedded_svc::http::Method,
+    esp_idf_svc::http::server::{
+        Configuration as ServerConfiguration, EspHttpServer,
+    },
+    std::{
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    },
+};
 
-use crate::wifi::Connection;
+use crate::{
+    error::{Error, Result},
+    wifi::Connection,
+};
+#[cfg(feature = "wifi")]
+use crate::{
+    infra::{self, State},
+    message::{Notifier, Trigger},
+};
+
+/// Size of the chunks [`Client::post_stream`] reads from its body and sends.
+const STREAM_CHUNK_LEN: usize = 1024;
+
+/// Default size limit of the response bodies read by [`Client::get`].
+pub const DEFAULT_MAX_BODY_LEN: usize = 8 * 1024;
+
+/// Path of the endpoint returning the current state, see [`Server`].
+#[cfg(feature = "wifi")]
+pub const STATE_PATH: &str = "/state";
+
+/// Path of the endpoint toggling the device on and off, see [`Server`].
+#[cfg(feature = "wifi")]
+pub const TOGGLE_PATH: &str = "/toggle";
+
+/// Fails with [`Error::HttpStatus`] unless `status` is in the success range.
+fn check_status(status: u16) -> Result<u16> {
+    if (200..300).contains(&status) {
+        Ok(status)
+    } else {
+        Err(Error::HttpStatus(status))
+    }
+}
 
 /// Represents an HTTP client that interacts with a server over Wi-Fi.
 ///
@@ -11,6 +57,7 @@ use crate::wifi::Connection;
 pub struct Client<'a> {
     client: HttpClient<EspHttpConnection>,
     wifi: Connection<'a>,
+    max_body_len: usize,
 }
 
 impl<'a> Client<'a> {
@@ -28,9 +75,81 @@ impl<'a> Client<'a> {
     ///
     /// Returns an error if the HTTP client cannot be initialized.
     pub fn new(wifi: Connection<'a>) -> Result<Self> {
-        let client =
-            HttpClient::wrap(EspHttpConnection::new(&Configuration::default())?);
-        Ok(Self { client, wifi })
+        Ok(Self {
+            client: Self::connect(None)?,
+            wifi,
+            max_body_len: DEFAULT_MAX_BODY_LEN,
+        })
+    }
+
+    /// Sets the size limit of the response bodies read by [`Client::get`].
+    ///
+    /// # Arguments
+    ///
+    /// * `max_body_len` - The limit in bytes, [`DEFAULT_MAX_BODY_LEN`] by default.
+    ///
+    /// # Returns
+    ///
+    /// The updated `Client`.
+    #[must_use]
+    pub fn with_max_body_len(mut self, max_body_len: usize) -> Self {
+        self.max_body_len = max_body_len;
+        self
+    }
+
+    /// Sets how long requests wait for the network before failing.
+    ///
+    /// Without a timeout, ESP-IDF gives up after 5 seconds. Requests timing
+    /// out return an error, so that callers can retry instead of blocking.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The request timeout, zero keeps the ESP-IDF default.
+    ///
+    /// # Returns
+    ///
+    /// The updated `Client`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be initialized.
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<Self> {
+        self.client = Self::connect((!timeout.is_zero()).then_some(timeout))?;
+        Ok(self)
+    }
+
+    /// Creates the underlying HTTP connection.
+    fn connect(timeout: Option<Duration>) -> Result<HttpClient<EspHttpConnection>> {
+        Ok(HttpClient::wrap(EspHttpConnection::new(&Configuration {
+            timeout,
+            ..Default::default()
+        })?))
+    }
+
+    /// Checks if the underlying Wi-Fi connection is currently up.
+    ///
+    /// # Returns
+    ///
+    /// `true` if Wi-Fi is connected, `false` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if checking the Wi-Fi state fails.
+    pub fn is_connected(&self) -> Result<bool> {
+        Ok(self.wifi.is_on()?)
+    }
+
+    /// Fails with [`Error::WifiDown`] unless Wi-Fi is connected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Wi-Fi is not connected or checking its state fails.
+    fn ensure_connected(&self) -> Result<()> {
+        if self.is_connected()? {
+            Ok(())
+        } else {
+            Err(Error::WifiDown)
+        }
     }
 
     /// Sends a POST request to the specified URL with an optional payload.
@@ -46,14 +165,94 @@ impl<'a> Client<'a> {
     ///
     /// # Errors
     ///
-    /// Returns an error if the Wi-Fi is not connected, the request fails, or the response status is not in the success range.
+    /// Returns [`Error::WifiDown`] if the Wi-Fi is not connected, [`Error::HttpStatus`] if the
+    /// response status is not in the success range, or another error if the request fails.
     pub fn post(&mut self, url: &str, payload: Option<&[u8]>) -> Result<u16> {
-        ensure!(self.wifi.is_on()?, "WIFI is off");
+        self.send(url, "text/plain", payload.unwrap_or(b""))
+    }
+
+    /// Sends a POST request with a JSON body to the specified URL.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to send the POST request to.
+    /// * `json` - The JSON document to send, e.g. from
+    ///   [`Reading::to_json`](crate::gps::Reading::to_json).
+    ///
+    /// # Returns
+    ///
+    /// The HTTP status code of the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WifiDown`] if the Wi-Fi is not connected, [`Error::HttpStatus`] if the
+    /// response status is not in the success range, or another error if the request fails.
+    pub fn post_json(&mut self, url: &str, json: &str) -> Result<u16> {
+        self.send(url, "application/json", json.as_bytes())
+    }
+
+    /// Sends a POST request whose body is streamed from a reader.
+    ///
+    /// The body is sent with chunked transfer encoding, one chunk per read,
+    /// so it is never buffered as a whole. This suits large payloads such as
+    /// a batch of readings; small ones are simpler to send with
+    /// [`Client::post`].
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to send the POST request to.
+    /// * `body` - The reader the body is read from until it is exhausted.
+    ///
+    /// # Returns
+    ///
+    /// The HTTP status code of the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WifiDown`] if the Wi-Fi is not connected, [`Error::HttpStatus`] if the
+    /// response status is not in the success range, or another error if the request or reading
+    /// `body` fails.
+    pub fn post_stream(
+        &mut self,
+        url: &str,
+        mut body: impl std::io::Read,
+    ) -> Result<u16> {
+        self.ensure_connected()?;
+
+        let headers = [
+            ("content-type", "application/octet-stream"),
+            ("transfer-encoding", "chunked"),
+        ];
+        let mut request = self.client.post(url, &headers)?;
+
+        // The empty chunk read at the end of the body terminates the request
+        let mut buf = [0u8; STREAM_CHUNK_LEN];
+        loop {
+            let n = body.read(&mut buf).map_err(anyhow::Error::from)?;
+            request.write_all(format!("{n:X}\r\n").as_bytes())?;
+            request.write_all(&buf[..n])?;
+            request.write_all(b"\r\n")?;
+            if n == 0 {
+                break;
+            }
+        }
+        request.flush()?;
+
+        check_status(request.submit()?.status())
+    }
+
+    /// Sends a POST request with the given content type and body.
+    fn send(
+        &mut self,
+        url: &str,
+        content_type: &str,
+        payload: &[u8],
+    ) -> Result<u16> {
+        self.ensure_connected()?;
 
-        let payload = payload.unwrap_or(b"");
         let content_length_header = format!("{}", payload.len());
         let headers = [
-            ("content-type", "text/plain"),
+            ("content-type", content_type),
             ("content-length", &*content_length_header),
         ];
 
@@ -61,14 +260,207 @@ impl<'a> Client<'a> {
         request.write_all(payload)?;
         request.flush()?;
 
-        let response = request.submit()?;
+        check_status(request.submit()?.status())
+    }
+
+    /// Sends a GET request and reads the whole response body, e.g. to fetch a
+    /// configuration at boot.
+    ///
+    /// Unlike the other requests, a status outside the success range is not
+    /// an error, so that the caller can inspect the response.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to send the GET request to.
+    ///
+    /// # Returns
+    ///
+    /// The HTTP status code and the body of the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WifiDown`] if the Wi-Fi is not connected, or another error if the request
+    /// fails or the body exceeds the limit set with [`Client::with_max_body_len`].
+    pub fn get(&mut self, url: &str) -> Result<(u16, Vec<u8>)> {
+        self.ensure_connected()?;
+
+        let mut response = self.client.get(url)?.submit()?;
         let status = response.status();
-        ensure!(
-            (200..300).contains(&status),
-            "Request failed with status: {}",
-            status
-        );
 
-        Ok(status)
+        let mut body = Vec::new();
+        let mut buf = [0u8; 1024];
+        let mut n = response.read(&mut buf)?;
+        while n > 0 && body.len() + n <= self.max_body_len {
+            body.extend_from_slice(&buf[..n]);
+            n = response.read(&mut buf)?;
+        }
+
+        if n == 0 {
+            Ok((status, body))
+        } else {
+            Err(anyhow!("Response body exceeds {} bytes", self.max_body_len).into())
+        }
+    }
+
+    /// Sends a GET request and reads the whole response body as text, see
+    /// [`Client::get`].
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to send the GET request to.
+    ///
+    /// # Returns
+    ///
+    /// The HTTP status code and the body of the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Client::get`], or an error if the body is not valid UTF-8.
+    pub fn get_string(&mut self, url: &str) -> Result<(u16, String)> {
+        let (status, body) = self.get(url)?;
+        let body = String::from_utf8(body).map_err(anyhow::Error::from)?;
+
+        Ok((status, body))
+    }
+
+    /// Downloads the body of a GET request, streaming it chunk by chunk.
+    ///
+    /// The body is never buffered as a whole, so this is suitable for large
+    /// payloads such as firmware images.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to send the GET request to.
+    /// * `sink` - A callback receiving each chunk of the body in order.
+    ///
+    /// # Returns
+    ///
+    /// The total number of body bytes received.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WifiDown`] if the Wi-Fi is not connected, [`Error::HttpStatus`] if the
+    /// response status is not in the success range, or another error if the request fails, the
+    /// body is shorter than its `content-length`, or `sink` fails.
+    pub fn download(
+        &mut self,
+        url: &str,
+        mut sink: impl FnMut(&[u8]) -> AnyResult<()>,
+    ) -> Result<usize> {
+        self.ensure_connected()?;
+
+        let mut response = self.client.get(url)?.submit()?;
+        check_status(response.status())?;
+        let expected = response.content_len();
+
+        let mut buf = [0u8; 1024];
+        let mut total = 0;
+        loop {
+            let n = response.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            sink(&buf[..n])?;
+            total += n;
+        }
+
+        if expected.is_none_or(|len| u64::try_from(total).is_ok_and(|n| n == len)) {
+            Ok(total)
+        } else {
+            Err(anyhow!(
+                "Truncated download: received {} of {:?} bytes",
+                total,
+                expected
+            )
+            .into())
+        }
+    }
+}
+
+/// Represents a minimal HTTP server for controlling the device from a browser.
+///
+/// The server listens on port 80 of the Wi-Fi interface, which must be
+/// connected for it to be reachable, and serves:
+/// * `GET /state` - The current state, formatted with `Debug`.
+/// * `POST /toggle` - Toggles the shared power state and emits a trigger,
+///   exactly like a press on the power button, so that web commands flow
+///   through the same state machine as physical ones.
+///
+/// Additional handlers can be registered through [`Server::server_mut`].
+#[cfg(feature = "wifi")]
+pub struct Server<'a> {
+    server: EspHttpServer<'a>,
+}
+
+#[cfg(feature = "wifi")]
+impl<'a> Server<'a> {
+    /// Creates a new `Server` instance and starts serving.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The state returned by `GET /state`, kept up to date by the state machine.
+    /// * `power` - The power state toggled by `POST /toggle`, shared with the power button.
+    /// * `notifier` - A notifier from the state machine's dispatcher.
+    /// * `trigger` - The trigger to emit on `POST /toggle`, usually the button press trigger.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `S` - The type of the reported state.
+    /// * `P` - The type of the `On` payload of the power state.
+    /// * `T` - The trigger type implementing the `Trigger` trait.
+    ///
+    /// # Returns
+    ///
+    /// A new `Server` handling requests in the background.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot be started or a handler cannot be registered.
+    pub fn new<S, P, T>(
+        status: Arc<Mutex<S>>,
+        power: Arc<Mutex<State<P>>>,
+        notifier: Notifier<T>,
+        trigger: &'static T,
+    ) -> Result<Self>
+    where
+        S: Debug + Send + 'static,
+        P: Send + 'static,
+        T: Trigger,
+    {
+        let mut server = EspHttpServer::new(&ServerConfiguration::default())?;
+
+        server.fn_handler(
+            STATE_PATH,
+            Method::Get,
+            move |request| -> AnyResult<()> {
+                let body = format!("{:?}", *infra::lock(&status));
+                request
+                    .into_response(200, None, &[("content-type", "text/plain")])?
+                    .write_all(body.as_bytes())?;
+                Ok(())
+            },
+        )?;
+
+        server.fn_handler(
+            TOGGLE_PATH,
+            Method::Post,
+            move |request| -> AnyResult<()> {
+                infra::lock(&power).toggle();
+                notifier.notify(trigger)?;
+                request.into_ok_response()?;
+                Ok(())
+            },
+        )?;
+
+        Ok(Self { server })
+    }
+
+    /// Returns the underlying server, to register additional handlers.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the wrapped `EspHttpServer`.
+    pub fn server_mut(&mut self) -> &mut EspHttpServer<'a> {
+        &mut self.server
     }
 }
@@ -1,4 +1,27 @@
 use anyhow::Result;
+use log::warn;
+use std::sync::{Mutex, MutexGuard};
+
+/// Locks a mutex, recovering it if a thread panicked while holding it.
+///
+/// The mutexes shared between threads hold plain values, such as on/off
+/// states and latest readings, which are always replaced in a single
+/// assignment. A panicking thread cannot leave them half-updated, so the value
+/// is still consistent and recovering it beats restarting the device. The
+/// poison is cleared, so that the warning is only logged once.
+///
+/// # Arguments
+/// * `mutex` - The mutex to lock.
+///
+/// # Returns
+/// The guard of the locked mutex.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| {
+        warn!("Recovering mutex poisoned by a panicked thread");
+        mutex.clear_poison();
+        e.into_inner()
+    })
+}
 
 /// A trait representing a poller that performs periodic tasks.
 ///
@@ -20,6 +43,7 @@ pub trait Poller {
 /// # Variants
 /// * `Off` - The switch is turned off.
 /// * `On(Option<T>)` - The switch is on, optionally with additional data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum State<T = ()> {
     Off,
     On(Option<T>),
@@ -71,8 +95,17 @@ impl<T> State<T> {
 /// A trait representing a switch that can toggle its state.
 ///
 /// # Errors
-/// This trait's `toggle` method returns an error if the toggle operation fails.
+/// This trait's methods return an error if the state cannot be read or changed.
 pub trait Switch {
+    /// Returns the current state of the switch.
+    ///
+    /// # Returns
+    /// The current `State`.
+    ///
+    /// # Errors
+    /// Returns an error if the state cannot be read.
+    fn state(&self) -> Result<State>;
+
     /// Toggles the state of the switch.
     ///
     /// # Returns
@@ -81,4 +114,23 @@ pub trait Switch {
     /// # Errors
     /// Returns an error if the toggle operation fails.
     fn toggle(&mut self) -> Result<()>;
+
+    /// Sets the switch to the given state, toggling it only if it differs
+    /// from the current one.
+    ///
+    /// # Arguments
+    /// * `state` - The state to switch to.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the state cannot be read or toggled.
+    fn set(&mut self, state: State) -> Result<()> {
+        if self.state()?.is_on() != state.is_on() {
+            self.toggle()?;
+        }
+
+        Ok(())
+    }
 }
@@ -2,28 +2,58 @@
 
 //! ESP32 embedded development library providing BLE, Wi-Fi, HTTP, GPS, LED,
 //! button, and timer functionality for the ESP-IDF framework.
+//!
+//! Without the default `hardware` feature, only the modules free of ESP-IDF
+//! code are built (`batch`, `ble`, `clock`, `color`, `error`, `gps`, `infra`,
+//! `light`, `logic`, `message`, `protocol`, `testing` and `time`), `ble`,
+//! `clock`, `gps` and `light` without their hardware drivers, so that they can
+//! be compiled and exercised on the host.
 
+/// Bounded queue batching items for periodic uploads.
+pub mod batch;
+/// Battery voltage monitoring via the ADC.
+#[cfg(feature = "hardware")]
+pub mod battery;
 /// Bluetooth Low Energy advertising and scanning.
 pub mod ble;
-/// Physical button input handling with polling-based debounce.
+/// Physical or touch button input handling with debounce, polled or interrupt-driven.
+#[cfg(feature = "hardware")]
 pub mod button;
 /// Hardware timer management and interrupt configuration.
 pub mod clock;
 /// RGB color representation and predefined color constants.
 pub mod color;
-/// GPS sensor reading via UART and NMEA parsing.
+/// In-memory diagnostic event log, queryable over HTTP.
+#[cfg(feature = "hardware")]
+pub mod diag;
+/// Typed errors for failure modes callers need to tell apart.
+pub mod error;
+/// GPS sensor reading via UART or a replayed recording, and NMEA or UBX parsing.
 pub mod gps;
-/// HTTP client for sending requests over Wi-Fi.
+/// HTTP client and server over Wi-Fi.
+#[cfg(feature = "hardware")]
 pub mod http;
 /// Core infrastructure traits and types: [`infra::Poller`], [`infra::Switch`], and [`infra::State`].
 pub mod infra;
 /// `NeoPixel` LED control via the RMT peripheral.
 pub mod light;
+/// State machine shared by the example firmwares, free of hardware.
+pub mod logic;
 /// Inter-thread messaging with triggers, notifiers, and dispatchers.
 pub mod message;
-/// Thread spawning with automatic device restart on failure.
+/// Over-the-air firmware updates downloaded over HTTP.
+#[cfg(feature = "hardware")]
+pub mod ota;
+/// BLE advertised name format and payload framing.
+pub mod protocol;
+/// Test doubles standing in for hardware drivers off-device.
+#[cfg(feature = "testing")]
+pub mod testing;
+/// Thread spawning with device restart or fault reporting on failure.
+#[cfg(feature = "hardware")]
 pub mod thread;
-/// Time utilities for sleeping and cooperative yielding.
+/// Time utilities for sleeping, cooperative yielding, and timestamps.
 pub mod time;
 /// Wi-Fi connection management and configuration.
+#[cfg(feature = "hardware")]
 pub mod wifi;
@@ -1,114 +1,213 @@
 use anyhow::Result;
+#[cfg(feature = "hardware")]
 use esp_idf_hal::rmt::{FixedLengthSignal, PinState, Pulse, TxRmtDriver};
+use log::{debug, warn};
+#[cfg(feature = "hardware")]
 use std::time::Duration;
 
 use crate::{
-    color::{Rgb, BLACK},
+    color::{ChannelOrder, Gamma, Rgb, Rgbw, BLACK},
     infra::{State, Switch},
 };
 
-/// Sends an RGB color value to a `NeoPixel` LED using the RMT peripheral.
+/// Number of attempts at transmitting a color word before giving up.
+const SEND_ATTEMPTS: u32 = 3;
+
+/// The kind of `NeoPixel` LED, which determines the length of the color word.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LedKind {
+    /// Three channels, sent as a 24-bit word (e.g. WS2812).
+    #[default]
+    Rgb,
+    /// Three color channels and a white one, sent as a 32-bit word (e.g. SK6812 RGBW).
+    Rgbw,
+}
+
+/// A trait representing the transmit side of a `NeoPixel` LED.
 ///
-/// # Arguments
+/// Implemented by `TxRmtDriver` for hardware and by `testing::RecordingSink`
+/// (behind the `testing` feature) so that LED logic can run off-device.
+pub trait PixelSink {
+    /// Transmits a color word, packed in the LED's channel order.
+    ///
+    /// # Arguments
+    /// * `color` - The color word, most significant bit first.
+    /// * `kind` - The kind of LED, 24 bits of `color` are sent for [`LedKind::Rgb`]
+    ///   and 32 for [`LedKind::Rgbw`].
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the word cannot be transmitted.
+    fn send(&mut self, color: u32, kind: LedKind) -> Result<()>;
+}
+
+#[cfg(feature = "hardware")]
+impl PixelSink for TxRmtDriver<'_> {
+    /// Sends a color word to a `NeoPixel` LED using the RMT peripheral.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    ///
+    /// * There is an issue with the RMT driver, such as failing to retrieve the counter clock frequency.
+    /// * There is an issue creating the pulses with the specified durations.
+    /// * There is an issue setting the signal pulses.
+    /// * There is an issue starting the transmission.
+    fn send(&mut self, color: u32, kind: LedKind) -> Result<()> {
+        let ticks_hz = self.counter_clock()?;
+        let pulses = (
+            Pulse::new_with_duration(
+                ticks_hz,
+                PinState::High,
+                &Duration::from_nanos(350),
+            )?,
+            Pulse::new_with_duration(
+                ticks_hz,
+                PinState::Low,
+                &Duration::from_nanos(800),
+            )?,
+            Pulse::new_with_duration(
+                ticks_hz,
+                PinState::High,
+                &Duration::from_nanos(700),
+            )?,
+            Pulse::new_with_duration(
+                ticks_hz,
+                PinState::Low,
+                &Duration::from_nanos(600),
+            )?,
+        );
+        match kind {
+            LedKind::Rgb => self.start_blocking(&encode::<24>(color, pulses)?)?,
+            LedKind::Rgbw => self.start_blocking(&encode::<32>(color, pulses)?)?,
+        }
+        Ok(())
+    }
+}
+
+/// Encodes the `N` low bits of a color word into an RMT signal, most
+/// significant bit first.
 ///
-/// * `rgb` - An `Rgb` struct containing the red, green, and blue color values.
-/// * `tx` - A mutable reference to a `TxRmtDriver` used to transmit the signal.
+/// # Arguments
+/// * `color` - The color word to encode.
+/// * `pulses` - The high and low pulses of a `0` bit, then of a `1` bit.
 ///
 /// # Returns
-///
-/// * `Result<()>` - Returns `Ok(())` if the operation was successful, or an `anyhow::Error` if an error occurred.
+/// The signal to transmit.
 ///
 /// # Errors
-///
-/// This function will return an error if:
-///
-/// * There is an issue with the RMT driver, such as failing to retrieve the counter clock frequency.
-/// * There is an issue creating the pulses with the specified durations.
-/// * There is an issue setting the signal pulses.
-/// * There is an issue starting the transmission.
-fn neopixel(rgb: &Rgb, tx: &mut TxRmtDriver) -> Result<()> {
-    let color: u32 = rgb.into();
-    let ticks_hz = tx.counter_clock()?;
-    let (t0_high, t0_low, t1_high, t1_low) = (
-        Pulse::new_with_duration(
-            ticks_hz,
-            PinState::High,
-            &Duration::from_nanos(350),
-        )?,
-        Pulse::new_with_duration(
-            ticks_hz,
-            PinState::Low,
-            &Duration::from_nanos(800),
-        )?,
-        Pulse::new_with_duration(
-            ticks_hz,
-            PinState::High,
-            &Duration::from_nanos(700),
-        )?,
-        Pulse::new_with_duration(
-            ticks_hz,
-            PinState::Low,
-            &Duration::from_nanos(600),
-        )?,
-    );
-    let mut signal = FixedLengthSignal::<24>::new();
-    for i in (0..24).rev() {
-        let p = 2_u32.pow(i);
-        let bit: bool = p & color != 0;
+/// Returns an error if the signal pulses cannot be set.
+#[cfg(feature = "hardware")]
+fn encode<const N: usize>(
+    color: u32,
+    (t0_high, t0_low, t1_high, t1_low): (Pulse, Pulse, Pulse, Pulse),
+) -> Result<FixedLengthSignal<N>> {
+    let mut signal = FixedLengthSignal::<N>::new();
+    for i in 0..N {
+        let bit: bool = (color >> (N - 1 - i)) & 1 != 0;
         let (high_pulse, low_pulse) = if bit {
             (t1_high, t1_low)
         } else {
             (t0_high, t0_low)
         };
-        signal.set(23 - i as usize, &(high_pulse, low_pulse))?;
+        signal.set(i, &(high_pulse, low_pulse))?;
     }
-    tx.start_blocking(&signal)?;
-    Ok(())
+    Ok(signal)
 }
 
 /// Represents an LED with color and state control.
 ///
+/// Failed transmissions are retried, as RMT glitches are usually transient.
+/// A color that still cannot be sent is logged rather than returned as an
+/// error, and stays applied internally, so the next successful transmission
+/// displays the current color and state.
+///
 /// # Type Parameters
-/// * `'a` - Lifetime of the LED.
-pub struct Led<'a> {
+/// * `S` - The sink the color words are sent to, typically a `TxRmtDriver`.
+pub struct Led<S: PixelSink> {
     color: Rgb,
     state: State,
-    tx_rmt: TxRmtDriver<'a>,
+    white: u8,
+    gamma: Option<Gamma>,
+    order: ChannelOrder,
+    kind: LedKind,
+    sink: S,
 }
 
-impl<'a> Led<'a> {
+impl<S: PixelSink> Led<S> {
     /// Creates a new `Led` instance.
     ///
     /// # Arguments
-    /// * `tx_rmt` - A `TxRmtDriver` for controlling the LED.
+    /// * `sink` - A `PixelSink` for controlling the LED.
+    /// * `order` - The channel order expected by the LED chip, GRB for WS2812.
+    /// * `kind` - The kind of LED, [`LedKind::Rgb`] unless it has a white channel.
     ///
     /// # Returns
-    /// A new `Led` initialized to off with black color.
+    /// A new `Led` initialized to off with black color and no gamma correction.
     ///
     /// # Errors
-    /// Returns an error if the LED cannot be initialized.
-    pub fn new(tx_rmt: TxRmtDriver<'a>) -> Result<Self> {
+    /// Never fails, transmission failures are logged instead; see [`Led`].
+    pub fn new(sink: S, order: ChannelOrder, kind: LedKind) -> Result<Self> {
         let mut ret = Self {
-            tx_rmt,
+            sink,
+            order,
+            kind,
             color: BLACK,
+            white: 0,
             state: State::off(),
+            gamma: None,
         };
-        ret.apply()?;
+        ret.apply();
 
         Ok(ret)
     }
 
-    /// Applies the current state and color to the LED.
-    ///
-    /// # Errors
-    /// Returns an error if the LED state or color cannot be applied.
-    fn apply(&mut self) -> Result<()> {
-        match self.state {
-            State::On(_) => neopixel(&self.color, &mut self.tx_rmt),
-            State::Off => neopixel(&BLACK, &mut self.tx_rmt),
+    /// Applies the current state and color to the LED, retrying failed
+    /// transmissions.
+    fn apply(&mut self) {
+        let (rgb, white) = match self.state {
+            State::On(_) => (self.color, self.white),
+            State::Off => (BLACK, 0),
+        };
+        let color = match self.kind {
+            LedKind::Rgb => self.gamma.map_or_else(
+                || rgb.pack(self.order),
+                |gamma| rgb.gamma_corrected(gamma).pack(self.order),
+            ),
+            LedKind::Rgbw => {
+                let rgbw = Rgbw::from_rgb(&rgb, white);
+                self.gamma
+                    .map_or(rgbw, |gamma| rgbw.gamma_corrected(gamma))
+                    .pack(self.order)
+            }
+        };
+
+        let kind = self.kind;
+        let sent =
+            (1..SEND_ATTEMPTS).fold(self.sink.send(color, kind), |sent, attempt| {
+                sent.or_else(|e| {
+                    debug!("LED transmission failed (attempt {attempt}): {e}");
+                    self.sink.send(color, kind)
+                })
+            });
+        if let Err(e) = sent {
+            warn!(
+                "Giving up on LED transmission after {SEND_ATTEMPTS} attempts: {e}"
+            );
         }
     }
 
+    /// Returns the sink the LED sends its color words to.
+    ///
+    /// # Returns
+    /// A reference to the underlying `PixelSink`.
+    #[must_use]
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
     /// Sets the color of the LED.
     ///
     /// # Arguments
@@ -118,11 +217,73 @@ impl<'a> Led<'a> {
     /// `Ok(())` on success.
     ///
     /// # Errors
-    /// Returns an error if the color cannot be applied.
+    /// Never fails, transmission failures are logged instead; see [`Led`].
     pub fn set_color(&mut self, color: Rgb) -> Result<()> {
         self.color = color;
+        self.white = 0;
+
+        self.apply();
+
+        Ok(())
+    }
 
-        self.apply()
+    /// Sets the color of the LED, including its white channel.
+    ///
+    /// The white component is ignored unless the LED is an [`LedKind::Rgbw`].
+    ///
+    /// # Arguments
+    /// * `color` - The new color for the LED.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Never fails, transmission failures are logged instead; see [`Led`].
+    pub fn set_color_rgbw(&mut self, color: Rgbw) -> Result<()> {
+        self.color = color.rgb();
+        self.white = color.w();
+
+        self.apply();
+
+        Ok(())
+    }
+
+    /// Returns the color the LED displays when on.
+    ///
+    /// # Returns
+    /// The current color, before any gamma correction.
+    #[must_use]
+    pub fn current_color(&self) -> Rgb {
+        self.color
+    }
+
+    /// Returns whether the LED is on.
+    ///
+    /// # Returns
+    /// `true` if the LED is on, `false` otherwise.
+    #[must_use]
+    pub fn is_on(&self) -> bool {
+        self.state.is_on()
+    }
+
+    /// Sets the gamma correction applied to colors before they are displayed.
+    ///
+    /// Correction is disabled by default, so colors are sent to the LED as is.
+    ///
+    /// # Arguments
+    /// * `gamma` - The gamma curve to apply, or `None` to disable correction.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Never fails, transmission failures are logged instead; see [`Led`].
+    pub fn set_gamma(&mut self, gamma: Option<Gamma>) -> Result<()> {
+        self.gamma = gamma;
+
+        self.apply();
+
+        Ok(())
     }
 
     /// Turns on the LED.
@@ -131,11 +292,13 @@ impl<'a> Led<'a> {
     /// `Ok(())` on success.
     ///
     /// # Errors
-    /// Returns an error if the LED cannot be turned on.
+    /// Never fails, transmission failures are logged instead; see [`Led`].
     pub fn on(&mut self) -> Result<()> {
         self.state = State::on();
 
-        self.apply()
+        self.apply();
+
+        Ok(())
     }
 
     /// Turns off the LED.
@@ -144,22 +307,35 @@ impl<'a> Led<'a> {
     /// `Ok(())` on success.
     ///
     /// # Errors
-    /// Returns an error if the LED cannot be turned off.
+    /// Never fails, transmission failures are logged instead; see [`Led`].
     pub fn off(&mut self) -> Result<()> {
         self.state = State::off();
 
-        self.apply()
+        self.apply();
+
+        Ok(())
     }
 }
 
-impl Switch for Led<'_> {
+impl<S: PixelSink> Switch for Led<S> {
+    /// Returns whether the LED is on.
+    ///
+    /// # Returns
+    /// The current `State`.
+    ///
+    /// # Errors
+    /// Never fails.
+    fn state(&self) -> Result<State> {
+        Ok(self.state)
+    }
+
     /// Toggles the state of the LED.
     ///
     /// # Returns
     /// `Ok(())` on success.
     ///
     /// # Errors
-    /// Returns an error if the LED state cannot be toggled.
+    /// Never fails, transmission failures are logged instead; see [`Led`].
     fn toggle(&mut self) -> Result<()> {
         match self.state {
             State::On(_) => self.off(),
@@ -0,0 +1,93 @@
+use crate::{
+    color::{Palette, GREEN, RED},
+    infra, trigger_enum,
+};
+
+trigger_enum! {
+    /// Triggers exchanged between the threads of the example firmwares.
+    #[derive(Debug, Eq, Hash, PartialEq)]
+    pub enum Trigger {
+        ButtonPressed = 1 << 0,
+        TimerTicked = 1 << 1,
+        DeviceFoundActive = 1 << 2,
+        DeviceFoundInactive = 1 << 3,
+        DeviceNotFound = 1 << 4,
+        GpsDataAvailable = 1 << 5,
+        Heartbeat = 1 << 6,
+        UpdateRequested = 1 << 7,
+        LowBattery = 1 << 8,
+        BatteryOk = 1 << 9,
+        GpsFixLost = 1 << 10,
+        GpsFixAcquired = 1 << 11,
+        MotionStarted = 1 << 12,
+        MotionStopped = 1 << 13,
+        WifiConnected = 1 << 14,
+        WifiDisconnected = 1 << 15,
+        GpsError = 1 << 16,
+        GpsRecovered = 1 << 17,
+        ThreadFaulted = 1 << 18,
+    }
+}
+
+/// Whether a nearby device is active or inactive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceNearby {
+    /// The nearby device advertises itself as active.
+    Active,
+    /// The nearby device advertises itself as inactive.
+    Inactive,
+}
+
+/// Application state: either off, or on with optional nearby device info.
+pub type State = infra::State<DeviceNearby>;
+
+/// Every application state, each of which must have a color in the palette.
+pub const STATES: [State; 4] = [
+    State::Off,
+    State::On(None),
+    State::On(Some(DeviceNearby::Active)),
+    State::On(Some(DeviceNearby::Inactive)),
+];
+
+impl Default for Palette<State> {
+    /// Creates the palette of the example firmwares: green when on or near an
+    /// active device, red when off or near an inactive device.
+    ///
+    /// # Returns
+    /// A `Palette` mapping every state of [`STATES`], with a red fallback.
+    fn default() -> Self {
+        Palette::new(RED)
+            .with(State::Off, RED)
+            .with(State::On(None), GREEN)
+            .with(State::On(Some(DeviceNearby::Active)), GREEN)
+            .with(State::On(Some(DeviceNearby::Inactive)), RED)
+    }
+}
+
+/// Computes the state following a trigger, without side effects.
+///
+/// Handlers call this first and key their I/O off the resulting transition.
+/// Triggers that do not affect the state, and device triggers received while
+/// off, leave it unchanged.
+///
+/// # Arguments
+/// * `current` - The current state.
+/// * `trigger` - The trigger being handled.
+///
+/// # Returns
+/// The next state, equal to `current` if the trigger does not change it.
+#[must_use]
+pub fn next_state(current: &State, trigger: &Trigger) -> State {
+    match (current, trigger) {
+        (State::Off, Trigger::ButtonPressed)
+        | (State::On(_), Trigger::DeviceNotFound) => State::on(),
+        (State::On(_), Trigger::ButtonPressed) => State::off(),
+        (State::On(_), Trigger::DeviceFoundActive) => {
+            State::On(Some(DeviceNearby::Active))
+        }
+        (State::On(_), Trigger::DeviceFoundInactive) => {
+            State::On(Some(DeviceNearby::Inactive))
+        }
+        _ => *current,
+    }
+}
@@ -1,8 +1,14 @@
+#[cfg(feature = "hardware")]
 use anyhow::{anyhow, Result};
-use esp_idf_hal::{delay::BLOCK, task::notification};
-use std::{
-    collections::HashSet, fmt::Debug, hash::Hash, num::NonZeroU32, sync::Arc,
+#[cfg(feature = "hardware")]
+use esp_idf_hal::{
+    delay::{BLOCK, NON_BLOCK},
+    sys::TickType_t,
+    task::notification,
 };
+use std::{collections::HashSet, fmt::Debug, hash::Hash};
+#[cfg(feature = "hardware")]
+use std::{num::NonZeroU32, sync::Arc};
 
 /// A trait for notification trigger types used in the inter-thread messaging system.
 ///
@@ -64,6 +70,23 @@ macro_rules! trigger_enum {
     };
 }
 
+/// Decodes a notification bitmask into the triggers it carries.
+///
+/// # Arguments
+/// * `bits` - The bitwise OR of the [`Trigger::as_u32`] values of the triggers.
+///
+/// # Returns
+/// A `HashSet` of the triggers whose bit is set, bits matching no trigger
+/// being ignored.
+#[must_use]
+pub fn decode<T: Trigger>(bits: u32) -> HashSet<&'static T> {
+    T::ALL
+        .iter()
+        .filter(|trigger| bits & trigger.as_u32() != 0)
+        .collect()
+}
+
+#[cfg(feature = "hardware")]
 fn trigger_to_nonzero<T: Trigger>(trigger: &T) -> Result<NonZeroU32> {
     NonZeroU32::new(trigger.as_u32())
         .ok_or_else(|| anyhow!("Invalid value for NonZeroU32"))
@@ -73,11 +96,13 @@ fn trigger_to_nonzero<T: Trigger>(trigger: &T) -> Result<NonZeroU32> {
 ///
 /// # Type Parameters
 /// * `T` - The trigger type implementing the `Trigger` trait.
+#[cfg(feature = "hardware")]
 pub struct Notifier<T: Trigger> {
     notifier: Arc<notification::Notifier>,
     _marker: std::marker::PhantomData<T>,
 }
 
+#[cfg(feature = "hardware")]
 impl<T: Trigger> Notifier<T> {
     /// Creates a new `Notifier` instance.
     ///
@@ -119,11 +144,13 @@ impl<T: Trigger> Notifier<T> {
 ///
 /// # Type Parameters
 /// * `T` - The trigger type implementing the `Trigger` trait.
+#[cfg(feature = "hardware")]
 pub struct Dispatcher<T: Trigger> {
     notification: notification::Notification,
     _marker: std::marker::PhantomData<T>,
 }
 
+#[cfg(feature = "hardware")]
 impl<T: Trigger> Dispatcher<T> {
     /// Creates a new `Dispatcher` instance.
     ///
@@ -152,24 +179,38 @@ impl<T: Trigger> Dispatcher<T> {
 
     /// Collects triggers from the notification system.
     ///
+    /// Blocks until at least one trigger is notified.
+    ///
     /// # Returns
     /// A `HashSet` of collected triggers.
     ///
     /// # Errors
     /// Returns an error if the collection fails.
     pub fn collect(&self) -> Result<HashSet<&'static T>> {
-        let mut set = HashSet::new();
-
-        let notification = self.notification.wait(BLOCK);
-        if let Some(notification) = notification {
-            let bits = notification.get();
-            for trigger in T::ALL {
-                if bits & trigger.as_u32() != 0 {
-                    set.insert(trigger);
-                }
-            }
-        }
+        self.wait(BLOCK)
+    }
+
+    /// Collects the triggers already notified, without blocking.
+    ///
+    /// This lets a loop interleave background work between notifications.
+    ///
+    /// # Returns
+    /// A `HashSet` of collected triggers, empty if none is pending.
+    ///
+    /// # Errors
+    /// Returns an error if the collection fails.
+    pub fn drain(&self) -> Result<HashSet<&'static T>> {
+        self.wait(NON_BLOCK)
+    }
 
-        Ok(set)
+    /// Waits up to `timeout` ticks for triggers and collects them.
+    ///
+    /// # Errors
+    /// Returns an error if the collection fails.
+    fn wait(&self, timeout: TickType_t) -> Result<HashSet<&'static T>> {
+        Ok(self
+            .notification
+            .wait(timeout)
+            .map_or_else(HashSet::new, |notification| decode(notification.get())))
     }
 }
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use embedded_svc::io::Write;
+use esp_idf_hal::reset::restart;
+use esp_idf_svc::ota::EspOta;
+use log::{info, warn};
+
+use crate::http::Client;
+
+/// Downloads a firmware image over HTTP, flashes it, and reboots into it.
+///
+/// The image is streamed into the next OTA partition. The boot partition is
+/// only switched once the whole image has been written and validated, so a
+/// partial download leaves the running firmware untouched.
+///
+/// # Arguments
+/// * `client` - The HTTP client to download the image with.
+/// * `url` - The URL of the firmware image.
+///
+/// # Returns
+/// Never returns on success, as the device reboots into the new image.
+///
+/// # Errors
+/// Returns an error if the partition table has no OTA slots, the download
+/// fails, or the image cannot be written or validated.
+pub fn update(client: &mut Client, url: &str) -> Result<!> {
+    info!("Starting OTA update from {}", url);
+
+    let mut ota = EspOta::new()?;
+    let mut update = ota.initiate_update()?;
+
+    match client.download(url, |chunk| Ok(update.write_all(chunk)?)) {
+        Ok(size) => {
+            update.complete()?;
+            info!("OTA update of {} bytes complete, rebooting", size);
+            restart();
+        }
+        Err(e) => {
+            warn!("OTA update failed, keeping current firmware: {:#}", e);
+            update.abort()?;
+            Err(e.into())
+        }
+    }
+}
+
+/// Marks the running firmware as valid, cancelling any pending rollback.
+///
+/// Should be called once the application has started successfully, so that a
+/// faulty image is rolled back by the bootloader when rollback is enabled.
+///
+/// # Returns
+/// `Ok(())` on success.
+///
+/// # Errors
+/// Returns an error if the OTA state cannot be read or updated.
+pub fn mark_valid() -> Result<()> {
+    EspOta::new()?.mark_running_slot_valid()?;
+
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use anyhow::{anyhow, ensure, Result};
+
+use crate::infra::State;
+
+/// Suffix of the name advertised by a device that is on.
+pub const ACTIVE_SUFFIX: &str = "-Active";
+
+/// Suffix of the name advertised by a device that is off.
+pub const INACTIVE_SUFFIX: &str = "-Inactive";
+
+/// Computes the CRC-8 (polynomial `0x07`, initial value `0x00`) of the given
+/// bytes.
+///
+/// Guards the advertised names, the payload frames built by [`pack`], and
+/// the readings encoded by
+/// [`gps::Reading::to_bytes`](crate::gps::Reading::to_bytes).
+///
+/// # Arguments
+/// * `bytes` - The bytes to checksum.
+///
+/// # Returns
+/// The CRC-8 of `bytes`, 0 when empty.
+#[must_use]
+pub fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x07
+            }
+        })
+    })
+}
+
+/// Builds the name a device advertises for its state, e.g. `esp-flow-Active`.
+///
+/// [`ble::Advertiser`](crate::ble::Advertiser) appends its checksum to this
+/// name, and [`ble::Scanner`](crate::ble::Scanner) strips it before the name
+/// is handed to [`parse_name`].
+///
+/// # Arguments
+/// * `app_name` - The application name, shared by devices that should find
+///   each other.
+/// * `state` - The state of the advertising device.
+///
+/// # Returns
+/// The name to advertise.
+#[must_use]
+pub fn advertised_name<T>(app_name: &str, state: &State<T>) -> String {
+    let suffix = match state {
+        State::On(_) => ACTIVE_SUFFIX,
+        State::Off => INACTIVE_SUFFIX,
+    };
+
+    format!("{app_name}{suffix}")
+}
+
+/// Recovers the state of a device from the name it advertises.
+///
+/// This is the inverse of [`advertised_name`], for any application name.
+///
+/// # Arguments
+/// * `name` - The advertised name, without its checksum.
+///
+/// # Returns
+/// `Some(state)` if the name follows the format, `None` for other devices.
+#[must_use]
+pub fn parse_name(name: &str) -> Option<State> {
+    if name.ends_with(ACTIVE_SUFFIX) {
+        Some(State::on())
+    } else if name.ends_with(INACTIVE_SUFFIX) {
+        Some(State::Off)
+    } else {
+        None
+    }
+}
+
+/// Frames a payload as `[length, payload..., crc8]`, where the CRC-8 covers
+/// the length and payload bytes.
+///
+/// # Arguments
+/// * `payload` - The bytes to frame; BLE advertisements hold far fewer than
+///   the 255 the length byte allows.
+///
+/// # Returns
+/// The framed bytes, two longer than `payload`.
+///
+/// # Errors
+/// Returns an error if the payload is longer than 255 bytes.
+pub fn pack(payload: &[u8]) -> Result<Vec<u8>> {
+    let len = u8::try_from(payload.len()).map_err(|_| {
+        anyhow!(
+            "BLE payload too long: {} bytes, expected at most 255",
+            payload.len()
+        )
+    })?;
+    let mut frame = vec![len];
+    frame.extend_from_slice(payload);
+    frame.push(crc8(&frame));
+
+    Ok(frame)
+}
+
+/// Validates a frame built by [`pack`] and extracts its payload.
+///
+/// # Arguments
+/// * `frame` - The framed bytes.
+///
+/// # Returns
+/// The payload carried by the frame.
+///
+/// # Errors
+/// Returns an error if the frame length does not match its length byte or
+/// the CRC-8 does not match.
+pub fn unpack(frame: &[u8]) -> Result<Vec<u8>> {
+    let (&crc, body) = frame
+        .split_last()
+        .ok_or_else(|| anyhow!("Empty BLE frame"))?;
+    let (&len, payload) = body
+        .split_first()
+        .ok_or_else(|| anyhow!("BLE frame too short"))?;
+    ensure!(
+        usize::from(len) == payload.len(),
+        "BLE frame length mismatch: expected {len}, got {}",
+        payload.len()
+    );
+    ensure!(crc8(body) == crc, "BLE frame CRC mismatch");
+
+    Ok(payload.to_vec())
+}
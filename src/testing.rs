@@ -0,0 +1,208 @@
+use anyhow::{ensure, Result};
+
+use crate::{
+    clock::{Clock, Pace},
+    light::{LedKind, PixelSink},
+};
+
+/// A call recorded by [`FakeClock`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockCall {
+    /// [`Clock::on`] was called.
+    On,
+    /// [`Clock::off`] was called.
+    Off,
+    /// [`Clock::set_freq`] was called with the given frequency in Hz.
+    SetFreq(u64),
+}
+
+/// A host-side [`Clock`] that records calls instead of driving hardware.
+#[derive(Debug, Default)]
+pub struct FakeClock {
+    calls: Vec<ClockCall>,
+    ticks: u32,
+    processed: u32,
+    missed: u32,
+    freq: u64,
+    now_ms: u64,
+    phase: u64,
+}
+
+impl FakeClock {
+    /// Creates a new `FakeClock` with no recorded calls.
+    ///
+    /// # Returns
+    /// A new `FakeClock` instance.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the tick frequency the clock starts with, as configured on the
+    /// hardware before it is handed over.
+    ///
+    /// # Arguments
+    /// * `freq` - Initial tick frequency in Hz.
+    ///
+    /// # Returns
+    /// The updated `FakeClock` instance.
+    #[must_use]
+    pub fn with_freq(mut self, freq: u64) -> Self {
+        self.freq = freq;
+        self
+    }
+
+    /// Simulates a tick of the clock interrupt.
+    pub fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+    }
+
+    /// Advances virtual time, firing the ticks falling within it while the
+    /// clock is on.
+    ///
+    /// Partial periods carry over, so advancing twice by half a period fires
+    /// one tick.
+    ///
+    /// # Arguments
+    /// * `ms` - Virtual time to advance by, in milliseconds.
+    pub fn advance(&mut self, ms: u64) {
+        self.now_ms = self.now_ms.saturating_add(ms);
+        if self.is_on() == Some(true) {
+            self.phase = self.phase.saturating_add(ms.saturating_mul(self.freq));
+            #[allow(clippy::cast_possible_truncation)]
+            let fired = (self.phase / 1000) as u32;
+            self.phase %= 1000;
+            self.ticks = self.ticks.wrapping_add(fired);
+        }
+    }
+
+    /// Returns the virtual time elapsed since the clock was created.
+    ///
+    /// # Returns
+    /// The total time passed to [`FakeClock::advance`], in milliseconds.
+    #[must_use]
+    pub fn now_ms(&self) -> u64 {
+        self.now_ms
+    }
+
+    /// Returns the calls recorded so far, oldest first.
+    ///
+    /// # Returns
+    /// A slice of the recorded [`ClockCall`]s.
+    #[must_use]
+    pub fn calls(&self) -> &[ClockCall] {
+        &self.calls
+    }
+
+    /// Returns the most recent on/off call, if any.
+    ///
+    /// # Returns
+    /// `Some(true)` if the clock was last turned on, `Some(false)` if it was
+    /// last turned off, `None` if neither happened.
+    #[must_use]
+    pub fn is_on(&self) -> Option<bool> {
+        self.calls.iter().rev().find_map(|call| match call {
+            ClockCall::On => Some(true),
+            ClockCall::Off => Some(false),
+            ClockCall::SetFreq(_) => None,
+        })
+    }
+}
+
+impl Clock for FakeClock {
+    fn on(&mut self) -> Result<()> {
+        self.calls.push(ClockCall::On);
+
+        Ok(())
+    }
+
+    fn off(&mut self) -> Result<()> {
+        self.calls.push(ClockCall::Off);
+        self.phase = 0;
+
+        Ok(())
+    }
+
+    fn set_freq(&mut self, freq: u64) -> Result<()> {
+        ensure!(freq > 0, "FakeClock frequency must be positive");
+        self.calls.push(ClockCall::SetFreq(freq));
+        self.freq = freq;
+        self.phase = 0;
+
+        Ok(())
+    }
+
+    fn process(&mut self) -> u32 {
+        let pending = self.ticks.wrapping_sub(self.processed);
+        self.processed = self.ticks;
+        self.missed = self.missed.wrapping_add(pending.saturating_sub(1));
+
+        pending
+    }
+
+    fn missed(&self) -> u32 {
+        self.missed
+    }
+}
+
+impl Pace for FakeClock {
+    /// Returns the number of milliseconds in one period of the given frequency.
+    fn period(&self, freq: u64) -> u64 {
+        1000_u64.checked_div(freq).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the virtual time, see [`FakeClock::now_ms`].
+    fn now_ticks(&self) -> u64 {
+        self.now_ms
+    }
+
+    /// Advances virtual time to the deadline, see [`FakeClock::advance`].
+    async fn delay_until(&mut self, deadline: u64) -> Result<()> {
+        self.advance(deadline.saturating_sub(self.now_ms));
+
+        Ok(())
+    }
+}
+
+/// A host-side [`PixelSink`] that records color words instead of driving hardware.
+#[derive(Debug, Default)]
+pub struct RecordingSink {
+    words: Vec<u32>,
+}
+
+impl RecordingSink {
+    /// Creates a new `RecordingSink` with no recorded words.
+    ///
+    /// # Returns
+    /// A new `RecordingSink` instance.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the words sent so far, oldest first.
+    ///
+    /// # Returns
+    /// A slice of the recorded color words.
+    #[must_use]
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    /// Returns the most recently sent word, if any.
+    ///
+    /// # Returns
+    /// The last recorded color word, or `None` if nothing was sent.
+    #[must_use]
+    pub fn last(&self) -> Option<u32> {
+        self.words.last().copied()
+    }
+}
+
+impl PixelSink for RecordingSink {
+    fn send(&mut self, color: u32, _kind: LedKind) -> Result<()> {
+        self.words.push(color);
+
+        Ok(())
+    }
+}
@@ -1,9 +1,19 @@
 use anyhow::Result;
-use esp_idf_hal::reset::restart;
-use log::error;
-use std::thread;
+use esp_idf_hal::{
+    reset::{restart, ResetReason},
+    sys::{esp, esp_deep_sleep_start, esp_sleep_enable_ext0_wakeup, gpio_get_level},
+};
+use log::{error, info};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+};
 
-use crate::time::sleep;
+use crate::{
+    infra,
+    message::{Notifier, Trigger},
+    time::sleep,
+};
 
 /// Handles program failure by restarting the device.
 ///
@@ -16,11 +26,55 @@ pub fn failure() -> ! {
     restart();
 }
 
+/// Returns the reason for the last device reset.
+///
+/// Useful to tell restarts triggered by [`failure`] (reported as a software
+/// reset) apart from panics and watchdog timeouts.
+///
+/// # Returns
+/// The [`ResetReason`] reported by ESP-IDF.
+#[must_use]
+pub fn last_reset_reason() -> ResetReason {
+    ResetReason::get()
+}
+
+/// Puts the device into deep sleep until a button on the given GPIO is pressed.
+///
+/// The button must pull the pin low when pressed, and the pin must be an RTC
+/// GPIO (e.g. `gpio39`) to serve as an ext0 wakeup source. This waits for the
+/// button to be released first, so that the press requesting sleep does not
+/// wake the device right away.
+///
+/// Only the RTC domain stays powered during deep sleep: RAM is lost and every
+/// other peripheral (BLE, Wi-Fi, timers, RMT, UART) is shut down. Waking up
+/// resets the device, so `main` runs again from the start and reinitializes
+/// them, with [`last_reset_reason`] reporting a deep sleep reset. Application
+/// state is not restored: anything that must outlive the sleep has to be
+/// persisted to NVS beforehand.
+///
+/// # Arguments
+/// * `wake_gpio` - Number of the GPIO the button is connected to.
+///
+/// # Returns
+/// Never returns on success, the device wakes up through a reset.
+///
+/// # Errors
+/// Returns an error if the wakeup source cannot be configured.
+pub fn deep_sleep(wake_gpio: i32) -> Result<!> {
+    while unsafe { gpio_get_level(wake_gpio) } == 0 {
+        sleep(10);
+    }
+    esp!(unsafe { esp_sleep_enable_ext0_wakeup(wake_gpio, 0) })?;
+
+    info!("Entering deep sleep, press the button to wake up");
+    unsafe { esp_deep_sleep_start() }
+}
+
 /// Runs the main application logic with automatic error logging and device restart on exit.
 ///
 /// This function wraps the provided closure to ensure the device restarts
-/// if the program exits. Any errors are logged with their full chain
-/// before the restart occurs.
+/// if the program exits. The reason for the last reset is logged at startup,
+/// and any errors are logged with their full chain before the restart occurs.
 ///
 /// # Arguments
 /// * `f` - A closure that returns a `Result`.
@@ -34,6 +88,8 @@ pub fn main<F>(f: F) -> !
 where
     F: FnOnce() -> Result<()>,
 {
+    info!("Last reset reason: {:?}", last_reset_reason());
+
     if let Err(e) = f() {
         error!("Fatal error: {:#}", e);
     }
@@ -51,6 +107,131 @@ impl Drop for ExitGuard {
     }
 }
 
+/// The error a supervised thread stopped with, see [`spawn_supervised`].
+///
+/// # Fields
+/// * `thread` - The name the thread was spawned with.
+/// * `message` - The error, with its full chain.
+#[derive(Clone, Debug)]
+pub struct Fault {
+    thread: &'static str,
+    message: String,
+}
+
+impl Fault {
+    /// Returns the name of the thread that stopped.
+    ///
+    /// # Returns
+    /// The name passed to [`spawn_supervised`].
+    #[must_use]
+    pub fn thread(&self) -> &'static str {
+        self.thread
+    }
+
+    /// Returns the error the thread stopped with.
+    ///
+    /// # Returns
+    /// The error message, with its full chain.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Faults recorded by supervised threads for the main loop to act on.
+///
+/// Clones share the same faults, so one is handed to each supervised thread
+/// while the main loop keeps another.
+#[derive(Clone, Default)]
+pub struct Faults(Arc<Mutex<Vec<Fault>>>);
+
+impl Faults {
+    /// Creates an empty set of faults.
+    ///
+    /// # Returns
+    /// A new `Faults` with nothing recorded.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes and returns the recorded faults, oldest first.
+    ///
+    /// # Returns
+    /// The faults recorded since the last call.
+    #[must_use]
+    pub fn take(&self) -> Vec<Fault> {
+        std::mem::take(&mut *infra::lock(&self.0))
+    }
+}
+
+/// A guard that restarts the device only when its thread panics.
+struct PanicGuard;
+
+impl Drop for PanicGuard {
+    /// Restarts the device if the thread is unwinding from a panic.
+    fn drop(&mut self) {
+        if thread::panicking() {
+            failure();
+        }
+    }
+}
+
+/// Spawns a thread whose errors are reported to the main loop instead of
+/// restarting the device.
+///
+/// The coordination protocol is:
+/// 1. When `f` returns an error, the thread logs it, records it as a
+///    [`Fault`] in `faults`, notifies `trigger`, and exits.
+/// 2. The main loop, woken by `trigger`, calls [`Faults::take`] and decides
+///    what to do: keep running without the thread's feature, or fail, which
+///    restarts the device through [`main`].
+///
+/// Panics are not recoverable and still restart the device, like threads
+/// started with [`spawn`].
+///
+/// # Arguments
+/// * `name` - The name of the thread, reported in its faults.
+/// * `faults` - Where the thread records its error.
+/// * `notifier` - A notifier to wake the main loop.
+/// * `trigger` - The trigger to emit when the thread faults.
+/// * `f` - A closure running until it fails, such as [`Poller::poll`](crate::infra::Poller::poll).
+///
+/// # Returns
+/// A `JoinHandle` for the spawned thread.
+///
+/// # Type Parameters
+/// * `F` - The type of the closure.
+/// * `T` - The trigger type implementing the `Trigger` trait.
+pub fn spawn_supervised<F, T>(
+    name: &'static str,
+    faults: &Faults,
+    notifier: Notifier<T>,
+    trigger: &'static T,
+    f: F,
+) -> thread::JoinHandle<()>
+where
+    F: FnOnce() -> Result<!> + Send + 'static,
+    T: Trigger,
+{
+    let faults = faults.clone();
+    thread::spawn(move || {
+        let _guard = PanicGuard;
+        if let Err(e) = f() {
+            error!("Thread {name} faulted: {e:#}");
+            infra::lock(&faults.0).push(Fault {
+                thread: name,
+                message: format!("{e:#}"),
+            });
+            // Without a way to reach the main loop, restarting is all that is
+            // left
+            if notifier.notify(trigger).is_err() {
+                failure();
+            }
+        }
+    })
+}
+
 /// Spawns a new thread with a failure guard.
 ///
 /// # Arguments
@@ -1,11 +1,75 @@
-use esp_idf_hal::delay::FreeRtos;
+#[cfg(feature = "hardware")]
+use esp_idf_hal::{delay::FreeRtos, sys::esp_timer_get_time};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+#[cfg(not(feature = "hardware"))]
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+/// Whether the system clock has been set to the real time.
+static CLOCK_SYNCED: AtomicBool = AtomicBool::new(false);
+
+/// Instant standing in for boot on the host, taken on first use.
+#[cfg(not(feature = "hardware"))]
+static BOOT: OnceLock<Instant> = OnceLock::new();
 
 /// Delays execution for a specified number of milliseconds.
 ///
 /// # Arguments
 /// * `ms` - The number of milliseconds to delay.
 pub fn sleep(ms: u32) {
+    #[cfg(feature = "hardware")]
     FreeRtos::delay_ms(ms);
+    #[cfg(not(feature = "hardware"))]
+    std::thread::sleep(Duration::from_millis(u64::from(ms)));
+}
+
+/// Returns the time elapsed since boot.
+///
+/// The clock is monotonic and keeps counting in light sleep, so it is suitable
+/// for timestamping events and measuring their age. On the host, the time is
+/// counted from the first call instead.
+///
+/// # Returns
+/// The number of milliseconds since boot.
+#[must_use]
+pub fn now_ms() -> u64 {
+    #[cfg(feature = "hardware")]
+    let ms = u64::try_from(unsafe { esp_timer_get_time() } / 1000);
+    #[cfg(not(feature = "hardware"))]
+    let ms = u64::try_from(BOOT.get_or_init(Instant::now).elapsed().as_millis());
+
+    ms.unwrap_or_default()
+}
+
+/// Records that the system clock has been set to the real time, enabling
+/// [`now_unix`].
+///
+/// Called once SNTP completes a synchronization, see
+/// [`Connection::sync_time`](crate::wifi::Connection::sync_time).
+pub fn set_synced() {
+    CLOCK_SYNCED.store(true, Ordering::Relaxed);
+}
+
+/// Returns the current real-world time.
+///
+/// The system clock starts at the epoch on boot, so this is only meaningful
+/// once it has been synchronized.
+///
+/// # Returns
+/// The number of seconds since the Unix epoch, or `None` until the system
+/// clock is first synchronized.
+#[must_use]
+pub fn now_unix() -> Option<u64> {
+    CLOCK_SYNCED
+        .load(Ordering::Relaxed)
+        .then(|| SystemTime::now().duration_since(UNIX_EPOCH).ok())
+        .flatten()
+        .map(|elapsed| elapsed.as_secs())
 }
 
 /// Yields the current thread for a short duration.
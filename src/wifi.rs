@@ -1,6 +1,180 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, ensure, Result};
 use embedded_svc::wifi::{AuthMethod, ClientConfiguration, Configuration};
-use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
+use esp_idf_svc::{
+    mdns::EspMdns,
+    nvs::{EspNvs, NvsPartitionId},
+    sntp::{EspSntp, SntpConf, SNTP_SERVER_NUM},
+    sys::{
+        esp, esp_eap_client_set_ca_cert, esp_eap_client_set_identity,
+        esp_eap_client_set_password, esp_eap_client_set_username,
+        esp_wifi_sta_enterprise_enable,
+    },
+    wifi::{BlockingWifi, EspWifi},
+};
+use std::{ffi::CString, ops::RangeInclusive};
+#[cfg(feature = "wifi")]
+use {
+    embedded_svc::{
+        http::Method,
+        io::{Read, Write},
+        wifi::AccessPointConfiguration,
+    },
+    esp_idf_svc::{
+        http::server::{Configuration as ServerConfiguration, EspHttpServer},
+        sys::{esp_wifi_sta_get_ap_info, wifi_ap_record_t, ESP_OK},
+    },
+    std::sync::mpsc,
+};
+
+use crate::time;
+#[cfg(feature = "wifi")]
+use crate::{
+    infra::Poller,
+    message::{Notifier, Trigger},
+};
+
+/// Default number of consecutive checks a [`Monitor`] needs before reporting
+/// a change of the connection.
+#[cfg(feature = "wifi")]
+pub const DEFAULT_MONITOR_DEBOUNCE: u32 = 3;
+
+/// Default NTP server used to synchronize the system clock.
+pub const DEFAULT_NTP_SERVER: &str = "pool.ntp.org";
+
+/// NVS key of the stored SSID, see [`Config::store`].
+const NVS_SSID_KEY: &str = "wifi_ssid";
+
+/// NVS key of the stored password, see [`Config::store`].
+const NVS_PASSWORD_KEY: &str = "wifi_password";
+
+/// Size of the buffer a stored credential is read into: the longest password
+/// WPA2 allows, plus the NUL terminator.
+const NVS_CREDENTIAL_LEN: usize = 65;
+
+/// Longest SSID, in bytes.
+const SSID_MAX_LEN: usize = 32;
+
+/// Lengths of the WPA2-Personal passphrases, in characters.
+const PASSWORD_LENS: RangeInclusive<usize> = 8..=63;
+
+/// Largest provisioning form accepted, in bytes.
+#[cfg(feature = "wifi")]
+const PROVISIONING_MAX_BODY: usize = 256;
+
+/// Page served by [`provision`], posting the credentials back to itself.
+#[cfg(feature = "wifi")]
+const PROVISIONING_PAGE: &str = concat!(
+    "<!DOCTYPE html><html><head><meta name=\"viewport\" content=\"width=device-width\">",
+    "<title>Wi-Fi setup</title></head><body><h1>Wi-Fi setup</h1>",
+    "<form method=\"post\"><p><label>SSID <input name=\"ssid\" maxlength=\"32\" required></label></p>",
+    "<p><label>Password <input name=\"password\" type=\"password\" minlength=\"8\" maxlength=\"63\" required></label></p>",
+    "<p><button type=\"submit\">Connect</button></p></form></body></html>"
+);
+
+/// Checks that WPA2-Personal credentials can be valid, so that wrong ones are
+/// not stored and retried on every boot.
+///
+/// # Errors
+/// Returns an error if the SSID is empty or longer than 32 bytes, or the
+/// password is not 8 to 63 characters long.
+fn validate_credentials(ssid: &str, password: &str) -> Result<()> {
+    ensure!(
+        !ssid.is_empty() && ssid.len() <= SSID_MAX_LEN,
+        "Invalid SSID length {}, expected 1 to {SSID_MAX_LEN} bytes",
+        ssid.len()
+    );
+    let password_len = password.chars().count();
+    ensure!(
+        PASSWORD_LENS.contains(&password_len),
+        "Invalid WPA2 password length {password_len}, expected {} to {} characters",
+        PASSWORD_LENS.start(),
+        PASSWORD_LENS.end()
+    );
+
+    Ok(())
+}
+
+/// WPA2-Enterprise credentials, besides the password kept in [`Config`].
+///
+/// Only EAP methods authenticating with a username and password are
+/// supported, i.e. PEAP and TTLS with MSCHAPv2 as the inner method; EAP-TLS,
+/// which requires a client certificate, is not.
+///
+/// # Fields
+/// * `identity` - The outer identity, sent in the clear before the TLS tunnel
+///   is set up.
+/// * `username` - The username checked inside the tunnel.
+/// * `ca_cert` - The PEM certificate of the CA that signed the authentication
+///   server's certificate; the server is not verified when unset.
+pub struct Enterprise {
+    identity: &'static str,
+    username: &'static str,
+    ca_cert: Option<&'static str>,
+}
+
+impl Enterprise {
+    /// Creates new WPA2-Enterprise credentials.
+    ///
+    /// # Arguments
+    /// * `identity` - The outer identity, often `anonymous` or the username.
+    /// * `username` - The username checked inside the tunnel.
+    ///
+    /// # Returns
+    /// New `Enterprise` credentials, without server verification.
+    #[must_use]
+    pub fn new(identity: &'static str, username: &'static str) -> Self {
+        Self {
+            identity,
+            username,
+            ca_cert: None,
+        }
+    }
+
+    /// Verifies the authentication server against a CA certificate.
+    ///
+    /// # Arguments
+    /// * `ca_cert` - The PEM-encoded CA certificate.
+    ///
+    /// # Returns
+    /// The updated `Enterprise` credentials.
+    #[must_use]
+    pub fn with_ca_cert(mut self, ca_cert: &'static str) -> Self {
+        self.ca_cert = Some(ca_cert);
+        self
+    }
+
+    /// Hands the credentials over to the ESP-IDF EAP client.
+    ///
+    /// # Returns
+    /// The NUL-terminated CA certificate, which the EAP client keeps a
+    /// pointer to and must therefore outlive the connection.
+    ///
+    /// # Errors
+    /// Returns an error if a credential is too long or cannot be set.
+    fn apply(&self, password: &str) -> Result<Option<CString>> {
+        let len = |value: &str| i32::try_from(value.len());
+        esp!(unsafe {
+            esp_eap_client_set_identity(self.identity.as_ptr(), len(self.identity)?)
+        })?;
+        esp!(unsafe {
+            esp_eap_client_set_username(self.username.as_ptr(), len(self.username)?)
+        })?;
+        esp!(unsafe {
+            esp_eap_client_set_password(password.as_ptr(), len(password)?)
+        })?;
+
+        let ca_cert = self.ca_cert.map(CString::new).transpose()?;
+        if let Some(ca_cert) = &ca_cert {
+            let pem = ca_cert.as_bytes_with_nul();
+            esp!(unsafe {
+                esp_eap_client_set_ca_cert(pem.as_ptr(), i32::try_from(pem.len())?)
+            })?;
+        }
+        esp!(unsafe { esp_wifi_sta_enterprise_enable() })?;
+
+        Ok(ca_cert)
+    }
+}
 
 /// Wi-Fi network configuration containing SSID, password, and authentication method.
 ///
@@ -8,28 +182,109 @@ use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
 /// * `ssid` - The network SSID.
 /// * `password` - The network password.
 /// * `auth` - The authentication method (e.g., `WPA2Personal`).
+/// * `enterprise` - The credentials used when `auth` is `WPA2Enterprise`.
 pub struct Config {
-    ssid: &'static str,
-    password: &'static str,
+    ssid: String,
+    password: String,
     auth: AuthMethod,
+    enterprise: Option<Enterprise>,
 }
 
 impl Config {
-    fn new(ssid: &'static str, password: &'static str, auth: AuthMethod) -> Self {
+    fn new(ssid: String, password: String, auth: AuthMethod) -> Self {
         Self {
             ssid,
             password,
             auth,
+            enterprise: None,
         }
     }
 
+    /// Reads WPA2-Personal credentials stored by [`Config::store`].
+    ///
+    /// # Arguments
+    /// * `nvs` - The NVS namespace to read from.
+    ///
+    /// # Returns
+    /// The stored `Config`, or `None` if no SSID is stored.
+    ///
+    /// # Errors
+    /// Returns an error if an NVS entry cannot be read.
+    pub fn load<P: NvsPartitionId>(nvs: &EspNvs<P>) -> Result<Option<Self>> {
+        let mut buf = [0u8; NVS_CREDENTIAL_LEN];
+        let ssid = nvs.get_str(NVS_SSID_KEY, &mut buf)?.map(str::to_string);
+
+        ssid.map(|ssid| {
+            let password = nvs
+                .get_str(NVS_PASSWORD_KEY, &mut buf)?
+                .map(str::to_string)
+                .unwrap_or_default();
+            Ok(Self::new(ssid, password, AuthMethod::WPA2Personal))
+        })
+        .transpose()
+    }
+
+    /// Stores the SSID and password in NVS, so that [`Config::load`] finds
+    /// them on the next boots.
+    ///
+    /// # Arguments
+    /// * `nvs` - The NVS namespace to write to.
+    ///
+    /// # Returns
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    /// Returns an error if the SSID is empty or longer than 32 bytes, the
+    /// password is not 8 to 63 characters long, or an NVS entry cannot be
+    /// written.
+    pub fn store<P: NvsPartitionId>(&self, nvs: &EspNvs<P>) -> Result<()> {
+        validate_credentials(&self.ssid, &self.password)?;
+        nvs.set_str(NVS_SSID_KEY, &self.ssid)?;
+        nvs.set_str(NVS_PASSWORD_KEY, &self.password)?;
+
+        Ok(())
+    }
+
+    /// Removes the credentials stored by [`Config::store`], e.g. once they
+    /// failed to connect, so that the next boot provisions new ones.
+    ///
+    /// # Arguments
+    /// * `nvs` - The NVS namespace to remove them from.
+    ///
+    /// # Returns
+    /// `Ok(())` on success, including when no credentials are stored.
+    ///
+    /// # Errors
+    /// Returns an error if an NVS entry cannot be removed.
+    pub fn clear<P: NvsPartitionId>(nvs: &EspNvs<P>) -> Result<()> {
+        nvs.remove(NVS_SSID_KEY)?;
+        nvs.remove(NVS_PASSWORD_KEY)?;
+
+        Ok(())
+    }
+
+    /// Switches the configuration to WPA2-Enterprise authentication.
+    ///
+    /// # Arguments
+    /// * `enterprise` - The credentials to authenticate with, the password
+    ///   being the configuration's one.
+    ///
+    /// # Returns
+    /// The updated `Config`.
+    #[must_use]
+    pub fn with_enterprise(mut self, enterprise: Enterprise) -> Self {
+        self.auth = AuthMethod::WPA2Enterprise;
+        self.enterprise = Some(enterprise);
+        self
+    }
+
     /// Returns the configured Wi-Fi SSID.
     ///
     /// # Returns
     /// The SSID as a string slice.
     #[must_use]
     pub fn ssid(&self) -> &str {
-        self.ssid
+        &self.ssid
     }
 
     /// Returns the configured Wi-Fi password.
@@ -38,7 +293,7 @@ impl Config {
     /// The password as a string slice.
     #[must_use]
     pub fn password(&self) -> &str {
-        self.password
+        &self.password
     }
 
     /// Returns the configured authentication method.
@@ -53,7 +308,10 @@ impl Config {
     /// Creates a `Config` from compile-time environment variables.
     ///
     /// Reads `WIFI_SSID` and `WIFI_PASSWORD` via `option_env!` and defaults
-    /// to `WPA2Personal` authentication.
+    /// to `WPA2Personal` authentication. Setting `WIFI_EAP_USERNAME` selects
+    /// `WPA2Enterprise` instead, with the outer identity read from
+    /// `WIFI_EAP_IDENTITY` (the username when unset) and the CA certificate
+    /// from `WIFI_EAP_CA_CERT`.
     ///
     /// # Returns
     /// A `Config` populated from environment variables.
@@ -65,9 +323,153 @@ impl Config {
             .ok_or_else(|| anyhow!("WIFI_SSID environment variable not set"))?;
         let password = option_env!("WIFI_PASSWORD")
             .ok_or_else(|| anyhow!("WIFI_PASSWORD environment variable not set"))?;
+        let config = Self::new(
+            ssid.to_string(),
+            password.to_string(),
+            AuthMethod::WPA2Personal,
+        );
+
+        Ok(match option_env!("WIFI_EAP_USERNAME") {
+            Some(username) => {
+                let enterprise = Enterprise::new(
+                    option_env!("WIFI_EAP_IDENTITY").unwrap_or(username),
+                    username,
+                );
+                config.with_enterprise(match option_env!("WIFI_EAP_CA_CERT") {
+                    Some(ca_cert) => enterprise.with_ca_cert(ca_cert),
+                    None => enterprise,
+                })
+            }
+            None => config,
+        })
+    }
+}
 
-        Ok(Self::new(ssid, password, AuthMethod::WPA2Personal))
+/// Decodes an `application/x-www-form-urlencoded` value.
+#[cfg(feature = "wifi")]
+fn url_decode(value: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.bytes();
+    while let Some(byte) = rest.next() {
+        bytes.push(match byte {
+            b'+' => b' ',
+            b'%' => {
+                let hex = [rest.next(), rest.next()]
+                    .into_iter()
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(|| anyhow!("Truncated escape in form value"))?;
+                u8::from_str_radix(std::str::from_utf8(&hex)?, 16)?
+            }
+            byte => byte,
+        });
     }
+
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Returns the decoded value of a field of an URL-encoded form.
+#[cfg(feature = "wifi")]
+fn form_value(form: &str, key: &str) -> Result<Option<String>> {
+    form.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| url_decode(value))
+        .transpose()
+}
+
+/// Asks for the Wi-Fi credentials through a web page served on a soft access
+/// point, and stores them in NVS.
+///
+/// The device opens a network named `ap_ssid`, without a password, and logs
+/// the address of the setup page (`http://192.168.71.1` by default). Once a
+/// phone or laptop joined it and submitted the form, the access point is
+/// stopped and the credentials are stored with [`Config::store`], ready for
+/// [`Connection::new`]. Credentials that cannot be valid for WPA2, e.g. a
+/// password shorter than 8 characters, are rejected with an error page and
+/// the access point stays up. There is no DNS redirection, so the page has to
+/// be opened by hand rather than popping up as a captive portal.
+///
+/// # Arguments
+/// * `handler` - The Wi-Fi handler, left stopped on return.
+/// * `nvs` - The NVS namespace to store the credentials in.
+/// * `ap_ssid` - The name of the setup network.
+///
+/// # Returns
+/// The `Config` built from the submitted credentials.
+///
+/// # Errors
+/// Returns an error if the access point or the setup page cannot be started,
+/// or the credentials cannot be stored.
+#[cfg(feature = "wifi")]
+pub fn provision<P: NvsPartitionId>(
+    handler: &mut BlockingWifi<EspWifi<'_>>,
+    nvs: &EspNvs<P>,
+    ap_ssid: &str,
+) -> Result<Config> {
+    handler.set_configuration(&Configuration::AccessPoint(
+        AccessPointConfiguration {
+            ssid: ap_ssid
+                .try_into()
+                .map_err(|()| anyhow!("Failed to convert access point SSID"))?,
+            auth_method: AuthMethod::None,
+            ..Default::default()
+        },
+    ))?;
+    handler.start()?;
+    handler.wait_netif_up()?;
+    log::info!(
+        "No Wi-Fi credentials, join the {ap_ssid} network and open http://{}",
+        handler.wifi().ap_netif().get_ip_info()?.ip
+    );
+
+    let (tx, rx) = mpsc::channel();
+    let mut server = EspHttpServer::new(&ServerConfiguration::default())?;
+    server.fn_handler("/", Method::Get, |request| -> Result<()> {
+        request
+            .into_response(200, None, &[("content-type", "text/html")])?
+            .write_all(PROVISIONING_PAGE.as_bytes())?;
+        Ok(())
+    })?;
+    server.fn_handler("/", Method::Post, move |mut request| -> Result<()> {
+        let mut body = [0u8; PROVISIONING_MAX_BODY];
+        let mut len = 0;
+        loop {
+            match request.read(&mut body[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        let form = std::str::from_utf8(&body[..len])?;
+        let ssid = form_value(form, "ssid")?
+            .filter(|ssid| !ssid.is_empty())
+            .ok_or_else(|| anyhow!("Missing SSID in provisioning form"))?;
+        let password = form_value(form, "password")?.unwrap_or_default();
+        // Keep the access point up until valid credentials are submitted
+        match validate_credentials(&ssid, &password) {
+            Ok(()) => {
+                tx.send((ssid, password))?;
+                request
+                    .into_ok_response()?
+                    .write_all(b"Saved, connecting to the network...")?;
+            }
+            Err(e) => {
+                request
+                    .into_status_response(400)?
+                    .write_all(format!("{e}, go back and try again").as_bytes())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    let (ssid, password) = rx.recv()?;
+    drop(server);
+    handler.stop()?;
+
+    let config = Config::new(ssid, password, AuthMethod::WPA2Personal);
+    config.store(nvs)?;
+    log::info!("Stored Wi-Fi credentials for {}", config.ssid());
+
+    Ok(config)
 }
 
 /// Represents a Wi-Fi connection, handling its configuration and state management.
@@ -75,12 +477,17 @@ impl Config {
 /// This struct leverages the `BlockingWifi` handler from the ESP-IDF framework for managing the connection.
 pub struct Connection<'a> {
     handler: BlockingWifi<EspWifi<'a>>,
+    mdns: Option<EspMdns>,
+    sntp: Option<EspSntp<'static>>,
+    _ca_cert: Option<CString>,
 }
 
 impl<'a> Connection<'a> {
     /// Creates a new `Connection` instance with the given Wi-Fi handler and configuration.
     ///
     /// Configures, starts, connects, and waits for the network interface to come up.
+    /// `WPA2Enterprise` authentication uses the configuration's [`Enterprise`]
+    /// credentials, every other method the SSID and password only.
     ///
     /// # Arguments
     ///
@@ -94,8 +501,22 @@ impl<'a> Connection<'a> {
     /// # Errors
     ///
     /// Returns an error if the configuration cannot be set, SSID/password conversion fails,
-    /// or the connection cannot be established.
+    /// enterprise credentials are missing or rejected, or the connection cannot be established.
     pub fn new(handler: BlockingWifi<EspWifi<'a>>, config: &Config) -> Result<Self> {
+        let enterprise = match config.auth() {
+            AuthMethod::WPA2Enterprise => {
+                Some(config.enterprise.as_ref().ok_or_else(|| {
+                    anyhow!("WPA2-Enterprise requires enterprise credentials")
+                })?)
+            }
+            _ => None,
+        };
+        // Enterprise passwords go to the EAP client, not the station config
+        let password = if enterprise.is_some() {
+            ""
+        } else {
+            config.password()
+        };
         let configuration: Configuration =
             Configuration::Client(ClientConfiguration {
                 auth_method: config.auth(),
@@ -103,8 +524,7 @@ impl<'a> Connection<'a> {
                     .ssid()
                     .try_into()
                     .map_err(|()| anyhow!("Failed to convert SSID"))?,
-                password: config
-                    .password()
+                password: password
                     .try_into()
                     .map_err(|()| anyhow!("Failed to convert password"))?,
                 ..Default::default()
@@ -112,12 +532,85 @@ impl<'a> Connection<'a> {
 
         let mut handler = handler;
         handler.set_configuration(&configuration)?;
+        let ca_cert = enterprise
+            .map(|enterprise| enterprise.apply(config.password()))
+            .transpose()?
+            .flatten();
 
         handler.start()?;
         handler.connect()?;
         handler.wait_netif_up()?;
 
-        Ok(Self { handler })
+        Ok(Self {
+            handler,
+            mdns: None,
+            sntp: None,
+            _ca_cert: ca_cert,
+        })
+    }
+
+    /// Advertises the device on the local network over mDNS.
+    ///
+    /// The device becomes reachable as `{hostname}.local`, and its HTTP server
+    /// is advertised as an `_http._tcp` service so that browsers and service
+    /// discovery tools can find it. Advertising stops when the connection is
+    /// dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `hostname` - The hostname to advertise, without the `.local` suffix.
+    /// * `http_port` - The port the HTTP server listens on.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mDNS service is already taken or the hostname
+    /// or service cannot be registered.
+    pub fn advertise(&mut self, hostname: &str, http_port: u16) -> Result<()> {
+        let mut mdns = EspMdns::take()?;
+        mdns.set_hostname(hostname)?;
+        mdns.set_instance_name(hostname)?;
+        mdns.add_service(None, "_http", "_tcp", http_port, &[])?;
+        log::info!("Advertising {hostname}.local over mDNS");
+
+        self.mdns = Some(mdns);
+        Ok(())
+    }
+
+    /// Synchronizes the system clock with an NTP server in the background.
+    ///
+    /// The first synchronization completes asynchronously, after which
+    /// [`time::now_unix`] starts returning the real time. The clock is then
+    /// periodically resynchronized as long as the connection is alive.
+    ///
+    /// # Arguments
+    ///
+    /// * `server` - The NTP server host, e.g. [`DEFAULT_NTP_SERVER`].
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once synchronization has started.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SNTP service is already taken or cannot be started.
+    pub fn sync_time(&mut self, server: &str) -> Result<()> {
+        let conf = SntpConf {
+            servers: [server; SNTP_SERVER_NUM],
+            ..Default::default()
+        };
+        self.sntp = Some(EspSntp::new_with_callback(&conf, |synced| {
+            log::info!(
+                "System clock synchronized: {}s since epoch",
+                synced.as_secs()
+            );
+            time::set_synced();
+        })?);
+
+        Ok(())
     }
 
     /// Checks if the Wi-Fi connection is currently on.
@@ -133,3 +626,97 @@ impl<'a> Connection<'a> {
         Ok(self.handler.is_connected()?)
     }
 }
+
+/// Watches the station connection and reports when it goes up or down.
+///
+/// The connection is checked once per second, independently of the
+/// [`Connection`] owning the driver, so the monitor can poll on its own
+/// thread. A change is only reported once it lasted for the debounce number
+/// of consecutive checks, so that brief blips do not reach the dispatcher.
+///
+/// # Type Parameters
+/// * `T` - The trigger type implementing the `Trigger` trait.
+#[cfg(feature = "wifi")]
+pub struct Monitor<T: Trigger> {
+    notifier: Notifier<T>,
+    connected_trigger: &'static T,
+    disconnected_trigger: &'static T,
+    debounce: u32,
+}
+
+#[cfg(feature = "wifi")]
+impl<T: Trigger> Monitor<T> {
+    /// Interval between two checks when polling, in milliseconds.
+    const INTERVAL: u32 = 1000;
+
+    /// Creates a new `Monitor` instance.
+    ///
+    /// # Arguments
+    /// * `notifier` - A notifier to send connection events.
+    /// * `connected_trigger` - The trigger to emit when the connection comes up.
+    /// * `disconnected_trigger` - The trigger to emit when the connection goes down.
+    /// * `debounce` - Number of consecutive checks a change must last before it
+    ///   is reported, e.g. [`DEFAULT_MONITOR_DEBOUNCE`].
+    ///
+    /// # Returns
+    /// A new `Monitor` ready to poll.
+    #[must_use]
+    pub fn new(
+        notifier: Notifier<T>,
+        connected_trigger: &'static T,
+        disconnected_trigger: &'static T,
+        debounce: u32,
+    ) -> Self {
+        Self {
+            notifier,
+            connected_trigger,
+            disconnected_trigger,
+            debounce: debounce.max(1),
+        }
+    }
+
+    /// Checks whether the station is associated with an access point.
+    ///
+    /// # Returns
+    /// `true` if the station is connected, `false` otherwise.
+    #[must_use]
+    pub fn is_connected() -> bool {
+        let mut info = wifi_ap_record_t::default();
+        unsafe { esp_wifi_sta_get_ap_info(&mut info) == ESP_OK }
+    }
+}
+
+#[cfg(feature = "wifi")]
+impl<T: Trigger> Poller for Monitor<T> {
+    /// Periodically checks the connection and reports its changes.
+    ///
+    /// The state when polling starts is taken as is, without emitting a
+    /// trigger. Afterwards, the connected trigger is emitted once when the
+    /// connection comes back, and the disconnected trigger once when it drops.
+    ///
+    /// # Errors
+    /// Returns an error if notification fails.
+    fn poll(&mut self) -> Result<!> {
+        let mut connected = Self::is_connected();
+        let mut streak = 0;
+        loop {
+            streak = if Self::is_connected() == connected {
+                0
+            } else {
+                streak + 1
+            };
+
+            if streak >= self.debounce {
+                connected = !connected;
+                streak = 0;
+                self.notifier.notify(if connected {
+                    self.connected_trigger
+                } else {
+                    self.disconnected_trigger
+                })?;
+            }
+
+            time::sleep(Self::INTERVAL);
+        }
+    }
+}
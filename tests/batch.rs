@@ -0,0 +1,88 @@
+use esp_flow::batch::Batch;
+
+#[test]
+fn new_rejects_invalid_batch_sizes() {
+    assert!(Batch::<u8>::new(10, 0, 1000).is_err());
+    assert!(Batch::<u8>::new(10, 11, 1000).is_err());
+    assert!(Batch::<u8>::new(10, 10, 1000).is_ok());
+}
+
+#[test]
+fn empty_batch_is_never_due() {
+    let batch = Batch::<u8>::new(10, 3, 1000).unwrap();
+
+    assert!(batch.is_empty());
+    assert!(!batch.is_due(0));
+    assert!(!batch.is_due(u64::MAX));
+}
+
+#[test]
+fn batch_is_due_once_full() {
+    let mut batch = Batch::new(10, 3, 1000).unwrap();
+
+    batch.push(1, 0);
+    batch.push(2, 0);
+    assert!(!batch.is_due(0));
+    batch.push(3, 0);
+    assert!(batch.is_due(0));
+}
+
+#[test]
+fn batch_is_due_once_oldest_item_waited_for_interval() {
+    let mut batch = Batch::new(10, 3, 1000).unwrap();
+
+    batch.push(1, 500);
+    batch.push(2, 1400);
+    assert!(!batch.is_due(1499));
+    assert!(batch.is_due(1500));
+}
+
+#[test]
+fn push_drops_oldest_item_when_full() {
+    let mut batch = Batch::new(3, 3, 1000).unwrap();
+
+    assert_eq!(batch.push(1, 0), None);
+    assert_eq!(batch.push(2, 0), None);
+    assert_eq!(batch.push(3, 0), None);
+    assert_eq!(batch.push(4, 0), Some(1));
+    assert_eq!(batch.len(), 3);
+    assert_eq!(batch.iter().copied().collect::<Vec<_>>(), [2, 3, 4]);
+}
+
+#[test]
+fn clear_empties_and_restarts_the_interval() {
+    let mut batch = Batch::new(10, 3, 1000).unwrap();
+
+    batch.push(1, 0);
+    batch.clear();
+    assert!(batch.is_empty());
+    assert!(!batch.is_due(5000));
+
+    batch.push(2, 5000);
+    assert!(!batch.is_due(5999));
+    assert!(batch.is_due(6000));
+}
+
+#[test]
+fn defer_postpones_a_due_batch_by_the_interval() {
+    let mut batch = Batch::new(10, 2, 1000).unwrap();
+
+    batch.push(1, 0);
+    batch.push(2, 0);
+    batch.defer(100);
+    assert!(!batch.is_due(100));
+    assert!(!batch.is_due(1099));
+    assert!(batch.is_due(1100));
+    assert_eq!(batch.len(), 2);
+}
+
+#[test]
+fn clear_cancels_deferral() {
+    let mut batch = Batch::new(10, 1, 1000).unwrap();
+
+    batch.push(1, 0);
+    batch.defer(0);
+    batch.clear();
+    batch.push(2, 10);
+    assert!(batch.is_due(10));
+}
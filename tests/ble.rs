@@ -0,0 +1,72 @@
+use std::{
+    future::Future,
+    pin::pin,
+    ptr,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use esp_flow::{ble::ScanSchedule, testing::FakeClock};
+
+// FakeClock delays complete immediately, so a single poll drives a future to
+// completion without an executor.
+fn block_on<F: Future>(future: F) -> F::Output {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    let waker = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) };
+    match pin!(future).poll(&mut Context::from_waker(&waker)) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future did not complete in a single poll"),
+    }
+}
+
+#[test]
+fn scans_start_at_a_fixed_rate() {
+    let mut schedule = ScanSchedule::new(FakeClock::new(), 4, 1).unwrap();
+
+    block_on(schedule.wait()).unwrap();
+    assert_eq!(schedule.clock_mut().now_ms(), 250);
+
+    schedule.clock_mut().advance(100);
+    block_on(schedule.wait()).unwrap();
+    assert_eq!(schedule.clock_mut().now_ms(), 500);
+}
+
+#[test]
+fn overrun_scans_skip_missed_starts() {
+    let mut schedule = ScanSchedule::new(FakeClock::new(), 4, 1).unwrap();
+    block_on(schedule.wait()).unwrap();
+
+    schedule.clock_mut().advance(600);
+    block_on(schedule.wait()).unwrap();
+    assert_eq!(schedule.clock_mut().now_ms(), 1000);
+}
+
+#[test]
+fn default_trigger_waits_for_consecutive_misses() {
+    let mut schedule = ScanSchedule::new(FakeClock::new(), 1, 3).unwrap();
+
+    assert!(!schedule.record(false));
+    assert!(!schedule.record(false));
+    assert!(!schedule.record(true));
+    assert!(!schedule.record(false));
+    assert!(!schedule.record(false));
+    assert!(schedule.record(false));
+    assert!(schedule.record(false));
+}
+
+#[test]
+fn zero_max_misses_reports_every_empty_scan() {
+    let mut schedule = ScanSchedule::new(FakeClock::new(), 1, 0).unwrap();
+
+    assert!(schedule.record(false));
+    assert!(!schedule.record(true));
+}
+
+#[test]
+fn zero_scan_frequency_is_rejected() {
+    assert!(ScanSchedule::new(FakeClock::new(), 0, 1).is_err());
+}
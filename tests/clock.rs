@@ -0,0 +1,19 @@
+use esp_flow::clock::next_deadline;
+
+#[test]
+fn next_deadline_keeps_fixed_rate() {
+    assert_eq!(next_deadline(100, 100, 150), 200);
+    assert_eq!(next_deadline(200, 100, 200), 300);
+}
+
+#[test]
+fn next_deadline_skips_missed_periods() {
+    assert_eq!(next_deadline(100, 100, 200), 300);
+    assert_eq!(next_deadline(100, 100, 450), 500);
+    assert_eq!(next_deadline(100, 100, 499), 500);
+}
+
+#[test]
+fn next_deadline_with_zero_period_does_not_divide() {
+    assert_eq!(next_deadline(100, 0, 500), 100);
+}
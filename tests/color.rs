@@ -0,0 +1,303 @@
+use std::collections::HashSet;
+
+use esp_flow::color::{
+    gradient, gradient_map, perceptual, wheel, wheel_dim, Hsv, Palette, Rgb,
+    BATTERY_GRADIENT, BLACK, BLUE, CYAN, DAYLIGHT_6500K, GREEN, MAGENTA, RED,
+    WARM_WHITE_2700K, YELLOW,
+};
+
+#[test]
+fn primaries_convert_to_hsv() {
+    assert_eq!(Rgb::new(255, 0, 0).to_hsv(), Hsv::new(0, 255, 255));
+    assert_eq!(Rgb::new(0, 255, 0).to_hsv(), Hsv::new(120, 255, 255));
+    assert_eq!(Rgb::new(0, 0, 255).to_hsv(), Hsv::new(240, 255, 255));
+    assert_eq!(Rgb::new(255, 0, 255).to_hsv(), Hsv::new(300, 255, 255));
+}
+
+#[test]
+fn grays_have_no_hue_nor_saturation() {
+    for level in [0, 1, 128, 255] {
+        assert_eq!(
+            Rgb::new(level, level, level).to_hsv(),
+            Hsv::new(0, 0, level)
+        );
+    }
+}
+
+#[test]
+fn named_colors_round_trip_through_hsv() {
+    for color in [RED, GREEN, BLUE, YELLOW, CYAN, MAGENTA] {
+        assert_eq!(Rgb::from(color.to_hsv()), color);
+    }
+}
+
+#[test]
+fn hues_round_trip_through_rgb() {
+    for hue in 0..360 {
+        let hsv = Rgb::from(Hsv::new(hue, 255, 255)).to_hsv();
+        let error =
+            (i32::from(hsv.h()) - i32::from(hue) + 180).rem_euclid(360) - 180;
+        assert!(error.abs() <= 1, "hue {hue} came back as {}", hsv.h());
+        assert_eq!((hsv.s(), hsv.v()), (255, 255));
+    }
+}
+
+#[test]
+fn from_hex_parses_long_and_short_forms() {
+    assert_eq!(Rgb::from_hex("#FF8800").unwrap(), Rgb::new(255, 136, 0));
+    assert_eq!(Rgb::from_hex("ff8800").unwrap(), Rgb::new(255, 136, 0));
+    assert_eq!(Rgb::from_hex("#F80").unwrap(), Rgb::new(255, 136, 0));
+    assert_eq!(Rgb::from_hex("0a1").unwrap(), Rgb::new(0, 170, 17));
+}
+
+#[test]
+fn from_hex_rejects_malformed_strings() {
+    for hex in ["", "#", "#FF88", "#FF88000", "#GG8800", "#+F8", "##F80"] {
+        assert!(Rgb::from_hex(hex).is_err(), "{hex:?} was accepted");
+    }
+}
+
+#[test]
+fn lerp_u8_spans_both_endpoints() {
+    let (from, to) = (Rgb::new(0, 100, 255), Rgb::new(255, 50, 0));
+    assert_eq!(from.lerp_u8(&to, 0), from);
+    assert_eq!(from.lerp_u8(&to, 255), to);
+    assert_eq!(from.lerp_u8(&to, 128), Rgb::new(128, 75, 127));
+}
+
+#[test]
+fn gradient_includes_endpoints() {
+    let colors: Vec<_> = gradient(BLACK, Rgb::new(255, 255, 255), 5).collect();
+    assert_eq!(
+        colors,
+        [0, 63, 127, 191, 255].map(|level| Rgb::new(level, level, level))
+    );
+}
+
+#[test]
+fn short_gradients() {
+    assert_eq!(gradient(RED, GREEN, 0).count(), 0);
+    assert_eq!(gradient(RED, GREEN, 1).collect::<Vec<_>>(), [RED]);
+    assert_eq!(gradient(RED, GREEN, 2).collect::<Vec<_>>(), [RED, GREEN]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn hsv_round_trips_through_serde() {
+    let hsv = Hsv::new(200, 128, 64);
+    let json = serde_json::to_string(&hsv).unwrap();
+    assert_eq!(json, r#"{"h":200,"s":128,"v":64}"#);
+    assert_eq!(serde_json::from_str::<Hsv>(&json).unwrap(), hsv);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialized_hsv_wraps_hue() {
+    let hsv: Hsv = serde_json::from_str(r#"{"h":400,"s":255,"v":255}"#).unwrap();
+    assert_eq!(hsv, Hsv::new(40, 255, 255));
+    assert_eq!(hsv.h(), 40);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn rgb_round_trips_through_serde() {
+    let json = serde_json::to_string(&Rgb::new(255, 136, 0)).unwrap();
+    assert_eq!(json, r##""#FF8800""##);
+    assert_eq!(
+        serde_json::from_str::<Rgb>(&json).unwrap(),
+        Rgb::new(255, 136, 0)
+    );
+    assert_eq!(
+        serde_json::from_str::<Rgb>(r#"{"r":1,"g":2,"b":3}"#).unwrap(),
+        Rgb::new(1, 2, 3)
+    );
+}
+
+#[test]
+fn wheel_goes_red_green_blue_red() {
+    assert_eq!(wheel(0), Rgb::new(255, 0, 0));
+    assert_eq!(wheel(85), Rgb::new(0, 255, 0));
+    assert_eq!(wheel(170), Rgb::new(0, 0, 255));
+    assert_eq!(wheel(255), Rgb::new(255, 0, 0));
+
+    assert_eq!(wheel(42), Rgb::new(129, 126, 0));
+    assert_eq!(wheel(127), Rgb::new(0, 129, 126));
+    assert_eq!(wheel(212), Rgb::new(126, 0, 129));
+}
+
+#[test]
+fn wheel_dim_scales_brightness() {
+    assert_eq!(wheel_dim(85, 0), BLACK);
+    assert_eq!(wheel_dim(85, 255), wheel(85));
+    assert_eq!(wheel_dim(85, 128), Rgb::new(0, 128, 0));
+}
+
+#[test]
+fn lerp_interpolates_and_clamps() {
+    let (from, to) = (Rgb::new(0, 100, 255), Rgb::new(255, 50, 0));
+    assert_eq!(from.lerp(&to, 0.0), from);
+    assert_eq!(from.lerp(&to, 1.0), to);
+    assert_eq!(from.lerp(&to, 0.5), Rgb::new(128, 75, 128));
+    assert_eq!(from.lerp(&to, 0.25), Rgb::new(64, 88, 191));
+    assert_eq!(from.lerp(&to, -1.0), from);
+    assert_eq!(from.lerp(&to, 2.0), to);
+}
+
+#[test]
+fn u32_conversions_use_the_grb_layout() {
+    let color = Rgb::new(1, 2, 4);
+    assert_eq!(u32::from(&color), 0x0002_0104);
+    assert_eq!(Rgb::from(0x0002_0104), color);
+    assert_eq!(Rgb::from(0xFF02_0104), color);
+}
+
+#[test]
+fn u32_conversions_round_trip() {
+    for color in [BLACK, RED, GREEN, BLUE, Rgb::new(12, 34, 56)] {
+        assert_eq!(Rgb::from(u32::from(&color)), color);
+    }
+}
+
+#[test]
+fn rgb_is_const_constructible() {
+    const CORAL: Rgb = Rgb::new(255, 127, 80);
+    const RED_CHANNEL: u8 = CORAL.r();
+    assert_eq!((RED_CHANNEL, CORAL.g(), CORAL.b()), (255, 127, 80));
+}
+
+#[test]
+fn rgb_derives_standard_traits() {
+    let colors: HashSet<Rgb> =
+        [RED, GREEN, RED, Rgb::new(0, 25, 0)].into_iter().collect();
+    assert_eq!(colors.len(), 2);
+    assert_ne!(RED, GREEN);
+    assert_eq!(
+        format!("{:?}", Rgb::new(1, 2, 3)),
+        "Rgb { r: 1, g: 2, b: 3 }"
+    );
+}
+
+// Tanner Helland's approximation in floating point, which `Rgb::from_kelvin`
+// implements in fixed point.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn kelvin_reference(kelvin: u16) -> [u8; 3] {
+    let temp = f64::from(kelvin) / 100.0;
+    let channel = |value: f64| value.round().clamp(0.0, 255.0) as u8;
+    let red = if temp <= 66.0 {
+        255.0
+    } else {
+        329.698_727_446 * (temp - 60.0).powf(-0.133_204_759_2)
+    };
+    let green = if temp <= 66.0 {
+        99.470_802_586_1 * temp.ln() - 161.119_568_166_1
+    } else {
+        288.122_169_528_3 * (temp - 60.0).powf(-0.075_514_849_2)
+    };
+    let blue = if temp >= 66.0 {
+        255.0
+    } else if temp <= 19.0 {
+        0.0
+    } else {
+        138.517_731_223_1 * (temp - 10.0).ln() - 305.044_792_730_7
+    };
+    [channel(red), channel(green), channel(blue)]
+}
+
+#[test]
+fn from_kelvin_tracks_the_floating_point_formula() {
+    for kelvin in (1000..=12_000).step_by(50) {
+        let color = Rgb::from_kelvin(kelvin);
+        let actual = [color.r(), color.g(), color.b()];
+        let expected = kelvin_reference(kelvin);
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(&a, e)| a.abs_diff(e) <= 1),
+            "{kelvin} K: {actual:?} instead of {expected:?}"
+        );
+    }
+}
+
+#[test]
+fn from_kelvin_clamps_and_matches_named_whites() {
+    assert_eq!(Rgb::from_kelvin(2700), WARM_WHITE_2700K);
+    assert_eq!(Rgb::from_kelvin(6500), DAYLIGHT_6500K);
+    assert_eq!(Rgb::from_kelvin(0), Rgb::from_kelvin(1000));
+    assert_eq!(Rgb::from_kelvin(u16::MAX), Rgb::from_kelvin(12_000));
+}
+
+#[test]
+fn palette_maps_temperatures() {
+    let mut palette = Palette::new(BLACK).with("on", GREEN);
+    palette.set_kelvin("warm", 2700);
+
+    assert_eq!(palette.color(&"warm"), WARM_WHITE_2700K);
+    assert_eq!(palette.color(&"on"), GREEN);
+    assert_eq!(palette.color(&"unmapped"), BLACK);
+    assert!(palette.validate(&["on", "warm"]).is_ok());
+    assert!(palette.validate(&["on", "off"]).is_err());
+}
+
+#[test]
+fn arithmetic_saturates() {
+    let color = Rgb::new(200, 100, 0);
+    assert_eq!(color + Rgb::new(100, 100, 100), Rgb::new(255, 200, 100));
+    assert_eq!(color * 2, Rgb::new(255, 200, 0));
+    assert_eq!(color * 0.5, Rgb::new(100, 50, 0));
+    assert_eq!(color * -1.0, BLACK);
+    assert_eq!(color * 3.0, Rgb::new(255, 255, 0));
+    assert_eq!(color.dimmed(2.0), color);
+    assert_eq!(Rgb::new(3, 5, 7).dimmed(0.5), Rgb::new(2, 3, 4));
+}
+
+#[test]
+fn blend_composites_by_alpha() {
+    let base = Rgb::new(0, 0, 200);
+    assert_eq!(base.blend(&RED, 0), base);
+    assert_eq!(base.blend(&Rgb::new(255, 0, 0), 255), Rgb::new(255, 0, 0));
+    assert_eq!(base.blend(&Rgb::new(255, 0, 0), 51), Rgb::new(51, 0, 160));
+}
+
+#[test]
+fn perceptual_curve_is_monotonic() {
+    assert_eq!(perceptual(0), 0);
+    assert_eq!(perceptual(128), 47);
+    assert_eq!(perceptual(255), 255);
+    assert!((1..=255).all(|level| perceptual(level) >= perceptual(level - 1)));
+    assert!((0..21).all(|level| perceptual(level) <= 3));
+}
+
+#[test]
+fn at_level_dims_along_the_curve() {
+    let color = Rgb::new(255, 128, 0);
+    assert_eq!(color.at_level(0), BLACK);
+    assert_eq!(color.at_level(255), color);
+    assert_eq!(color.at_level(128), Rgb::new(47, 24, 0));
+}
+
+#[test]
+fn gradient_map_interpolates_between_stops() {
+    assert_eq!(gradient_map(0, &BATTERY_GRADIENT).unwrap(), RED);
+    assert_eq!(gradient_map(50, &BATTERY_GRADIENT).unwrap(), YELLOW);
+    assert_eq!(gradient_map(100, &BATTERY_GRADIENT).unwrap(), GREEN);
+    assert_eq!(
+        gradient_map(75, &BATTERY_GRADIENT).unwrap(),
+        YELLOW.lerp_u8(&GREEN, 127)
+    );
+}
+
+#[test]
+fn gradient_map_clamps_outside_stops() {
+    let stops = [(10, RED), (20, BLUE)];
+    assert_eq!(gradient_map(0, &stops).unwrap(), RED);
+    assert_eq!(gradient_map(255, &stops).unwrap(), BLUE);
+    assert_eq!(gradient_map(42, &[(7, GREEN)]).unwrap(), GREEN);
+}
+
+#[test]
+fn gradient_map_rejects_invalid_stops() {
+    assert!(gradient_map(0, &[]).is_err());
+    assert!(gradient_map(0, &[(20, RED), (10, BLUE)]).is_err());
+    assert!(gradient_map(0, &[(10, RED), (10, BLUE)]).is_err());
+}
@@ -0,0 +1,873 @@
+use esp_flow::{
+    error::Error,
+    gps::{
+        self,
+        ubx::{self, Frame, Parser, NAV_PVT},
+        Config, Decoder, MotionDetector, NmeaSource, Reading, ReplaySource,
+        SpeedSummary, SpeedTracker, Units, DEFAULT_MIN_CLIMB_M,
+        DEFAULT_MIN_MOVING_MPS, ENCODED_LEN,
+    },
+    infra,
+};
+use nmea::sentences::FixType;
+use std::{thread, time::Duration};
+
+// A UBX-NAV-PVT frame: 3D fix with 9 satellites at 48.8584 N, 2.2945 E,
+// 35 m above mean sea level, moving at 5.23 m/s towards 123.45678°, on
+// 2024-05-17 at 09:41:27 UTC.
+const NAV_PVT_FRAME: [u8; 100] = [
+    0xB5, 0x62, 0x01, 0x07, 0x5C, 0x00, 0xA8, 0x53, 0x61, 0x0C, 0xE8, 0x07, 0x05,
+    0x11, 0x09, 0x29, 0x1B, 0x37, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x03, 0x01, 0xEA, 0x09, 0xE8, 0x1C, 0x5E, 0x01, 0x40, 0x33, 0x1F, 0x1D, 0x50,
+    0x40, 0x01, 0x00, 0xB8, 0x88, 0x00, 0x00, 0xC4, 0x09, 0x00, 0x00, 0xD8, 0x0E,
+    0x00, 0x00, 0x1C, 0x0C, 0x00, 0x00, 0x68, 0x10, 0x00, 0x00, 0x88, 0xFF, 0xFF,
+    0xFF, 0x6E, 0x14, 0x00, 0x00, 0x4E, 0x61, 0xBC, 0x00, 0x58, 0x02, 0x00, 0x00,
+    0xF0, 0x49, 0x02, 0x00, 0x91, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1A, 0x8A,
+];
+
+// Offset of the fix type in a NAV-PVT payload.
+const FIX_TYPE_OFFSET: usize = 20;
+
+// Builds an NMEA sentence from its body, adding the checksum and line ending.
+fn sentence(body: &str) -> String {
+    let checksum = body.bytes().fold(0, |checksum, byte| checksum ^ byte);
+    format!("${body}*{checksum:02X}\r\n")
+}
+
+// The GGA sentence of a fix at 12:00:SS UTC, with the given HDOP.
+fn gga(second: u32, hdop: f32) -> String {
+    sentence(&format!(
+        "GPGGA,1200{second:02}.00,4851.3960,N,00221.1320,E,1,08,{hdop:.1},35.0,M,47.0,M,,"
+    ))
+}
+
+// The RMC sentence of a fix at 12:00:SS UTC on 2024-06-15, at 10 knots.
+fn rmc(second: u32) -> String {
+    sentence(&format!(
+        "GPRMC,1200{second:02}.00,A,4851.3960,N,00221.1320,E,10.00,45.0,150624,,,A"
+    ))
+}
+
+fn decoder() -> Decoder {
+    Decoder::new(&Config::new(9600, 22, None).unwrap())
+}
+
+fn nav_pvt_frame() -> Frame {
+    let mut frames = Parser::default().push(&NAV_PVT_FRAME);
+    assert_eq!(frames.len(), 1);
+    frames.remove(0).unwrap()
+}
+
+fn assert_gps_parse(error: &anyhow::Error) {
+    assert!(
+        matches!(error.downcast_ref::<Error>(), Some(Error::GpsParse(_))),
+        "{error:#}"
+    );
+}
+
+#[test]
+fn checksum_matches_frame() {
+    let end = NAV_PVT_FRAME.len() - 2;
+    assert_eq!(ubx::checksum(&NAV_PVT_FRAME[2..end]), [0x1A, 0x8A]);
+    assert_eq!(ubx::checksum(&[]), [0, 0]);
+}
+
+#[test]
+fn frame_round_trips_through_bytes() {
+    let frame = nav_pvt_frame();
+
+    assert_eq!((frame.class(), frame.id()), NAV_PVT);
+    assert_eq!(frame.payload().len(), 92);
+    assert_eq!(frame.to_bytes(), NAV_PVT_FRAME);
+}
+
+#[test]
+fn nav_pvt_decodes_fix() {
+    let reading = ubx::decode_nav_pvt(&nav_pvt_frame()).unwrap().unwrap();
+
+    assert!((reading.latitude() - 48.8584).abs() < 1e-7);
+    assert!((reading.longitude() - 2.2945).abs() < 1e-7);
+    assert!((reading.speed_mps().unwrap() - 5.23).abs() < 1e-3);
+    assert!((reading.course_deg().unwrap() - 123.456_78).abs() < 1e-3);
+    assert_eq!(reading.altitude(), Some(35.0));
+    assert_eq!(reading.fix_type(), Some(FixType::Gps));
+    assert_eq!(reading.num_satellites(), Some(9));
+    assert_eq!(reading.hdop(), None);
+    assert_eq!(reading.utc_s(), Some(1_715_938_887));
+}
+
+#[test]
+fn nav_pvt_without_fix_decodes_to_none() {
+    let mut payload = nav_pvt_frame().payload().to_vec();
+    payload[FIX_TYPE_OFFSET] = 0;
+    let frame = Frame::new(NAV_PVT.0, NAV_PVT.1, payload).unwrap();
+
+    assert!(ubx::decode_nav_pvt(&frame).unwrap().is_none());
+}
+
+#[test]
+fn nav_pvt_rejects_other_and_truncated_messages() {
+    let other = Frame::cfg_rate(200);
+    let truncated = Frame::new(
+        NAV_PVT.0,
+        NAV_PVT.1,
+        nav_pvt_frame().payload()[..91].to_vec(),
+    )
+    .unwrap();
+
+    assert_gps_parse(&ubx::decode_nav_pvt(&other).unwrap_err());
+    assert_gps_parse(&ubx::decode_nav_pvt(&truncated).unwrap_err());
+}
+
+#[test]
+fn parser_joins_split_frames() {
+    let mut parser = Parser::default();
+
+    for chunk in NAV_PVT_FRAME[..99].chunks(7) {
+        assert!(parser.push(chunk).is_empty());
+    }
+    let frames = parser.push(&NAV_PVT_FRAME[99..]);
+
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].as_ref().unwrap(), &nav_pvt_frame());
+}
+
+#[test]
+fn parser_skips_bytes_between_frames() {
+    let mut bytes = b"$GPTXT,01,01,02,u-blox ag*50\r\n".to_vec();
+    bytes.extend_from_slice(&NAV_PVT_FRAME);
+    bytes.extend_from_slice(&[0x00, 0xB5]);
+    bytes.extend_from_slice(&NAV_PVT_FRAME);
+
+    let frames = Parser::default().push(&bytes);
+
+    assert_eq!(frames.len(), 2);
+    assert!(frames.iter().all(Result::is_ok));
+}
+
+#[test]
+fn parser_reports_corrupt_frames_and_resyncs() {
+    let mut corrupt = NAV_PVT_FRAME;
+    corrupt[40] ^= 0xFF;
+    let mut bytes = corrupt.to_vec();
+    bytes.extend_from_slice(&NAV_PVT_FRAME);
+
+    let frames = Parser::default().push(&bytes);
+
+    assert_eq!(frames.len(), 2);
+    assert_gps_parse(frames[0].as_ref().unwrap_err());
+    assert_eq!(frames[1].as_ref().unwrap(), &nav_pvt_frame());
+}
+
+#[test]
+fn parser_rejects_oversized_lengths() {
+    let mut parser = Parser::default();
+    let frames = parser.push(&[0xB5, 0x62, 0x01, 0x07, 0xFF, 0xFF]);
+
+    assert_eq!(frames.len(), 1);
+    assert_gps_parse(frames[0].as_ref().unwrap_err());
+    assert_eq!(parser.push(&NAV_PVT_FRAME).len(), 1);
+}
+
+#[test]
+fn speeds_convert_from_knots() {
+    let reading = Reading::new(0.0, 0.0).with_motion(Some(10.0), None);
+
+    assert_eq!(reading.speed_knots(), Some(10.0));
+    assert!((reading.speed_mps().unwrap() - 5.144_44).abs() < 1e-4);
+    assert_eq!(reading.speed_ms(), reading.speed_mps());
+    assert!((reading.speed_kmh().unwrap() - 18.52).abs() < 1e-4);
+    assert!((reading.speed_mph().unwrap() - 11.507_79).abs() < 1e-4);
+}
+
+#[test]
+fn format_with_uses_display_units() {
+    let reading = Reading::new(48.8584, 2.2945).with_motion(Some(10.0), None);
+
+    assert_eq!(
+        reading.format_with(Units::Metric),
+        "Lat: 48.8584, Lon: 2.2945, Speed: 18.52 km/h"
+    );
+    assert_eq!(
+        reading.format_with(Units::Imperial),
+        "Lat: 48.8584, Lon: 2.2945, Speed: 11.51 mph"
+    );
+}
+
+#[test]
+fn unknown_speed_stays_unknown() {
+    let reading = Reading::new(48.8584, 2.2945);
+
+    assert_eq!(reading.speed_knots(), None);
+    assert_eq!(reading.speed_mps(), None);
+    assert_eq!(reading.speed_kmh(), None);
+    assert_eq!(reading.speed_mph(), None);
+    for units in [Units::Metric, Units::Imperial] {
+        assert_eq!(
+            reading.format_with(units),
+            "Lat: 48.8584, Lon: 2.2945, Speed: N/A"
+        );
+    }
+}
+
+#[test]
+fn geohash_matches_known_positions() {
+    assert_eq!(
+        Reading::new(57.649_11, 10.407_44).geohash(11),
+        "u4pruydqqvj"
+    );
+    assert_eq!(Reading::new(-33.856_8, 151.215_3).geohash(9), "r3gx2ux9g");
+}
+
+#[test]
+fn geohash_is_empty_at_precision_zero() {
+    assert_eq!(Reading::new(48.858_4, 2.294_5).geohash(0), "");
+}
+
+#[test]
+fn geohash_caps_precision_to_12() {
+    let reading = Reading::new(48.858_4, 2.294_5);
+
+    assert_eq!(reading.geohash(12), "u09tunquc9zh");
+    assert_eq!(reading.geohash(usize::MAX), reading.geohash(12));
+    assert!(reading.geohash(12).starts_with(&reading.geohash(5)));
+}
+
+fn assert_round_trips(reading: &Reading) {
+    let decoded = Reading::from_bytes(&reading.to_bytes()).unwrap();
+
+    assert!((decoded.latitude() - reading.latitude()).abs() < 1e-7);
+    assert!((decoded.longitude() - reading.longitude()).abs() < 1e-7);
+    assert_eq!(decoded.altitude(), reading.altitude());
+    assert_eq!(decoded.utc_s(), reading.utc_s());
+    match (decoded.speed_mps(), reading.speed_mps()) {
+        (Some(decoded), Some(speed)) => assert!((decoded - speed).abs() < 0.01),
+        (decoded, speed) => assert_eq!(decoded, speed),
+    }
+}
+
+#[test]
+fn reading_round_trips_through_bytes() {
+    let reading = Reading::new(48.858_4, 2.294_5)
+        .with_motion(Some(10.0), None)
+        .with_altitude(Some(35.0))
+        .with_utc(Some(1_715_938_887));
+
+    assert_eq!(reading.to_bytes().len(), ENCODED_LEN);
+    assert_round_trips(&reading);
+}
+
+#[test]
+fn reading_round_trips_extreme_coordinates() {
+    for (latitude, longitude) in [
+        (90.0, 180.0),
+        (-90.0, -180.0),
+        (90.0, -180.0),
+        (-90.0, 180.0),
+    ] {
+        assert_round_trips(
+            &Reading::new(latitude, longitude).with_motion(Some(1.0), None),
+        );
+    }
+}
+
+#[test]
+fn reading_round_trips_unknown_values() {
+    let reading = Reading::new(-33.856_8, 151.215_3);
+    let decoded = Reading::from_bytes(&reading.to_bytes()).unwrap();
+
+    assert_round_trips(&reading);
+    assert_eq!(decoded.speed_mps(), None);
+    assert_eq!(decoded.altitude(), None);
+    assert_eq!(decoded.utc_s(), None);
+}
+
+#[test]
+fn reading_from_bytes_rejects_invalid_encodings() {
+    let bytes = Reading::new(48.858_4, 2.294_5).to_bytes();
+
+    assert!(Reading::from_bytes(&bytes[..ENCODED_LEN - 1]).is_err());
+    assert!(Reading::from_bytes(&[]).is_err());
+    let mut version = bytes;
+    version[0] = 0;
+    assert!(Reading::from_bytes(&version).is_err());
+    for i in 1..ENCODED_LEN {
+        let mut corrupted = bytes;
+        corrupted[i] ^= 0x01;
+        assert!(Reading::from_bytes(&corrupted).is_err(), "flipped byte {i}");
+    }
+}
+
+#[test]
+fn reading_to_json_has_the_upload_shape() {
+    let reading = Reading::new(48.858_4, 2.294_5)
+        .with_motion(Some(10.0), None)
+        .with_altitude(Some(35.0))
+        .with_utc(Some(1_700_000_000));
+    let json = reading.to_json();
+
+    assert_eq!(
+        json,
+        r#"{"lat":48.8584000,"lon":2.2945000,"alt":35.0,"ts":1700000000,"speed_kmh":18.52}"#
+    );
+    serde_json::from_str::<serde_json::Value>(&json).unwrap();
+}
+
+#[test]
+fn reading_to_json_writes_unknown_values_as_null() {
+    let json = Reading::new(-33.856_8, 151.215_3).to_json();
+
+    assert_eq!(
+        json,
+        r#"{"lat":-33.8568000,"lon":151.2153000,"alt":null,"ts":null,"speed_kmh":null}"#
+    );
+    serde_json::from_str::<serde_json::Value>(&json).unwrap();
+}
+
+#[test]
+fn speed_summary_to_json_has_the_upload_shape() {
+    let json = SpeedSummary::default().to_json();
+
+    assert_eq!(
+        json,
+        r#"{"max_speed_kmh":0.00,"avg_speed_kmh":0.00,"distance_m":0.0,"moving_s":0,"gain_m":0.0,"loss_m":0.0,"heading_deg":null}"#
+    );
+    serde_json::from_str::<serde_json::Value>(&json).unwrap();
+}
+
+#[test]
+fn decoder_emits_one_reading_per_epoch() {
+    let mut decoder = decoder();
+
+    assert!(decoder.push(gga(0, 0.9).as_bytes()).is_none());
+    let reading = decoder.push(rmc(0).as_bytes()).unwrap();
+    assert!((reading.latitude() - 48.8566).abs() < 1e-6);
+    assert_eq!(reading.speed_knots(), Some(10.0));
+    assert_eq!(reading.altitude(), Some(35.0));
+    assert_eq!(reading.hdop(), Some(0.9));
+    assert_eq!(reading.utc_s(), Some(1_718_452_800));
+
+    assert!(decoder.push(gga(1, 0.9).as_bytes()).is_none());
+    assert!(decoder.push(rmc(1).as_bytes()).is_some());
+}
+
+#[test]
+fn decoder_emits_partial_epochs_when_the_next_one_starts() {
+    let mut decoder = decoder();
+
+    assert!(decoder.push(rmc(0).as_bytes()).is_none());
+    let reading = decoder.push(rmc(1).as_bytes()).unwrap();
+    assert_eq!(reading.utc_s(), Some(1_718_452_800));
+}
+
+#[test]
+fn decoder_returns_the_last_reading_of_a_burst() {
+    let burst = [gga(0, 0.9), rmc(0), gga(1, 0.9), rmc(1)].concat();
+
+    let reading = decoder().push(burst.as_bytes()).unwrap();
+    assert_eq!(reading.utc_s(), Some(1_718_452_801));
+}
+
+#[test]
+fn decoder_counts_hdop_rejections_once_per_epoch() {
+    let mut decoder = decoder();
+    let stats = decoder.stats();
+
+    assert!(decoder.push(gga(0, 9.9).as_bytes()).is_none());
+    assert!(decoder.push(rmc(0).as_bytes()).is_none());
+    assert_eq!(infra::lock(&stats).hdop_rejections(), 1);
+}
+
+#[test]
+fn decoder_applies_max_hdop_changes_through_its_handle() {
+    let mut decoder = decoder();
+    *infra::lock(&decoder.max_hdop()) = 10.0;
+
+    decoder.push(gga(0, 9.9).as_bytes());
+    assert!(decoder.push(rmc(0).as_bytes()).is_some());
+    assert_eq!(infra::lock(&decoder.stats()).hdop_rejections(), 0);
+}
+
+// A mock reader handing out a byte stream a few bytes at a time, splitting
+// sentences across reads like a UART does.
+struct ChunkedReader {
+    bytes: Vec<u8>,
+    chunk_len: usize,
+}
+
+impl NmeaSource for ChunkedReader {
+    fn read(&mut self, buf: &mut [u8], _timeout: u32) -> anyhow::Result<usize> {
+        let n = self.chunk_len.min(buf.len()).min(self.bytes.len());
+        buf[..n].copy_from_slice(&self.bytes[..n]);
+        self.bytes.drain(..n);
+        Ok(n)
+    }
+}
+
+// Pushes the given number of reads from the source into the decoder.
+fn decode_reads(
+    source: &mut impl NmeaSource,
+    decoder: &mut Decoder,
+    reads: usize,
+) -> Vec<Reading> {
+    let mut buf = [0; 64];
+    (0..reads)
+        .filter_map(|_| {
+            let n = source.read(&mut buf, 0).unwrap();
+            decoder.push(&buf[..n])
+        })
+        .collect()
+}
+
+// Two fixes, a second apart, as a recorded ride would hold them.
+const REPLAY: &[u8] = b"\
+$GPGGA,120000.00,4851.3960,N,00221.1320,E,1,08,0.9,35.0,M,47.0,M,,*5E\r\n\
+$GPRMC,120000.00,A,4851.3960,N,00221.1320,E,10.00,45.0,150624,,,A*6C\r\n\
+$GPGGA,120001.00,4851.3960,N,00221.1320,E,1,08,0.9,35.0,M,47.0,M,,*5F\r\n\
+$GPRMC,120001.00,A,4851.3960,N,00221.1320,E,10.00,45.0,150624,,,A*6D\r\n";
+
+#[test]
+fn replay_source_feeds_the_decoder() {
+    let mut decoder = decoder();
+    let stats = decoder.stats();
+    // Each line is longer than the read buffer, so takes two reads
+    let readings = decode_reads(&mut ReplaySource::new(REPLAY, 0), &mut decoder, 8);
+
+    assert_eq!(readings.len(), 2);
+    assert_eq!(readings[0].utc_s(), Some(1_718_452_800));
+    assert_eq!(readings[1].utc_s(), Some(1_718_452_801));
+    assert!((readings[1].latitude() - 48.8566).abs() < 1e-6);
+    assert_eq!(readings[1].altitude(), Some(35.0));
+    assert_eq!(readings[1].speed_knots(), Some(10.0));
+    let stats = infra::lock(&stats);
+    assert_eq!(stats.lines(), 4);
+    assert_eq!(stats.checksum_failures(), 0);
+    assert_eq!(stats.parse_failures(), 0);
+}
+
+#[test]
+fn replay_source_starts_over_once_read() {
+    let readings =
+        decode_reads(&mut ReplaySource::new(REPLAY, 0), &mut decoder(), 16);
+
+    let utc_s: Vec<_> = readings.iter().map(Reading::utc_s).collect();
+    assert_eq!(
+        utc_s,
+        [1_718_452_800, 1_718_452_801, 1_718_452_800, 1_718_452_801].map(Some)
+    );
+}
+
+#[test]
+fn decoder_combines_gga_and_rmc_split_across_reads() {
+    let bytes = [gga(0, 0.9), rmc(0), gga(1, 0.9), rmc(1)].concat();
+    let reads = bytes.len().div_ceil(7);
+    let mut reader = ChunkedReader {
+        bytes: bytes.into_bytes(),
+        chunk_len: 7,
+    };
+
+    let readings = decode_reads(&mut reader, &mut decoder(), reads);
+    assert_eq!(readings.len(), 2);
+    for (reading, utc_s) in readings.iter().zip([1_718_452_800, 1_718_452_801]) {
+        assert_eq!(reading.altitude(), Some(35.0));
+        assert_eq!(reading.hdop(), Some(0.9));
+        assert_eq!(reading.speed_knots(), Some(10.0));
+        assert_eq!(reading.utc_s(), Some(utc_s));
+    }
+}
+
+#[test]
+fn decoder_drops_sentences_with_a_bad_checksum() {
+    let mut decoder = decoder();
+    let stats = decoder.stats();
+    // The body changes but the checksum does not
+    let corrupted = gga(0, 0.9).replace(",E,", ",W,");
+    let unchecked = gga(0, 0.9).split('*').next().unwrap().to_string() + "\r\n";
+
+    assert!(decoder.push(corrupted.as_bytes()).is_none());
+    assert!(decoder.push(unchecked.as_bytes()).is_none());
+    decoder.push(rmc(0).as_bytes());
+    let reading = decoder.push(rmc(1).as_bytes()).unwrap();
+
+    // Only the RMC sentence made it into the reading
+    assert_eq!(reading.altitude(), None);
+    assert!(reading.longitude() > 0.0);
+    let stats = infra::lock(&stats);
+    assert_eq!(stats.lines(), 4);
+    assert_eq!(stats.checksum_failures(), 2);
+    assert_eq!(stats.parse_failures(), 0);
+}
+
+fn at_kmh(kmh: f32) -> Reading {
+    Reading::new(48.858_4, 2.294_5).with_motion(Some(kmh / 1.852), None)
+}
+
+#[test]
+fn motion_starts_after_consecutive_fast_readings() {
+    let mut detector = MotionDetector::new().with_start(2.0, 3).unwrap();
+
+    assert_eq!(detector.update(&at_kmh(5.0)), None);
+    assert_eq!(detector.update(&at_kmh(5.0)), None);
+    assert_eq!(detector.update(&at_kmh(1.0)), None);
+    assert_eq!(detector.update(&at_kmh(5.0)), None);
+    assert_eq!(detector.update(&at_kmh(5.0)), None);
+    assert!(!detector.is_moving());
+    assert_eq!(detector.update(&at_kmh(5.0)), Some(true));
+    assert!(detector.is_moving());
+    assert_eq!(detector.update(&at_kmh(5.0)), None);
+}
+
+#[test]
+fn motion_stops_after_consecutive_slow_readings() {
+    let mut detector = MotionDetector::new()
+        .with_start(2.0, 1)
+        .unwrap()
+        .with_stop(1.0, 2)
+        .unwrap();
+
+    assert_eq!(detector.update(&at_kmh(5.0)), Some(true));
+    // Between the stop and start speeds, neither counts
+    assert_eq!(detector.update(&at_kmh(1.5)), None);
+    assert_eq!(detector.update(&at_kmh(0.5)), None);
+    assert_eq!(detector.update(&at_kmh(1.5)), None);
+    assert_eq!(detector.update(&at_kmh(0.5)), None);
+    assert_eq!(detector.update(&at_kmh(0.5)), Some(false));
+    assert!(!detector.is_moving());
+}
+
+#[test]
+fn motion_ignores_readings_without_speed() {
+    let mut detector = MotionDetector::new().with_start(2.0, 2).unwrap();
+
+    assert_eq!(detector.update(&at_kmh(5.0)), None);
+    assert_eq!(detector.update(&Reading::new(48.858_4, 2.294_5)), None);
+    assert_eq!(detector.update(&at_kmh(5.0)), Some(true));
+}
+
+#[test]
+fn motion_detector_rejects_zero_readings() {
+    assert!(MotionDetector::new().with_start(2.0, 0).is_err());
+    assert!(MotionDetector::new().with_stop(1.0, 0).is_err());
+}
+
+// Readings moving north by about 3.3 m, each at the given speed and taken
+// 50 ms after the previous one, slow enough not to be taken for glitches.
+fn northbound(speeds_kmh: &[f32]) -> Vec<Reading> {
+    speeds_kmh
+        .iter()
+        .zip(0_u8..)
+        .map(|(&kmh, i)| {
+            if i > 0 {
+                thread::sleep(Duration::from_millis(50));
+            }
+            Reading::new(48.858_4 + f64::from(i) * 0.000_03, 2.294_5)
+                .with_motion(Some(kmh / 1.852), None)
+        })
+        .collect()
+}
+
+#[test]
+fn speed_tracker_accumulates_distance_and_speeds() {
+    let mut tracker = SpeedTracker::default();
+    let readings = northbound(&[10.0, 12.0, 15.0, 11.0, 10.0]);
+    for reading in &readings {
+        tracker.update(reading);
+    }
+
+    let (first, last) = (&readings[0], &readings[readings.len() - 1]);
+    let summary = tracker.summary();
+    assert!(
+        (summary.max_speed_mps() - 15.0 / 3.6).abs() < 1e-3,
+        "{summary}"
+    );
+    assert!(
+        (summary.distance_m() - gps::haversine(first, last)).abs() < 1e-6,
+        "{summary}"
+    );
+    assert_eq!(
+        summary.moving_time_ms(),
+        last.timestamp_ms() - first.timestamp_ms()
+    );
+    #[allow(clippy::cast_precision_loss)]
+    let average_mps =
+        summary.distance_m() * 1000.0 / summary.moving_time_ms() as f64;
+    assert!(
+        (f64::from(summary.average_speed_mps()) - average_mps).abs() < 1e-3,
+        "{summary}"
+    );
+}
+
+#[test]
+fn speed_tracker_ignores_stationary_jitter() {
+    let mut tracker = SpeedTracker::default();
+
+    // A receiver standing still reports small speeds and positions wandering
+    // by about a meter
+    for i in 0..20_u8 {
+        let offset = if i % 2 == 0 { 0.000_005 } else { -0.000_005 };
+        let kmh = if i % 3 == 0 { 0.3 } else { 0.0 };
+        tracker.update(
+            &Reading::new(48.858_4 + offset, 2.294_5 - offset)
+                .with_motion(Some(kmh / 1.852), None),
+        );
+    }
+
+    let summary = tracker.summary();
+    assert!(summary.distance_m().abs() < f64::EPSILON, "{summary}");
+    assert_eq!(summary.moving_time_ms(), 0);
+    assert!(
+        summary.average_speed_mps().abs() < f32::EPSILON,
+        "{summary}"
+    );
+    assert!(
+        summary.max_speed_mps() < DEFAULT_MIN_MOVING_MPS,
+        "{summary}"
+    );
+    assert_eq!(summary.heading_deg(), None);
+}
+
+#[test]
+fn speed_tracker_does_not_count_stops() {
+    let mut tracker = SpeedTracker::default();
+    let mut readings = northbound(&[10.0, 10.0, 10.0]);
+    thread::sleep(Duration::from_millis(50));
+    // Standing at the last position, then moving on
+    readings.push(readings[2].clone().with_motion(Some(0.0), None));
+    for reading in &readings {
+        tracker.update(reading);
+    }
+
+    let summary = tracker.summary();
+    assert_eq!(
+        summary.moving_time_ms(),
+        readings[2].timestamp_ms() - readings[0].timestamp_ms()
+    );
+    assert!(
+        (summary.distance_m() - gps::haversine(&readings[0], &readings[2])).abs()
+            < 1e-6,
+        "{summary}"
+    );
+}
+
+#[test]
+fn speed_tracker_reset_forgets_everything() {
+    let mut tracker = SpeedTracker::default();
+    for reading in &northbound(&[10.0, 15.0]) {
+        tracker.update(reading);
+    }
+    assert!(tracker.summary().distance_m() > 0.0);
+
+    tracker.reset();
+    assert_eq!(tracker.summary(), SpeedSummary::default());
+}
+
+#[test]
+fn haversine_matches_landmark_distances() {
+    // Geodesic distances on the WGS 84 ellipsoid, which the spherical
+    // haversine formula approximates
+    let cases = [
+        // Empire State Building to Times Square
+        ((40.748_4, -73.985_7), (40.758_0, -73.985_5), 1066.2),
+        // Louvre Pyramid to Notre-Dame de Paris
+        ((48.861_0, 2.335_8), (48.853_0, 2.349_9), 1364.6),
+    ];
+
+    for ((lat_a, lon_a), (lat_b, lon_b), expected_m) in cases {
+        let (a, b) = (Reading::new(lat_a, lon_a), Reading::new(lat_b, lon_b));
+        let distance_m = gps::haversine(&a, &b);
+        assert!(
+            (distance_m - expected_m).abs() / expected_m < 0.005,
+            "{distance_m} m, expected {expected_m} m"
+        );
+        assert!((gps::haversine(&b, &a) - distance_m).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn haversine_is_zero_between_identical_positions() {
+    let reading = Reading::new(48.858_4, 2.294_5);
+
+    assert!(gps::haversine(&reading, &reading).abs() < f64::EPSILON);
+}
+
+#[test]
+fn speed_tracker_skips_glitch_jumps() {
+    let mut tracker = SpeedTracker::default();
+    let before = northbound(&[10.0, 10.0, 10.0]);
+    thread::sleep(Duration::from_millis(5));
+    // About 50 km away in a few milliseconds
+    let glitch = Reading::new(49.3, 2.294_5).with_motion(Some(10.0 / 1.852), None);
+    thread::sleep(Duration::from_millis(5));
+    let after = northbound(&[10.0, 10.0]);
+    for reading in before.iter().chain([&glitch]).chain(&after) {
+        tracker.update(reading);
+    }
+
+    // The steps to and from the glitch do not count
+    let expected_m = gps::haversine(&before[0], &before[2])
+        + gps::haversine(&after[0], &after[1]);
+    let summary = tracker.summary();
+    assert!(
+        (summary.distance_m() - expected_m).abs() < 1e-6,
+        "{summary}"
+    );
+}
+
+#[test]
+fn bearing_points_to_cardinal_directions() {
+    let origin = Reading::new(0.0, 0.0);
+    let cases = [
+        (Reading::new(1.0, 0.0), 0.0),
+        (Reading::new(0.0, 1.0), 90.0),
+        (Reading::new(-1.0, 0.0), 180.0),
+        (Reading::new(0.0, -1.0), 270.0),
+        (Reading::new(0.0, 0.0), 0.0),
+    ];
+
+    for (destination, expected_deg) in cases {
+        let bearing_deg = gps::bearing(&origin, &destination);
+        assert!(
+            (bearing_deg - expected_deg).abs() < 1e-9,
+            "{bearing_deg}°, expected {expected_deg}°"
+        );
+    }
+}
+
+#[test]
+fn bearing_follows_the_great_circle() {
+    let eiffel_tower = Reading::new(48.858_4, 2.294_5);
+    let big_ben = Reading::new(51.500_7, -0.124_6);
+
+    // The initial bearings differ each way, unlike on a flat map
+    for (a, b, expected_deg) in [
+        (&eiffel_tower, &big_ben, 330.54),
+        (&big_ben, &eiffel_tower, 148.68),
+    ] {
+        let bearing_deg = gps::bearing(a, b);
+        assert!(
+            (bearing_deg - expected_deg).abs() < 0.01,
+            "{bearing_deg}°, expected {expected_deg}°"
+        );
+    }
+}
+
+#[test]
+fn speed_tracker_follows_the_heading() {
+    let mut tracker = SpeedTracker::default();
+    for reading in &northbound(&[10.0, 10.0, 10.0]) {
+        tracker.update(reading);
+    }
+
+    let heading_deg = tracker.summary().heading_deg().unwrap();
+    assert!(
+        heading_deg.min(360.0 - heading_deg) < 1e-6,
+        "{heading_deg}°"
+    );
+    let change_deg = tracker.heading_change_since(350.0).unwrap();
+    assert!((change_deg - 10.0).abs() < 1e-6, "{change_deg}°");
+    let change_deg = tracker.heading_change_since(90.0).unwrap();
+    assert!((change_deg + 90.0).abs() < 1e-6, "{change_deg}°");
+}
+
+fn at_altitude(latitude: f64, altitude_m: f32) -> Reading {
+    Reading::new(latitude, 2.294_5)
+        .with_motion(Some(0.0), None)
+        .with_altitude(Some(altitude_m))
+}
+
+#[test]
+fn elevation_ignores_sawtooth_noise() {
+    let mut tracker = SpeedTracker::default();
+
+    for i in 0..20 {
+        tracker.update(&at_altitude(
+            48.858_4,
+            if i % 2 == 0 { 100.0 } else { 101.0 },
+        ));
+    }
+
+    let summary = tracker.summary();
+    assert!(summary.elevation_gain_m().abs() < f64::EPSILON, "{summary}");
+    assert!(summary.elevation_loss_m().abs() < f64::EPSILON, "{summary}");
+}
+
+#[test]
+fn elevation_tracks_sawtooth_climbs_and_descents() {
+    let mut tracker = SpeedTracker::default();
+    // Climbs 20 m then descends 20 m in 1 m steps, with 1 m of noise on the
+    // way, twice
+    let profile = (0..20_u8).chain((1..=20).rev()).cycle().take(80);
+
+    for (i, step) in profile.chain([0]).enumerate() {
+        let noise_m = if i % 2 == 0 { 0.5 } else { -0.5 };
+        tracker.update(&at_altitude(48.858_4, 100.0 + f32::from(step) + noise_m));
+    }
+
+    // Both ends of each of the two climbs and descents may come short by
+    // the noise floor
+    let tolerance_m = 4.0 * DEFAULT_MIN_CLIMB_M;
+    let summary = tracker.summary();
+    assert!(
+        (summary.elevation_gain_m() - 40.0).abs() <= tolerance_m,
+        "{summary}"
+    );
+    assert!(
+        (summary.elevation_loss_m() - 40.0).abs() <= tolerance_m,
+        "{summary}"
+    );
+}
+
+#[test]
+fn elevation_ignores_glitch_readings() {
+    let mut tracker = SpeedTracker::default();
+
+    for _ in 0..3 {
+        tracker.update(&at_altitude(48.858_4, 100.0));
+    }
+    // Two jumps of about 50 km in a few milliseconds
+    for latitude in [49.3, 48.858_4] {
+        thread::sleep(Duration::from_millis(5));
+        tracker.update(&at_altitude(latitude, 500.0));
+    }
+
+    let summary = tracker.summary();
+    assert!(summary.elevation_gain_m().abs() < f64::EPSILON, "{summary}");
+    assert!(summary.elevation_loss_m().abs() < f64::EPSILON, "{summary}");
+}
+
+#[test]
+fn decoder_only_dates_fixes_with_their_own_rmc_sentence() {
+    let mut decoder = decoder();
+    let position = "4851.3960,N,00221.1320,E";
+
+    decoder.push(
+        sentence(&format!("GPRMC,235959.00,A,{position},0.00,,150624,,,A"))
+            .as_bytes(),
+    );
+    decoder.push(
+        sentence(&format!(
+            "GPGGA,000000.00,{position},1,08,0.9,35.0,M,47.0,M,,"
+        ))
+        .as_bytes(),
+    );
+    // The GGA fix at midnight is complete once the next fix starts, without
+    // the date of the RMC sentence from the day before
+    let reading = decoder
+        .push(
+            sentence(&format!(
+                "GPGGA,000001.00,{position},1,08,0.9,35.0,M,47.0,M,,"
+            ))
+            .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(reading.utc_s(), None);
+
+    let reading = decoder
+        .push(
+            sentence(&format!("GPRMC,000001.00,A,{position},0.00,,160624,,,A"))
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(reading.utc_s(), Some(1_718_496_001));
+}
@@ -0,0 +1,94 @@
+use esp_flow::{
+    color::{Palette, GREEN, RED},
+    logic::{next_state, DeviceNearby, State, Trigger, STATES},
+};
+
+#[test]
+fn button_toggles_power() {
+    assert_eq!(
+        next_state(&State::Off, &Trigger::ButtonPressed),
+        State::on()
+    );
+    for state in &STATES[1..] {
+        assert_eq!(next_state(state, &Trigger::ButtonPressed), State::Off);
+    }
+}
+
+#[test]
+fn devices_found_while_on_set_nearby_state() {
+    let active = State::On(Some(DeviceNearby::Active));
+    let inactive = State::On(Some(DeviceNearby::Inactive));
+    for state in &STATES[1..] {
+        assert_eq!(next_state(state, &Trigger::DeviceFoundActive), active);
+        assert_eq!(next_state(state, &Trigger::DeviceFoundInactive), inactive);
+        assert_eq!(next_state(state, &Trigger::DeviceNotFound), State::on());
+    }
+}
+
+#[test]
+fn devices_found_while_off_are_ignored() {
+    let triggers = [
+        Trigger::DeviceFoundActive,
+        Trigger::DeviceFoundInactive,
+        Trigger::DeviceNotFound,
+    ];
+    for trigger in &triggers {
+        assert_eq!(next_state(&State::Off, trigger), State::Off);
+    }
+}
+
+#[test]
+fn other_triggers_keep_state() {
+    let triggers = [
+        Trigger::TimerTicked,
+        Trigger::GpsDataAvailable,
+        Trigger::Heartbeat,
+        Trigger::LowBattery,
+        Trigger::GpsFixLost,
+        Trigger::MotionStarted,
+        Trigger::WifiDisconnected,
+        Trigger::ThreadFaulted,
+    ];
+    for state in &STATES {
+        for trigger in &triggers {
+            assert_eq!(next_state(state, trigger), *state);
+        }
+    }
+}
+
+#[test]
+fn scan_results_drive_a_session() {
+    let session = [
+        (Trigger::ButtonPressed, State::on()),
+        (
+            Trigger::DeviceFoundInactive,
+            State::On(Some(DeviceNearby::Inactive)),
+        ),
+        (
+            Trigger::DeviceFoundActive,
+            State::On(Some(DeviceNearby::Active)),
+        ),
+        (Trigger::TimerTicked, State::On(Some(DeviceNearby::Active))),
+        (Trigger::DeviceNotFound, State::on()),
+        (Trigger::ButtonPressed, State::Off),
+        (Trigger::DeviceFoundActive, State::Off),
+    ];
+    session
+        .iter()
+        .fold(State::Off, |state, (trigger, expected)| {
+            let next = next_state(&state, trigger);
+            assert_eq!(next, *expected, "{trigger:?} from {state:?}");
+            next
+        });
+}
+
+#[test]
+fn default_palette_maps_every_state() {
+    let palette = Palette::<State>::default();
+
+    palette.validate(&STATES).unwrap();
+    assert_eq!(palette.color(&State::Off), RED);
+    assert_eq!(palette.color(&State::on()), GREEN);
+    assert_eq!(palette.color(&State::On(Some(DeviceNearby::Active))), GREEN);
+    assert_eq!(palette.color(&State::On(Some(DeviceNearby::Inactive))), RED);
+}
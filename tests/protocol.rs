@@ -0,0 +1,76 @@
+use esp_flow::{
+    infra::State,
+    protocol::{advertised_name, crc8, pack, parse_name, unpack},
+};
+
+#[test]
+fn crc8_matches_known_values() {
+    assert_eq!(crc8(&[]), 0x00);
+    assert_eq!(crc8(b"123456789"), 0xF4);
+}
+
+#[test]
+fn parse_name_recovers_the_advertised_state() {
+    for app_name in ["esp-flow", "", "my-Active-app"] {
+        assert_eq!(
+            parse_name(&advertised_name::<()>(app_name, &State::on())),
+            Some(State::on())
+        );
+        assert_eq!(
+            parse_name(&advertised_name::<()>(app_name, &State::Off)),
+            Some(State::Off)
+        );
+    }
+}
+
+#[test]
+fn parse_name_ignores_other_devices() {
+    assert_eq!(parse_name(""), None);
+    assert_eq!(parse_name("esp-flow"), None);
+    assert_eq!(parse_name("esp-flow-active"), None);
+    assert_eq!(parse_name("esp-flow-Active-2"), None);
+}
+
+#[test]
+fn pack_frames_length_payload_and_crc() {
+    let frame = pack(&[0x01, 0x02, 0x03]).unwrap();
+
+    assert_eq!(frame.len(), 5);
+    assert_eq!(&frame[..4], &[3, 0x01, 0x02, 0x03]);
+    assert_eq!(frame[4], crc8(&frame[..4]));
+}
+
+#[test]
+fn unpack_round_trips_packed_payloads() {
+    for payload in [&[][..], &[0xAB], &[0x00; 29], &[0xFF; 255]] {
+        assert_eq!(unpack(&pack(payload).unwrap()).unwrap(), payload);
+    }
+}
+
+#[test]
+fn pack_rejects_payloads_longer_than_255_bytes() {
+    assert!(pack(&[0; 256]).is_err());
+}
+
+#[test]
+fn unpack_rejects_corrupted_frames() {
+    let frame = pack(&[0x10, 0x20, 0x30]).unwrap();
+
+    for i in 0..frame.len() {
+        let mut corrupted = frame.clone();
+        corrupted[i] ^= 0x01;
+        assert!(unpack(&corrupted).is_err(), "flipped byte {i}");
+    }
+}
+
+#[test]
+fn unpack_rejects_truncated_frames() {
+    let frame = pack(&[0x10, 0x20, 0x30]).unwrap();
+
+    assert!(unpack(&[]).is_err());
+    assert!(unpack(&frame[..1]).is_err());
+    assert!(unpack(&frame[..frame.len() - 1]).is_err());
+    let mut extended = frame.clone();
+    extended.push(0);
+    assert!(unpack(&extended).is_err());
+}
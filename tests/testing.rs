@@ -0,0 +1,77 @@
+use esp_flow::{
+    clock::Clock,
+    color::{ChannelOrder, GREEN, RED},
+    light::{Led, LedKind},
+    testing::{ClockCall, FakeClock, RecordingSink},
+};
+
+#[test]
+fn fake_clock_records_calls() {
+    let mut clock = FakeClock::new();
+    assert_eq!(clock.is_on(), None);
+
+    clock.on().unwrap();
+    clock.set_freq(4).unwrap();
+    clock.off().unwrap();
+
+    assert_eq!(
+        clock.calls(),
+        [ClockCall::On, ClockCall::SetFreq(4), ClockCall::Off]
+    );
+    assert_eq!(clock.is_on(), Some(false));
+}
+
+#[test]
+fn fake_clock_fires_ticks_while_on() {
+    let mut clock = FakeClock::new().with_freq(2);
+    clock.advance(1000);
+    assert_eq!(clock.process(), 0);
+
+    clock.on().unwrap();
+    clock.advance(250);
+    clock.advance(250);
+    clock.advance(1000);
+    assert_eq!(clock.process(), 3);
+    assert_eq!(clock.process(), 0);
+    assert_eq!(clock.now_ms(), 2500);
+}
+
+#[test]
+fn led_sends_packed_colors_to_sink() {
+    let mut led =
+        Led::new(RecordingSink::new(), ChannelOrder::Grb, LedKind::Rgb).unwrap();
+    assert_eq!(led.sink().words(), [0]);
+
+    led.set_color(RED).unwrap();
+    led.on().unwrap();
+    assert_eq!(led.sink().last(), Some(RED.pack(ChannelOrder::Grb)));
+
+    led.set_color(GREEN).unwrap();
+    led.off().unwrap();
+    assert_eq!(led.sink().last(), Some(0));
+    assert_eq!(led.current_color(), GREEN);
+}
+
+#[test]
+fn fake_clock_rejects_zero_frequency() {
+    let mut clock = FakeClock::new();
+    assert!(clock.set_freq(0).is_err());
+    assert!(clock.calls().is_empty());
+}
+
+#[test]
+fn fake_clock_counts_coalesced_ticks_as_missed() {
+    let mut clock = FakeClock::new().with_freq(10);
+    clock.on().unwrap();
+
+    clock.advance(100);
+    assert_eq!(clock.process(), 1);
+    assert_eq!(clock.missed(), 0);
+
+    clock.advance(300);
+    assert_eq!(clock.process(), 3);
+    clock.tick();
+    clock.tick();
+    assert_eq!(clock.process(), 2);
+    assert_eq!(clock.missed(), 3);
+}